@@ -0,0 +1,117 @@
+//! Stream account layout and vesting schedule math shared by the fee
+//! distributor's Streamflow client and the mock-streamflow program, so the two
+//! cannot drift apart.
+
+use anchor_lang::prelude::*;
+
+/// A Streamflow-style vesting schedule
+///
+/// Nothing unlocks before the cliff. At the cliff `cliff_amount` unlocks at
+/// once, then `amount_per_period` unlocks every `period` seconds. A `period`
+/// of 0 unlocks the post-cliff amount linearly up to `end_ts`. Everything is
+/// unlocked from `end_ts` on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VestingSchedule {
+    pub total: u64,
+    pub start_ts: i64,
+    pub end_ts: i64,
+    /// Cliff timestamp; values before `start_ts` (including 0) mean no cliff
+    pub cliff_ts: i64,
+    pub cliff_amount: u64,
+    /// Step length in seconds, 0 for a linear unlock
+    pub period: u64,
+    pub amount_per_period: u64,
+}
+
+impl VestingSchedule {
+    /// Amount unlocked at `timestamp`
+    pub fn unlocked_amount(&self, timestamp: i64) -> u64 {
+        if timestamp >= self.end_ts {
+            return self.total;
+        }
+
+        let cliff_ts = std::cmp::max(self.cliff_ts, self.start_ts);
+        if timestamp < cliff_ts {
+            return 0;
+        }
+
+        let elapsed = (timestamp - cliff_ts) as u128;
+        let vested_after_cliff = if self.period > 0 {
+            (elapsed / self.period as u128) * self.amount_per_period as u128
+        } else {
+            let remaining = self.total.saturating_sub(self.cliff_amount) as u128;
+            let duration = (self.end_ts - cliff_ts) as u128;
+            remaining * elapsed / duration
+        };
+
+        let unlocked = (self.cliff_amount as u128).saturating_add(vested_after_cliff);
+        std::cmp::min(unlocked, self.total as u128) as u64
+    }
+
+    /// Amount still locked at `timestamp`
+    pub fn locked_amount(&self, timestamp: i64) -> u64 {
+        self.total.saturating_sub(self.unlocked_amount(timestamp))
+    }
+}
+
+/// Stream account written by mock-streamflow and read by the distributor
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct StreamData {
+    pub initial_allocation: u64,
+    pub start_ts: i64,
+    pub end_ts: i64,
+    pub mint: Pubkey,
+    pub recipient: Pubkey,
+    /// Cancellation time, 0 if the stream was never cancelled
+    pub canceled_at: i64,
+    pub closed: bool,
+    /// Cliff timestamp, 0 for no cliff
+    pub cliff: i64,
+    pub cliff_amount: u64,
+    /// Unlock step in seconds, 0 for a linear unlock
+    pub period: u64,
+    pub amount_per_period: u64,
+}
+
+/// Whether a stream still vests
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamStatus {
+    Active,
+    Cancelled,
+    Closed,
+}
+
+impl StreamData {
+    pub const LEN: usize = 8 + 8 + 8 + 32 + 32 + 8 + 1 + 8 + 8 + 8 + 8;
+
+    pub fn vesting_schedule(&self) -> VestingSchedule {
+        VestingSchedule {
+            total: self.initial_allocation,
+            start_ts: self.start_ts,
+            end_ts: self.end_ts,
+            cliff_ts: self.cliff,
+            cliff_amount: self.cliff_amount,
+            period: self.period,
+            amount_per_period: self.amount_per_period,
+        }
+    }
+
+    /// Stream status at `timestamp`; a cancellation only applies from `canceled_at` on
+    pub fn status(&self, timestamp: i64) -> StreamStatus {
+        if self.closed {
+            StreamStatus::Closed
+        } else if self.canceled_at != 0 && timestamp >= self.canceled_at {
+            StreamStatus::Cancelled
+        } else {
+            StreamStatus::Active
+        }
+    }
+
+    /// Locked amount at `timestamp`; inactive streams have nothing locked
+    pub fn locked_amount(&self, timestamp: i64) -> u64 {
+        if self.status(timestamp) != StreamStatus::Active {
+            return 0;
+        }
+        self.vesting_schedule().locked_amount(timestamp)
+    }
+}
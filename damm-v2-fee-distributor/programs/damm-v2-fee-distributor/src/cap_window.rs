@@ -0,0 +1,47 @@
+use anchor_lang::prelude::*;
+
+/// Days of distribution totals a cap window keeps, and so the longest rolling window
+pub const MAX_CAP_WINDOW_DAYS: usize = 30;
+
+/// Rolling caps a config may enforce at once
+pub const MAX_ROLLING_CAPS: usize = 4;
+
+/// What happens to the part of a day's investor pool above its daily or rolling caps
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CapOverflowPolicy {
+    /// Held back and added to later days' investor pools, up to each day's cap
+    CarryForward,
+    /// Paid out with the creator remainder
+    Creator,
+    /// Left in the treasury
+    Retain,
+}
+
+/// Quote distributed to investors on one day
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CapWindowSlot {
+    pub day_id: i64,
+    pub distributed: u64,
+}
+
+impl CapWindowSlot {
+    pub const INIT_SPACE: usize = 8 + 8;
+}
+
+/// Add `amount` to `day_id`'s slot, reusing the slot of the day `MAX_CAP_WINDOW_DAYS` earlier
+pub fn record(slots: &mut [CapWindowSlot; MAX_CAP_WINDOW_DAYS], day_id: i64, amount: u64) {
+    let slot = &mut slots[day_id.rem_euclid(MAX_CAP_WINDOW_DAYS as i64) as usize];
+    if slot.day_id != day_id {
+        *slot = CapWindowSlot { day_id, distributed: 0 };
+    }
+    slot.distributed = slot.distributed.saturating_add(amount);
+}
+
+/// Quote distributed over the `window_days` days ending with `day_id`
+pub fn window_total(slots: &[CapWindowSlot; MAX_CAP_WINDOW_DAYS], day_id: i64, window_days: u16) -> u64 {
+    let first_day = day_id.saturating_sub(window_days as i64 - 1);
+    slots
+        .iter()
+        .filter(|slot| (first_day..=day_id).contains(&slot.day_id))
+        .fold(0u64, |total, slot| total.saturating_add(slot.distributed))
+}
@@ -1,143 +1,318 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
-
-/// cp-amm/DAMM v2 integration module
-pub mod cp_amm {
-    use super::*;
-
-    /// Claim fees from a DAMM v2 position
-    pub fn claim_fees(
-        ctx: Context<ClaimFees>,
-        position_id: Pubkey,
-    ) -> Result<ClaimResult> {
-        // TODO: Implement actual CPI to cp-amm
-        // This would:
-        // 1. Call cp-amm's claim_fees instruction
-        // 2. Transfer claimed tokens to the program's treasury
-        // 3. Return the amounts claimed for each token
-        
-        // For now, return mock values
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_spl::token_interface;
+
+use crate::{ErrorCode, INVESTOR_FEE_POS_OWNER_SEED, PROGRAM_AUTHORITY_SEED, VAULT_SEED};
+
+/// Fees claimed from a position, as they landed in the treasuries
+#[derive(Clone, Debug)]
+pub struct ClaimResult {
+    pub quote_amount: u64,
+    pub base_amount: u64,
+}
+
+/// What the crank does with base-token fees claimed from an honorary position
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BaseFeePolicy {
+    /// Fail the crank with `BaseFeeDetected`
+    Reject,
+    /// Send the base fees to the creator's base token account
+    RouteToCreator,
+    /// Swap the base fees to quote through the position's pool and distribute them
+    SwapToQuote,
+}
+
+/// Least a swap may return for `expected` quote under `max_slippage_bps`
+pub fn min_amount_out(expected: u64, max_slippage_bps: u16) -> u64 {
+    let kept_bps = 10_000u128.saturating_sub(max_slippage_bps as u128);
+    (expected as u128 * kept_bps / 10_000) as u64
+}
+
+/// The leading fields of a cp-amm pool account, after its 8-byte discriminator
+///
+/// Field order matches the mock-cp-amm program's `Pool`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PoolState {
+    pub authority: Pubkey,
+    pub quote_mint: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_vault: Pubkey,
+    pub base_vault: Pubkey,
+    pub sqrt_price_x64: u128,
+}
+
+/// The leading fields of a cp-amm position account, after its 8-byte discriminator
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PositionState {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub fee_quote_pending: u64,
+    pub fee_base_pending: u64,
+    pub liquidity_quote: u64,
+    pub liquidity_base: u64,
+}
+
+/// Accounts the cp-amm CPIs draw on
+///
+/// Fees are claimed into the config's treasuries and swaps are paid from and
+/// into them. Pools, pool authorities and vaults are looked up by key in
+/// `remaining_accounts`.
+pub struct CpAmmAccounts<'a, 'info> {
+    pub program: AccountInfo<'info>,
+    pub position_owner: AccountInfo<'info>,
+    pub position_owner_bump: u8,
+    /// The config whose program authority and treasuries these are
+    pub config: Pubkey,
+    pub program_authority: AccountInfo<'info>,
+    pub program_authority_bump: u8,
+    pub quote_mint: AccountInfo<'info>,
+    pub base_mint: AccountInfo<'info>,
+    pub quote_treasury: AccountInfo<'info>,
+    pub base_treasury: AccountInfo<'info>,
+    pub quote_token_program: AccountInfo<'info>,
+    pub base_token_program: AccountInfo<'info>,
+    pub remaining_accounts: &'a [AccountInfo<'info>],
+}
+
+impl<'info> CpAmmAccounts<'_, 'info> {
+    /// Read a pool account owned by the cp-amm program
+    pub fn pool_state(&self, pool: &AccountInfo) -> Result<PoolState> {
+        read_state(self.program.key, pool, "Pool")
+    }
+
+    /// Read an honorary position, which must be in `pool` and owned by the config's position owner PDA
+    pub fn position_state(&self, position: &AccountInfo, pool: &Pubkey) -> Result<PositionState> {
+        honorary_position_state(self.program.key, position, pool, self.position_owner.key)
+    }
+
+    /// Claim a position's pending fees into the treasuries, signed by the position owner PDA,
+    /// returning what landed in them
+    pub fn claim_fees(&self, position: &AccountInfo<'info>, pool: &Pubkey) -> Result<ClaimResult> {
+        let position_state = self.position_state(position, pool)?;
+        let pool = self.find(&position_state.pool)?;
+        let pool_state = self.pool_state(pool)?;
+        let quote_before = token_amount(&self.quote_treasury)?;
+        let base_before = token_amount(&self.base_treasury)?;
+
+        let owner_seeds: &[&[u8]] = &[
+            VAULT_SEED,
+            self.config.as_ref(),
+            INVESTOR_FEE_POS_OWNER_SEED,
+            &[self.position_owner_bump],
+        ];
+        self.invoke(
+            "claim_fees",
+            Vec::new(),
+            &[
+                (pool, false),
+                (self.find(&pool_state.authority)?, false),
+                (position, true),
+                (&self.position_owner, false),
+                (self.find(&pool_state.quote_vault)?, true),
+                (self.find(&pool_state.base_vault)?, true),
+                (&self.quote_treasury, true),
+                (&self.base_treasury, true),
+                (&self.quote_mint, false),
+                (&self.base_mint, false),
+                (&self.quote_token_program, false),
+                (&self.base_token_program, false),
+            ],
+            &self.position_owner,
+            owner_seeds,
+        )?;
+
         Ok(ClaimResult {
-            quote_amount: 1000000,
-            base_amount: 0, // Should always be 0 for quote-only positions
+            quote_amount: token_amount(&self.quote_treasury)?.saturating_sub(quote_before),
+            base_amount: token_amount(&self.base_treasury)?.saturating_sub(base_before),
+        })
+    }
+
+    /// Withdraw all of a position's liquidity into the treasuries, signed by the position owner PDA,
+    /// returning what landed in them
+    pub fn remove_all_liquidity(&self, position: &AccountInfo<'info>, pool: &Pubkey) -> Result<RemoveLiquidityResult> {
+        let position_state = self.position_state(position, pool)?;
+        let pool = self.find(&position_state.pool)?;
+        let pool_state = self.pool_state(pool)?;
+        let quote_before = token_amount(&self.quote_treasury)?;
+        let base_before = token_amount(&self.base_treasury)?;
+
+        let owner_seeds: &[&[u8]] = &[
+            VAULT_SEED,
+            self.config.as_ref(),
+            INVESTOR_FEE_POS_OWNER_SEED,
+            &[self.position_owner_bump],
+        ];
+        self.invoke(
+            "remove_all_liquidity",
+            Vec::new(),
+            &[
+                (pool, false),
+                (self.find(&pool_state.authority)?, false),
+                (position, true),
+                (&self.position_owner, false),
+                (self.find(&pool_state.quote_vault)?, true),
+                (self.find(&pool_state.base_vault)?, true),
+                (&self.quote_treasury, true),
+                (&self.base_treasury, true),
+                (&self.quote_mint, false),
+                (&self.base_mint, false),
+                (&self.quote_token_program, false),
+                (&self.base_token_program, false),
+            ],
+            &self.position_owner,
+            owner_seeds,
+        )?;
+
+        Ok(RemoveLiquidityResult {
+            quote_amount: token_amount(&self.quote_treasury)?.saturating_sub(quote_before),
+            base_amount: token_amount(&self.base_treasury)?.saturating_sub(base_before),
         })
     }
 
-    /// Create a new DAMM v2 position
-    pub fn create_position(
-        ctx: Context<CreatePosition>,
-        tick_lower: i32,
-        tick_upper: i32,
-    ) -> Result<Pubkey> {
-        // TODO: Implement actual CPI to cp-amm
-        // This would:
-        // 1. Call cp-amm's create_position instruction
-        // 2. Return the position ID
-        
-        // For now, return a mock position ID
-        Ok(Pubkey::new_unique())
+    /// Swap `amount_in` of the base treasury for quote through `pool`, returning the quote received
+    pub fn swap_base_for_quote(
+        &self,
+        pool: &AccountInfo<'info>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<u64> {
+        let pool_state = self.pool_state(pool)?;
+        let quote_before = token_amount(&self.quote_treasury)?;
+
+        let authority_seeds: &[&[u8]] = &[
+            PROGRAM_AUTHORITY_SEED,
+            self.config.as_ref(),
+            &[self.program_authority_bump],
+        ];
+        self.invoke(
+            "swap_base_for_quote",
+            (amount_in, minimum_amount_out).try_to_vec()?,
+            &[
+                (pool, false),
+                (self.find(&pool_state.authority)?, false),
+                (&self.program_authority, false),
+                (&self.base_treasury, true),
+                (&self.quote_treasury, true),
+                (self.find(&pool_state.quote_vault)?, true),
+                (self.find(&pool_state.base_vault)?, true),
+                (&self.quote_mint, false),
+                (&self.base_mint, false),
+                (&self.quote_token_program, false),
+                (&self.base_token_program, false),
+            ],
+            &self.program_authority,
+            authority_seeds,
+        )?;
+
+        Ok(token_amount(&self.quote_treasury)?.saturating_sub(quote_before))
+    }
+
+    /// Close a position with no fees pending and no liquidity, sending its rent to `rent_receiver`
+    pub fn close_position(
+        &self,
+        position: &AccountInfo<'info>,
+        pool: &Pubkey,
+        rent_receiver: &AccountInfo<'info>,
+    ) -> Result<()> {
+        self.position_state(position, pool)?;
+        let owner_seeds: &[&[u8]] = &[
+            VAULT_SEED,
+            self.config.as_ref(),
+            INVESTOR_FEE_POS_OWNER_SEED,
+            &[self.position_owner_bump],
+        ];
+        self.invoke(
+            "close_position",
+            Vec::new(),
+            &[(position, true), (&self.position_owner, false), (rent_receiver, true)],
+            &self.position_owner,
+            owner_seeds,
+        )
+    }
+
+    fn find(&self, key: &Pubkey) -> Result<&AccountInfo<'info>> {
+        self.remaining_accounts
+            .iter()
+            .find(|account| account.key == key)
+            .ok_or_else(|| error!(ErrorCode::MissingCpAmmAccounts))
     }
 
-    /// Validate that a position will only accrue quote fees
-    pub fn validate_quote_only_position(
-        pool_id: Pubkey,
-        tick_lower: i32,
-        tick_upper: i32,
-        current_tick: i32,
-    ) -> Result<bool> {
-        // TODO: Implement actual validation logic
-        // This would:
-        // 1. Analyze the pool's current price and tick range
-        // 2. Determine if the position will only accrue quote fees
-        // 3. Return true if quote-only, false otherwise
-        
-        // For now, return true (assume quote-only)
-        Ok(true)
+    /// Invoke cp-amm instruction `name`, with `signer` signing through `signer_seeds`
+    fn invoke(
+        &self,
+        name: &str,
+        args: Vec<u8>,
+        accounts: &[(&AccountInfo<'info>, bool)],
+        signer: &AccountInfo<'info>,
+        signer_seeds: &[&[u8]],
+    ) -> Result<()> {
+        let mut data = discriminator("global", name).to_vec();
+        data.extend(args);
+        let instruction = Instruction {
+            program_id: *self.program.key,
+            accounts: accounts
+                .iter()
+                .map(|(account, is_writable)| AccountMeta {
+                    pubkey: *account.key,
+                    is_signer: account.key == signer.key,
+                    is_writable: *is_writable,
+                })
+                .collect(),
+            data,
+        };
+        let account_infos: Vec<AccountInfo<'info>> = accounts
+            .iter()
+            .map(|(account, _)| (*account).clone())
+            .chain(std::iter::once(self.program.clone()))
+            .collect();
+        invoke_signed(&instruction, &account_infos, &[signer_seeds])?;
+        Ok(())
     }
 }
 
-/// Result of claiming fees from a position
-#[derive(Clone, Debug)]
-pub struct ClaimResult {
-    pub quote_amount: u64,
-    pub base_amount: u64,
+/// Read a position of `program`, which must be in `pool` and owned by `owner`
+pub fn honorary_position_state(
+    program: &Pubkey,
+    position: &AccountInfo,
+    pool: &Pubkey,
+    owner: &Pubkey,
+) -> Result<PositionState> {
+    let position_state: PositionState = read_state(program, position, "Position")?;
+    require!(
+        position_state.pool == *pool && position_state.owner == *owner,
+        ErrorCode::InvalidHonoraryPosition
+    );
+    Ok(position_state)
 }
 
-/// Account structure for claiming fees
-#[derive(Accounts)]
-pub struct ClaimFees<'info> {
-    /// The position account
-    /// CHECK: Validated by cp-amm program
-    pub position: AccountInfo<'info>,
-    
-    /// The position owner
-    /// CHECK: Must be the program's PDA
-    pub position_owner: AccountInfo<'info>,
-    
-    /// The pool account
-    /// CHECK: Validated by cp-amm program
-    pub pool: AccountInfo<'info>,
-    
-    /// The quote token vault
-    /// CHECK: Validated by cp-amm program
-    pub quote_vault: AccountInfo<'info>,
-    
-    /// The base token vault
-    /// CHECK: Validated by cp-amm program
-    pub base_vault: AccountInfo<'info>,
-    
-    /// The program's quote treasury
-    #[account(mut)]
-    pub quote_treasury: Account<'info, TokenAccount>,
-    
-    /// The program's base treasury
-    #[account(mut)]
-    pub base_treasury: Account<'info, TokenAccount>,
-    
-    /// The cp-amm program
-    /// CHECK: Validated by the caller
-    pub cp_amm_program: AccountInfo<'info>,
-    
-    pub token_program: Program<'info, Token>,
+/// Read account `name` of `program`, checking its owner and discriminator
+fn read_state<T: AnchorDeserialize>(program: &Pubkey, account: &AccountInfo, name: &str) -> Result<T> {
+    require_keys_eq!(*account.owner, *program, ErrorCode::InvalidCpAmmAccount);
+    let data = account.try_borrow_data()?;
+    require!(
+        data.len() >= 8 && data[..8] == discriminator("account", name),
+        ErrorCode::InvalidCpAmmAccount
+    );
+    Ok(T::deserialize(&mut &data[8..])?)
 }
 
-/// Account structure for creating a position
-#[derive(Accounts)]
-pub struct CreatePosition<'info> {
-    /// The pool account
-    /// CHECK: Validated by cp-amm program
-    pub pool: AccountInfo<'info>,
-    
-    /// The position owner
-    /// CHECK: Must be the program's PDA
-    pub position_owner: AccountInfo<'info>,
-    
-    /// The payer for position creation
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    
-    /// The cp-amm program
-    /// CHECK: Validated by the caller
-    pub cp_amm_program: AccountInfo<'info>,
-    
-    pub system_program: Program<'info, System>,
+/// Anchor's 8-byte discriminator for `namespace:name`
+fn discriminator(namespace: &str, name: &str) -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(format!("{namespace}:{name}").as_bytes()).to_bytes()[..8]);
+    discriminator
 }
 
-/// Error codes for cp-amm operations
-#[error_code]
-pub enum CpAmmError {
-    #[msg("Invalid pool configuration")]
-    InvalidPoolConfig,
-    #[msg("Position creation failed")]
-    PositionCreationFailed,
-    #[msg("Fee claim failed")]
-    FeeClaimFailed,
-    #[msg("Position not found")]
-    PositionNotFound,
-    #[msg("Insufficient liquidity")]
-    InsufficientLiquidity,
-    #[msg("Invalid tick range")]
-    InvalidTickRange,
-    #[msg("Position not quote-only")]
-    PositionNotQuoteOnly,
-}
\ No newline at end of file
+fn token_amount(token_account: &AccountInfo) -> Result<u64> {
+    let account = token_interface::TokenAccount::try_deserialize(&mut &token_account.data.borrow()[..])?;
+    Ok(account.amount)
+}
+
+/// Result of removing all liquidity from a position
+#[derive(Clone, Debug)]
+pub struct RemoveLiquidityResult {
+    pub quote_amount: u64,
+    pub base_amount: u64,
+}
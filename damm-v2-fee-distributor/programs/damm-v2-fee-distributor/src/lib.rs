@@ -1,13 +1,30 @@
+// The `#[program]` macro expands to `AccountInfo::realloc`, deprecated in newer Solana crates
+#![allow(deprecated)]
+
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_lang::system_program;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 use anchor_spl::associated_token::AssociatedToken;
-use spl_associated_token_account::get_associated_token_address;
-
-mod streamflow;
-mod cp_amm;
 
-use streamflow::{StreamflowClient, MockStreamflowClient};
-use cp_amm::cp_amm;
+pub mod mock_streamflow;
+pub mod cp_amm;
+pub mod lock_provider;
+pub mod quote_mint;
+pub mod weighting;
+pub mod payout_cap;
+pub mod payout;
+pub mod price_source;
+pub mod cap_window;
+
+use cap_window::{CapOverflowPolicy, CapWindowSlot};
+use cp_amm::{BaseFeePolicy, CpAmmAccounts};
+use lock_provider::{LockProviderKind, LockState, LockStatus};
+use payout::PageTerms;
+use payout_cap::InvestorCapPolicy;
+use price_source::{Price, PriceSource, PythPriceSource};
+use weighting::{Weighting, WeightingMode};
 
 declare_id!("11111111111111111111111111111112");
 
@@ -16,10 +33,55 @@ pub const VAULT_SEED: &[u8] = b"vault";
 pub const CONFIG_SEED: &[u8] = b"investor_fee_config";
 pub const PROGRESS_SEED: &[u8] = b"investor_fee_progress";
 pub const INVESTOR_FEE_POS_OWNER_SEED: &[u8] = b"investor_fee_pos_owner";
+pub const PROGRAM_AUTHORITY_SEED: &[u8] = b"program_authority";
+pub const DAY_SUMMARY_SEED: &[u8] = b"investor_fee_day_summary";
+pub const PROGRESS_HISTORY_SEED: &[u8] = b"investor_fee_progress_history";
+pub const PROTOCOL_CONFIG_SEED: &[u8] = b"protocol_config";
+pub const PROTOCOL_FEE_VAULT_SEED: &[u8] = b"protocol_fee_vault";
+pub const CAP_WINDOW_SEED: &[u8] = b"cap_window";
 
 // 24 hours in seconds
 pub const DAY_IN_SECONDS: i64 = 86400;
 
+// Finalized progress accounts can be closed once they are this many days old
+pub const DEFAULT_PROGRESS_RETENTION_DAYS: u16 = 30;
+
+// Honorary positions a config can register, including the one created at initialization
+pub const MAX_HONORARY_POSITIONS: usize = 4;
+
+// Recipients a creator remainder can be split between
+pub const MAX_REMAINDER_RECIPIENTS: usize = 8;
+
+// Points in an investor fee share schedule
+pub const MAX_FEE_SHARE_POINTS: usize = 12;
+
+// Upper bound on the protocol fee skimmed off each day's claim
+pub const MAX_PROTOCOL_FEE_BPS: u16 = 1000;
+
+// Basis point denominator
+pub const BPS_DENOMINATOR: u64 = 10000;
+
+/// Default slippage allowed when swapping base fees to quote (1%)
+pub const DEFAULT_MAX_SWAP_SLIPPAGE_BPS: u16 = 100;
+
+/// Pause bits on `ConfigAccount::paused` and `ProtocolConfig::paused`
+pub const PAUSE_CLAIM: u8 = 1 << 0;
+pub const PAUSE_INVESTOR_PAYOUTS: u8 = 1 << 1;
+pub const PAUSE_CREATOR_PAYOUTS: u8 = 1 << 2;
+pub const PAUSE_ALL: u8 = PAUSE_CLAIM | PAUSE_INVESTOR_PAYOUTS | PAUSE_CREATOR_PAYOUTS;
+
+/// How long a config must stay fully paused before its treasury can be emergency-withdrawn
+pub const EMERGENCY_WITHDRAW_TIMELOCK_SECS: i64 = 3 * DAY_IN_SECONDS;
+
+/// How long after `request_decommission` a config can be decommissioned
+pub const DECOMMISSION_NOTICE_SECS: i64 = 3 * DAY_IN_SECONDS;
+
+/// Oldest oracle price a USD daily cap accepts by default
+pub const DEFAULT_MAX_PRICE_AGE_SECS: u32 = 60;
+
+/// Widest oracle confidence interval a USD daily cap accepts by default (2% of the price)
+pub const DEFAULT_MAX_PRICE_CONFIDENCE_BPS: u16 = 200;
+
 #[program]
 pub mod damm_v2_fee_distributor {
     use super::*;
@@ -42,6 +104,20 @@ pub mod damm_v2_fee_distributor {
         config.tick_lower = tick_lower;
         config.tick_upper = tick_upper;
         config.creation_ts = clock.unix_timestamp;
+        config.authority = ctx.accounts.payer.key();
+        config.progress_retention_days = DEFAULT_PROGRESS_RETENTION_DAYS;
+        // No lock program until the authority sets one with `update_config`
+        config.lock_provider = LockProviderKind::MockStreamflow;
+        config.lock_program = Pubkey::default();
+        config.weighting_mode = WeightingMode::LockedAmount;
+        config.investor_cap_policy = InvestorCapPolicy::Redistribute;
+        config.max_price_age_secs = DEFAULT_MAX_PRICE_AGE_SECS;
+        config.max_price_confidence_bps = DEFAULT_MAX_PRICE_CONFIDENCE_BPS;
+        config.cap_overflow_policy = CapOverflowPolicy::Creator;
+        config.guardian = ctx.accounts.payer.key();
+        config.creator = ctx.accounts.payer.key();
+        config.base_fee_policy = BaseFeePolicy::Reject;
+        config.max_swap_slippage_bps = DEFAULT_MAX_SWAP_SLIPPAGE_BPS;
         config.bump = ctx.bumps.config;
 
         // Validate that this position will only accrue quote fees
@@ -52,6 +128,9 @@ pub mod damm_v2_fee_distributor {
             ErrorCode::InvalidTickRange
         );
 
+        // Only quote mints the payout path can move tokens for are accepted
+        quote_mint::validate_quote_mint(&ctx.accounts.quote_mint.to_account_info())?;
+
         emit!(HonoraryPositionInitialized {
             pool: pool_id,
             position_id: ctx.accounts.position.key(),
@@ -62,56 +141,310 @@ pub mod damm_v2_fee_distributor {
         Ok(())
     }
 
+    /// Register another honorary position with the same quote mint (config authority only)
+    pub fn add_honorary_position(
+        ctx: Context<AddHonoraryPosition>,
+        pool_id: Pubkey,
+        tick_lower: i32,
+        tick_upper: i32,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let position = ctx.accounts.position.key();
+        require!(!config.closed, ErrorCode::ConfigClosed);
+
+        require!(
+            tick_lower < tick_upper,
+            ErrorCode::InvalidTickRange
+        );
+        require!(
+            config.honorary_positions().all(|(registered, _)| registered != position),
+            ErrorCode::DuplicateHonoraryPosition
+        );
+        require!(
+            config.additional_positions.len() + 1 < MAX_HONORARY_POSITIONS,
+            ErrorCode::TooManyHonoraryPositions
+        );
+        // The position must already be the config's, in the pool it is registered with
+        cp_amm::honorary_position_state(
+            ctx.accounts.cp_amm_program.key,
+            &ctx.accounts.position,
+            &pool_id,
+            ctx.accounts.investor_fee_position_owner_pda.key,
+        )?;
+
+        config.additional_positions.push(HonoraryPosition {
+            position,
+            pool: pool_id,
+            tick_lower,
+            tick_upper,
+        });
+
+        emit!(HonoraryPositionInitialized {
+            pool: pool_id,
+            position_id: position,
+            owner_pda: ctx.accounts.investor_fee_position_owner_pda.key(),
+            quote_mint: config.quote_mint,
+        });
+
+        Ok(())
+    }
+
+    /// Update mutable config parameters (config authority only)
+    pub fn update_config(ctx: Context<UpdateConfig>, params: ConfigUpdateParams) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+
+        if let Some(progress_retention_days) = params.progress_retention_days {
+            config.progress_retention_days = progress_retention_days;
+        }
+        if let Some(crank_tip_bps) = params.crank_tip_bps {
+            require!(
+                crank_tip_bps as u64 <= BPS_DENOMINATOR,
+                ErrorCode::InvalidBasisPoints
+            );
+            config.crank_tip_bps = crank_tip_bps;
+        }
+        if let Some(crank_tip_max) = params.crank_tip_max {
+            config.crank_tip_max = crank_tip_max;
+        }
+        if let Some(lock_provider) = params.lock_provider {
+            config.lock_provider = lock_provider;
+        }
+        if let Some(lock_program) = params.lock_program {
+            config.lock_program = lock_program;
+        }
+        if let Some(max_investor_share_bps) = params.max_investor_share_bps {
+            require!(
+                max_investor_share_bps == 0
+                    || (payout_cap::MIN_INVESTOR_CAP_BPS..=BPS_DENOMINATOR as u16)
+                        .contains(&max_investor_share_bps),
+                ErrorCode::InvalidInvestorCap
+            );
+            config.max_investor_share_bps = max_investor_share_bps;
+        }
+        if let Some(investor_cap_policy) = params.investor_cap_policy {
+            config.investor_cap_policy = investor_cap_policy;
+        }
+        if let Some(usd_daily_cap) = params.usd_daily_cap {
+            config.usd_daily_cap = usd_daily_cap;
+        }
+        if let Some(price_feed) = params.price_feed {
+            config.price_feed = price_feed;
+        }
+        if let Some(oracle_program) = params.oracle_program {
+            config.oracle_program = oracle_program;
+        }
+        if let Some(max_price_age_secs) = params.max_price_age_secs {
+            config.max_price_age_secs = max_price_age_secs;
+        }
+        if let Some(max_price_confidence_bps) = params.max_price_confidence_bps {
+            require!(
+                max_price_confidence_bps as u64 <= BPS_DENOMINATOR,
+                ErrorCode::InvalidBasisPoints
+            );
+            config.max_price_confidence_bps = max_price_confidence_bps;
+        }
+        if let Some(guardian) = params.guardian {
+            config.guardian = guardian;
+        }
+        if let Some(creator) = params.creator {
+            config.creator = creator;
+        }
+        if let Some(base_fee_policy) = params.base_fee_policy {
+            config.base_fee_policy = base_fee_policy;
+        }
+        if let Some(max_swap_slippage_bps) = params.max_swap_slippage_bps {
+            require!(
+                max_swap_slippage_bps as u64 <= BPS_DENOMINATOR,
+                ErrorCode::InvalidBasisPoints
+            );
+            config.max_swap_slippage_bps = max_swap_slippage_bps;
+        }
+        if let Some(base_price_feed) = params.base_price_feed {
+            config.base_price_feed = base_price_feed;
+        }
+        if let Some(creator_base_ata) = params.creator_base_ata {
+            config.creator_base_ata = creator_base_ata;
+        }
+        if let Some(cp_amm_program) = params.cp_amm_program {
+            config.cp_amm_program = cp_amm_program;
+        }
+        if let Some(quote_sweep_recipient) = params.quote_sweep_recipient {
+            config.quote_sweep_recipient = quote_sweep_recipient;
+        }
+        if let Some(base_sweep_recipient) = params.base_sweep_recipient {
+            config.base_sweep_recipient = base_sweep_recipient;
+        }
+        if let Some(recovery_ata) = params.recovery_ata {
+            // The recovery account cannot be swapped once an emergency is under way
+            require!(config.paused == 0, ErrorCode::Paused);
+            config.recovery_ata = recovery_ata;
+        }
+        if let Some(rolling_caps) = params.rolling_caps {
+            rolling_caps.validate()?;
+            config.rolling_caps = rolling_caps;
+        }
+        if let Some(cap_overflow_policy) = params.cap_overflow_policy {
+            config.cap_overflow_policy = cap_overflow_policy;
+        }
+        if let Some(weighting_mode) = params.weighting_mode {
+            config.weighting_mode = weighting_mode;
+        }
+        if let Some(fee_share_schedule) = params.fee_share_schedule {
+            fee_share_schedule.validate()?;
+            config.fee_share_schedule = fee_share_schedule;
+        }
+        if let Some(remainder_split) = params.remainder_split {
+            remainder_split.validate()?;
+            config.remainder_split = remainder_split;
+        }
+
+        Ok(())
+    }
+
     /// Permissionless 24h distribution crank with pagination
-    pub fn crank_distribute_page(
-        ctx: Context<CrankDistributePage>,
+    pub fn crank_distribute_page<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CrankDistributePage<'info>>,
         page_investors: Vec<InvestorRecord>,
         y0: u64,
         investor_fee_share_bps: u16,
         optional_daily_cap_lamports: Option<u64>,
         min_payout_lamports: u64,
-        is_final_page: bool,
     ) -> Result<()> {
         let clock = Clock::get()?;
         let current_time = clock.unix_timestamp;
         let day_id = current_time / DAY_IN_SECONDS;
 
-        // Calculate progress PDA for this day
-        let progress_pda = Self::get_progress_pda(day_id)?;
+        // Load today's progress account, created and paid for by the cranker if needed
+        let mut progress = load_or_create_progress(
+            &ctx.accounts.cranker,
+            &ctx.accounts.progress,
+            &ctx.accounts.system_program,
+            &ctx.accounts.config.key(),
+            day_id,
+        )?;
+
+        // A decommissioned config only pays out the day of its final claim
         require!(
-            ctx.accounts.progress.key() == progress_pda,
-            ErrorCode::InvalidProgressAccount
+            !ctx.accounts.config.closed || progress.last_distribution_ts != 0,
+            ErrorCode::ConfigClosed
         );
 
-        // Check if progress account exists and initialize if needed
-        let mut progress = if ctx.accounts.progress.data_is_empty() {
-            // Initialize new progress account
-            let mut progress_data = ProgressAccount {
-                day_id,
-                last_distribution_ts: 0,
-                claimed_quote_for_day: 0,
-                cumulative_distributed_today: 0,
-                carry_over: 0,
-                pagination_cursor: 0,
-            };
-            progress_data
-        } else {
-            // Load existing progress account
-            ProgressAccount::try_from_slice(&ctx.accounts.progress.data.borrow())?
-        };
+        // Once the day is closed no further pages may be paid out
+        require!(!progress.finalized, ErrorCode::DayAlreadyFinalized);
+        require_not_paused(&ctx.accounts.config, &ctx.accounts.protocol_config, PAUSE_INVESTOR_PAYOUTS)?;
         
         if progress.last_distribution_ts == 0 {
-            // First call of the day - claim fees
-            let claimed_quote = Self::claim_fees_from_damm(ctx.accounts)?;
-            
-            // Validate quote-only: check that no base tokens were claimed
+            // First call of the day - claim fees from every honorary position, at most once per day
+            require_not_paused(&ctx.accounts.config, &ctx.accounts.protocol_config, PAUSE_CLAIM)?;
+            require!(
+                day_id > ctx.accounts.config.last_claimed_day_id,
+                ErrorCode::DayGateNotPassed
+            );
+            let cp_amm = crank_cp_amm_accounts(
+                ctx.accounts,
+                ctx.remaining_accounts,
+                ctx.bumps.investor_fee_position_owner_pda,
+                ctx.bumps.program_authority,
+            )?;
+            let mut position_claims = Vec::with_capacity(MAX_HONORARY_POSITIONS);
+            for (position, pool) in ctx.accounts.config.honorary_positions() {
+                let position_info = if position == ctx.accounts.position.key() {
+                    &ctx.accounts.position
+                } else {
+                    ctx.remaining_accounts
+                        .iter()
+                        .find(|account| account.key == &position)
+                        .ok_or(ErrorCode::MissingHonoraryPosition)?
+                };
+                // Measured in the treasuries, so net of any transfer fee withheld on the way
+                let claim = cp_amm.claim_fees(position_info, &pool)?;
+                // Each position must stay quote-only unless base fees have somewhere to go
+                require!(
+                    claim.base_amount == 0 || ctx.accounts.config.base_fee_policy != BaseFeePolicy::Reject,
+                    ErrorCode::BaseFeeDetected
+                );
+
+                position_claims.push(PositionFeeClaim {
+                    position,
+                    claimed_quote: claim.quote_amount,
+                    claimed_base: claim.base_amount,
+                });
+            }
+            let claimed_quote = position_claims
+                .iter()
+                .try_fold(0u64, |total, claim| total.checked_add(claim.claimed_quote))
+                .ok_or(ErrorCode::MathOverflow)?;
+            let claimed_base = position_claims
+                .iter()
+                .try_fold(0u64, |total, claim| total.checked_add(claim.claimed_base))
+                .ok_or(ErrorCode::MathOverflow)?;
+
+            // Base fees are rejected, routed to the creator or swapped to quote by config
+            let swapped_quote = settle_base_fees(
+                ctx.accounts,
+                &cp_amm,
+                ctx.remaining_accounts,
+                ctx.bumps.program_authority,
+                claimed_base,
+            )?;
+            let claimed_quote = claimed_quote
+                .checked_add(swapped_quote)
+                .ok_or(ErrorCode::MathOverflow)?;
+
             require!(
                 claimed_quote > 0,
                 ErrorCode::InsufficientClaimedQuote
             );
 
+            // Skim the protocol fee off the top of the claim into the fee vault
+            let protocol_fee_bps = ctx.accounts.protocol_config.protocol_fee_bps;
+            let protocol_fee = ctx.accounts.protocol_config.protocol_fee_for(claimed_quote);
+            if protocol_fee > 0 {
+                let fee = transfer_from_treasury(
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.program_quote_treasury,
+                    &ctx.accounts.quote_mint,
+                    &ctx.accounts.program_authority,
+                    &ctx.accounts.protocol_fee_vault.to_account_info(),
+                    ctx.remaining_accounts,
+                    &ctx.accounts.config.key(),
+                    ctx.bumps.program_authority,
+                    protocol_fee,
+                )?;
+                progress.transfer_fees_withheld = progress.transfer_fees_withheld.saturating_add(fee);
+            }
+
+            // Pay the cranker's tip off the top of the claim
+            let crank_tip = ctx.accounts.config.crank_tip_for(claimed_quote);
+            if crank_tip > 0 {
+                let cranker_quote_ata = ctx
+                    .accounts
+                    .cranker_quote_ata
+                    .as_ref()
+                    .ok_or(ErrorCode::MissingCrankerQuoteAta)?;
+                let fee = transfer_from_treasury(
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.program_quote_treasury,
+                    &ctx.accounts.quote_mint,
+                    &ctx.accounts.program_authority,
+                    &cranker_quote_ata.to_account_info(),
+                    ctx.remaining_accounts,
+                    &ctx.accounts.config.key(),
+                    ctx.bumps.program_authority,
+                    crank_tip,
+                )?;
+                progress.transfer_fees_withheld = progress.transfer_fees_withheld.saturating_add(fee);
+            }
+
+            // The day stays open until it is finalized
+            let config = &mut ctx.accounts.config;
+            config.open_days = config.open_days.saturating_add(1);
+            config.last_claimed_day_id = day_id;
+
             // Store claimed amount for the day
             progress.claimed_quote_for_day = claimed_quote;
+            progress.crank_tip = crank_tip;
+            progress.protocol_fee = protocol_fee;
             progress.last_distribution_ts = current_time;
             progress.day_id = day_id;
             progress.cumulative_distributed_today = 0;
@@ -121,294 +454,1789 @@ pub mod damm_v2_fee_distributor {
             emit!(QuoteFeesClaimed {
                 day_id,
                 claimed_quote,
+                crank_tip,
+                cranker: ctx.accounts.cranker.key(),
+                position_claims,
+                protocol_fee,
+                protocol_fee_bps,
+                claimed_base,
+                swapped_quote,
             });
-        } else {
-            // Ensure we're still in the same day
-            require!(
-                progress.day_id == day_id,
-                ErrorCode::DayGateNotPassed
-            );
         }
 
-        // Calculate locked total from all investors on this page
-        let mut locked_total = 0u64;
-        for investor in &page_investors {
-            let locked_amount = Self::get_locked_amount(
-                &ctx.accounts.streamflow_program,
-                &investor.stream_pubkey,
-                current_time,
-            )?;
-            locked_total = locked_total.saturating_add(locked_amount);
+        // On a tallied day the pages must walk the tallied investors in the same order
+        if progress.investors_tallied > 0 {
+            fold_investor_keys(&mut progress.payout_cursor, &mut progress.payout_digest, &page_investors)?;
         }
 
-        // Calculate eligible investor share
-        let f_locked = locked_total.checked_div(y0).unwrap_or(0);
-        let eligible_investor_share_bps = std::cmp::min(
-            investor_fee_share_bps,
-            (f_locked * 10000).checked_div(y0).unwrap_or(0) as u16,
-        );
-
-        let investor_fee_quote = progress.claimed_quote_for_day
-            .checked_mul(eligible_investor_share_bps as u64)
-            .unwrap_or(0)
-            .checked_div(10000)
-            .unwrap_or(0);
+        // Read every lock on this page once, at the day's snapshot instant, so
+        // all pages of the day weight investors against the same point in time
+        let page_locks = page_investors
+            .iter()
+            .map(|investor| {
+                read_investor_lock(
+                    &ctx.accounts.config,
+                    ctx.remaining_accounts,
+                    investor,
+                    progress.snapshot_ts,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let locked_total = page_locks
+            .iter()
+            .fold(0u64, |total, lock| total.saturating_add(lock.locked_amount));
+
+        // Split the page's payout by the config's weighting mode
+        let page_weights: Vec<u128> = page_locks
+            .iter()
+            .map(|lock| ctx.accounts.config.weighting_mode.weight(lock, progress.snapshot_ts))
+            .collect();
+
+        // The config's share schedule, evaluated at the day's snapshot, replaces the flat share
+        let investor_fee_share_bps = ctx
+            .accounts
+            .config
+            .fee_share_schedule
+            .share_bps_at(progress.snapshot_ts.saturating_sub(ctx.accounts.config.creation_ts))
+            .unwrap_or(investor_fee_share_bps);
+
+        // With a per-investor cap every page must see the same day-wide totals,
+        // so the day must be tallied before its first payout page
+        let config = &ctx.accounts.config;
+        if config.max_investor_share_bps > 0 {
+            require!(progress.investors_tallied > 0, ErrorCode::InvestorTallyRequired);
+        }
 
-        // Apply daily cap if provided
-        let daily_cap_net = if let Some(cap) = optional_daily_cap_lamports {
-            std::cmp::max(0, cap.saturating_sub(progress.cumulative_distributed_today))
+        // A USD daily cap is converted to quote at the oracle price and
+        // applies alongside any quote-denominated cap passed by the cranker
+        let usd_cap_quote = if config.usd_daily_cap > 0 {
+            let price = read_oracle_price(config, ctx.accounts.price_feed.as_ref(), current_time)?;
+            Some(price.usd_to_quote(config.usd_daily_cap, ctx.accounts.quote_mint.decimals)?)
         } else {
-            investor_fee_quote
+            None
+        };
+        let daily_cap = match (optional_daily_cap_lamports, usd_cap_quote) {
+            (Some(quote_cap), Some(usd_cap)) => Some(std::cmp::min(quote_cap, usd_cap)),
+            (quote_cap, usd_cap) => quote_cap.or(usd_cap),
         };
-        let final_investor_fee_quote = std::cmp::min(investor_fee_quote, daily_cap_net);
 
-        // Distribute to investors on this page
-        let mut page_total_distributed = 0u64;
-        let mut dust_carry = 0u64;
+        // A cranker's cap below what the day has already paid can't be honoured
+        if let Some(quote_cap) = optional_daily_cap_lamports {
+            require!(
+                quote_cap >= progress.cumulative_distributed_today,
+                ErrorCode::CapExceeded
+            );
+        }
 
-        for investor in &page_investors {
-            let locked_amount = Self::get_locked_amount(
-                &ctx.accounts.streamflow_program,
-                &investor.stream_pubkey,
-                current_time,
-            )?;
+        // Every page of a day under rolling caps counts against the window
+        require!(
+            config.rolling_caps.caps.is_empty() || ctx.accounts.cap_window.is_some(),
+            ErrorCode::MissingCapWindow
+        );
+        let window_room = match ctx.accounts.cap_window.as_ref() {
+            Some(cap_window) if !config.rolling_caps.caps.is_empty() => {
+                config.rolling_caps.room(cap_window, day_id)
+            }
+            _ => u64::MAX,
+        };
 
-            if locked_amount == 0 {
-                continue;
+        let first_page = progress.pagination_cursor == 0;
+        let page = payout::pay_page(
+            &mut progress,
+            &PageTerms {
+                weights: &page_weights,
+                locked_total,
+                y0,
+                investor_fee_share_bps,
+                daily_cap,
+                max_investor_share_bps: config.max_investor_share_bps,
+                investor_cap_policy: config.investor_cap_policy,
+                held_back: ctx.accounts.cap_window.as_ref().map_or(0, |cap_window| cap_window.held_back),
+                window_room,
+                min_payout: min_payout_lamports,
+            },
+        )?;
+        // The first page draws its reserve from the overflow held back on earlier days
+        if first_page {
+            if let Some(cap_window) = ctx.accounts.cap_window.as_mut() {
+                cap_window.held_back -= progress.held_back_reserved;
             }
+        }
 
-            let weight = locked_amount
-                .checked_mul(final_investor_fee_quote)
-                .unwrap_or(0)
-                .checked_div(locked_total)
-                .unwrap_or(0);
+        // Distribute to investors on this page
+        let mut page_transfer_fees = 0u64;
+        for ((investor, lock), &payout) in page_investors.iter().zip(&page_locks).zip(&page.payouts) {
+            let status = lock.status;
+            if status != LockStatus::Active {
+                // Allocation was clawed back or moved - flag it so the investor can be dropped
+                emit!(InvestorStreamInactive {
+                    day_id,
+                    stream: investor.stream_pubkey,
+                    investor_quote_ata: investor.investor_quote_ata,
+                    status,
+                });
+            }
 
-            if weight >= min_payout_lamports {
+            if payout > 0 {
                 // Transfer to investor's ATA
-                Self::transfer_to_investor(
+                let investor_quote_ata = find_remaining_account(
+                    ctx.remaining_accounts,
+                    &investor.investor_quote_ata,
+                )?;
+                let fee = transfer_from_treasury(
                     &ctx.accounts.token_program,
                     &ctx.accounts.program_quote_treasury,
-                    &investor.investor_quote_ata,
-                    weight,
+                    &ctx.accounts.quote_mint,
+                    &ctx.accounts.program_authority,
+                    investor_quote_ata,
+                    ctx.remaining_accounts,
+                    &ctx.accounts.config.key(),
+                    ctx.bumps.program_authority,
+                    payout,
                 )?;
-                page_total_distributed = page_total_distributed.saturating_add(weight);
-            } else {
-                dust_carry = dust_carry.saturating_add(weight);
+                page_transfer_fees = page_transfer_fees.saturating_add(fee);
             }
         }
+        let page_total_distributed = page.distributed;
+
+        // Record the page in the rolling window
+        if let Some(cap_window) = ctx.accounts.cap_window.as_mut() {
+            cap_window.record(day_id, page_total_distributed);
+        }
 
-        // Update progress
-        progress.cumulative_distributed_today = progress
-            .cumulative_distributed_today
-            .saturating_add(page_total_distributed);
-        progress.carry_over = progress.carry_over.saturating_add(dust_carry);
-        progress.pagination_cursor = progress.pagination_cursor.saturating_add(1);
+        progress.transfer_fees_withheld = progress
+            .transfer_fees_withheld
+            .saturating_add(page_transfer_fees);
 
         emit!(InvestorPayoutPage {
             day_id,
             page_id: progress.pagination_cursor,
             page_total_distributed,
+            page_transfer_fees,
+        });
+
+        // Save progress account
+        let mut progress_data = ctx.accounts.progress.try_borrow_mut_data()?;
+        progress.try_serialize(&mut &mut progress_data[..])?;
+
+        Ok(())
+    }
+
+    /// Permissionless: add a page of investors to the day's totals before any payouts
+    ///
+    /// Required when the config caps per-investor payouts, so every payout page
+    /// works from the day's full investor total.
+    pub fn tally_investor_page<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TallyInvestorPage<'info>>,
+        page_investors: Vec<InvestorRecord>,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let day_id = clock.unix_timestamp / DAY_IN_SECONDS;
+
+        let mut progress = load_or_create_progress(
+            &ctx.accounts.cranker,
+            &ctx.accounts.progress,
+            &ctx.accounts.system_program,
+            &ctx.accounts.config.key(),
+            day_id,
+        )?;
+        require!(
+            !ctx.accounts.config.closed || progress.last_distribution_ts != 0,
+            ErrorCode::ConfigClosed
+        );
+        require!(!progress.finalized, ErrorCode::DayAlreadyFinalized);
+        require!(progress.pagination_cursor == 0, ErrorCode::InvestorTallyClosed);
+        require_not_paused(&ctx.accounts.config, &ctx.accounts.protocol_config, PAUSE_INVESTOR_PAYOUTS)?;
+        fold_investor_keys(&mut progress.tally_cursor, &mut progress.tally_digest, &page_investors)?;
+
+        let mut page_weight_total = 0u128;
+        for investor in &page_investors {
+            let lock = read_investor_lock(
+                &ctx.accounts.config,
+                ctx.remaining_accounts,
+                investor,
+                progress.snapshot_ts,
+            )?;
+            let weight = ctx.accounts.config.weighting_mode.weight(&lock, progress.snapshot_ts);
+
+            progress.tally(lock.locked_amount, weight);
+            page_weight_total = page_weight_total.saturating_add(weight);
+        }
+
+        let mut progress_data = ctx.accounts.progress.try_borrow_mut_data()?;
+        progress.try_serialize(&mut &mut progress_data[..])?;
+
+        emit!(InvestorPageTallied {
+            day_id,
+            investors: page_investors.len() as u64,
+            page_weight_total,
         });
 
-        // If this is the final page, send remainder to creator
-        if is_final_page {
-            let remainder = progress.claimed_quote_for_day
-                .saturating_sub(progress.cumulative_distributed_today)
-                .saturating_sub(progress.carry_over);
+        Ok(())
+    }
+
+    /// Close out a day: pay the creator remainder and record an immutable summary
+    pub fn finalize_day<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FinalizeDay<'info>>,
+        day_id: i64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+
+        require!(
+            !ctx.accounts.progress.data_is_empty(),
+            ErrorCode::InvalidProgressAccount
+        );
+        let mut progress =
+            ProgressAccount::try_deserialize(&mut &ctx.accounts.progress.data.borrow()[..])?;
+
+        require!(progress.day_id == day_id, ErrorCode::InvalidProgressAccount);
+        require!(!progress.finalized, ErrorCode::DayAlreadyFinalized);
+        require_not_paused(&ctx.accounts.config, &ctx.accounts.protocol_config, PAUSE_CREATOR_PAYOUTS)?;
+
+        // Pages may still come until the day is over, unless a tally fixed the day's investors
+        // and the check below confirms every one of them was paid
+        require!(
+            day_id < clock.unix_timestamp / DAY_IN_SECONDS || progress.investors_tallied > 0,
+            ErrorCode::DayNotOver
+        );
+        require!(
+            progress.payout_digest == progress.tally_digest,
+            ErrorCode::TallyMismatch
+        );
+
+        // Whatever of the held-back reserve investors were not paid or carried goes back to
+        // the cap window instead of to the creator
+        let held_back_returned = progress.release_held_back();
+        if held_back_returned > 0 {
+            let cap_window = ctx.accounts.cap_window.as_mut().ok_or(ErrorCode::MissingCapWindow)?;
+            cap_window.held_back = cap_window.held_back.saturating_add(held_back_returned);
+        }
+
+        // Unless the overflow policy pays it to the creator, the rest of the overflow above
+        // the caps is held back for later days or retained in the treasury
+        let config = &ctx.accounts.config;
+        let cap_overflow_withheld = match config.cap_overflow_policy {
+            CapOverflowPolicy::Creator => 0,
+            CapOverflowPolicy::CarryForward | CapOverflowPolicy::Retain => std::cmp::min(
+                progress.cap_overflow.saturating_sub(held_back_returned),
+                progress.creator_remainder(),
+            ),
+        };
+        if cap_overflow_withheld > 0 {
+            let cap_window = ctx.accounts.cap_window.as_mut().ok_or(ErrorCode::MissingCapWindow)?;
+            if config.cap_overflow_policy == CapOverflowPolicy::CarryForward {
+                cap_window.held_back = cap_window.held_back.saturating_add(cap_overflow_withheld);
+            } else {
+                cap_window.retained = cap_window.retained.saturating_add(cap_overflow_withheld);
+            }
+        }
+        progress.cap_overflow_withheld = cap_overflow_withheld;
+
+        // Everything that was neither paid to investors nor carried as dust goes to the creator,
+        // or is divided across the config's remainder split when one is set
+        let creator_amount = progress.creator_remainder();
+        let remainder_split = &ctx.accounts.config.remainder_split;
+        let payouts: Vec<(Pubkey, u64)> = if remainder_split.recipients.is_empty() {
+            vec![(ctx.accounts.creator_quote_ata.key(), creator_amount)]
+        } else {
+            remainder_split
+                .recipients
+                .iter()
+                .map(|recipient| recipient.recipient)
+                .zip(remainder_split.amounts(creator_amount))
+                .collect()
+        };
 
-            if remainder > 0 {
-                Self::transfer_to_creator(
+        let mut remainder_payouts = Vec::with_capacity(payouts.len());
+        for (recipient, amount) in payouts {
+            if amount > 0 {
+                let recipient_info = if recipient == ctx.accounts.creator_quote_ata.key() {
+                    ctx.accounts.creator_quote_ata.to_account_info()
+                } else {
+                    ctx.remaining_accounts
+                        .iter()
+                        .find(|account| account.key == &recipient)
+                        .ok_or(ErrorCode::MissingRemainderRecipient)?
+                        .clone()
+                };
+                let fee = transfer_from_treasury(
                     &ctx.accounts.token_program,
                     &ctx.accounts.program_quote_treasury,
-                    &ctx.accounts.creator_quote_ata,
-                    remainder,
+                    &ctx.accounts.quote_mint,
+                    &ctx.accounts.program_authority,
+                    &recipient_info,
+                    ctx.remaining_accounts,
+                    &ctx.accounts.config.key(),
+                    ctx.bumps.program_authority,
+                    amount,
                 )?;
+                progress.transfer_fees_withheld = progress.transfer_fees_withheld.saturating_add(fee);
             }
+            remainder_payouts.push(RemainderPayout { recipient, amount });
+        }
 
-            emit!(CreatorPayoutDayClosed {
-                day_id,
-                creator_amount: remainder,
-            });
+        // A decommissioned config's last day leaves nothing behind: removed liquidity, dust and
+        // withheld overflow still in its treasury go to the quote sweep recipient
+        let quote_swept = if ctx.accounts.config.closed {
+            let quote_sweep_recipient = ctx
+                .accounts
+                .quote_sweep_recipient
+                .as_ref()
+                .ok_or(ErrorCode::InvalidSweepRecipient)?;
+            ctx.accounts.program_quote_treasury.reload()?;
+            let amount = ctx.accounts.program_quote_treasury.amount;
+            if amount > 0 {
+                let fee = transfer_from_treasury(
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.program_quote_treasury,
+                    &ctx.accounts.quote_mint,
+                    &ctx.accounts.program_authority,
+                    &quote_sweep_recipient.to_account_info(),
+                    ctx.remaining_accounts,
+                    &ctx.accounts.config.key(),
+                    ctx.bumps.program_authority,
+                    amount,
+                )?;
+                progress.transfer_fees_withheld = progress.transfer_fees_withheld.saturating_add(fee);
+            }
+            if let Some(cap_window) = ctx.accounts.cap_window.as_mut() {
+                cap_window.held_back = 0;
+                cap_window.retained = 0;
+            }
+            amount
+        } else {
+            0
+        };
 
-            // Reset for next day
-            progress.last_distribution_ts = current_time;
-            progress.day_id = day_id.saturating_add(1);
+        let summary = &mut ctx.accounts.day_summary;
+        summary.day_id = day_id;
+        summary.claimed_quote = progress.claimed_quote_for_day;
+        summary.crank_tip = progress.crank_tip;
+        summary.protocol_fee = progress.protocol_fee;
+        summary.distributed_to_investors = progress.cumulative_distributed_today;
+        summary.creator_amount = creator_amount;
+        summary.dust_carried = progress.carry_over;
+        summary.investors_paid = progress.investors_paid;
+        summary.investors_skipped = progress.investors_skipped;
+        summary.locked_total = progress.locked_total;
+        summary.transfer_fees_withheld = progress.transfer_fees_withheld;
+        summary.finalized_ts = clock.unix_timestamp;
+        summary.bump = ctx.bumps.day_summary;
+
+        if progress.last_distribution_ts != 0 {
+            let config = &mut ctx.accounts.config;
+            config.open_days = config.open_days.saturating_sub(1);
         }
-
-        // Save progress account
+        progress.finalized = true;
         let mut progress_data = ctx.accounts.progress.try_borrow_mut_data()?;
-        progress.serialize(&mut &mut progress_data[..])?;
+        progress.try_serialize(&mut &mut progress_data[..])?;
+
+        emit!(CreatorPayoutDayClosed {
+            day_id,
+            creator_amount,
+            remainder_payouts,
+            cap_overflow_withheld,
+            quote_swept,
+        });
 
         Ok(())
     }
 
-    // Helper functions
-    fn get_progress_pda(day_id: i64) -> Result<Pubkey> {
-        let (progress_pda, _bump) = Pubkey::find_program_address(
-            &[PROGRESS_SEED, &day_id.to_le_bytes()],
-            &crate::ID,
-        );
-        Ok(progress_pda)
-    }
-
-    fn claim_fees_from_damm(accounts: &CrankDistributePage) -> Result<u64> {
-        // TODO: Implement actual CPI to cp-amm to claim fees
-        // For now, return a mock value
-        // In production, this would:
-        // 1. Call cp-amm's claim_fees instruction
-        // 2. Check that only quote tokens were claimed
-        // 3. Return the claimed amount
-        Ok(1000000) // Mock value
-    }
-
-    fn get_locked_amount(
-        streamflow_program: &AccountInfo,
-        stream_pubkey: &Pubkey,
-        timestamp: i64,
-    ) -> Result<u64> {
-        // TODO: Implement actual Streamflow stream reading
-        // For now, return a mock value
-        // In production, this would read the stream account and calculate locked amount
-        Ok(500000) // Mock value
-    }
-
-    fn transfer_to_investor(
-        token_program: &AccountInfo,
-        from: &AccountInfo,
-        to: &AccountInfo,
-        amount: u64,
-    ) -> Result<()> {
-        let cpi_accounts = Transfer {
-            from: from.to_account_info(),
-            to: to.to_account_info(),
-            authority: from.to_account_info(),
-        };
-        let cpi_program = token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, amount)?;
+    /// Create the rolling per-config history that closed progress accounts fold into
+    pub fn initialize_progress_history(ctx: Context<InitializeProgressHistory>) -> Result<()> {
+        let history = &mut ctx.accounts.progress_history;
+        history.config = ctx.accounts.config.key();
+        history.bump = ctx.bumps.progress_history;
+        ctx.accounts.config.has_progress_history = true;
         Ok(())
     }
 
-    fn transfer_to_creator(
-        token_program: &AccountInfo,
-        from: &AccountInfo,
-        to: &AccountInfo,
-        amount: u64,
+    /// Create the per-config window of daily totals that rolling caps are enforced against
+    pub fn initialize_cap_window(ctx: Context<InitializeCapWindow>) -> Result<()> {
+        let cap_window = &mut ctx.accounts.cap_window;
+        cap_window.config = ctx.accounts.config.key();
+        cap_window.bump = ctx.bumps.cap_window;
+        Ok(())
+    }
+
+    /// Create the program-wide protocol config (program upgrade authority only); the payer
+    /// becomes the protocol admin
+    pub fn initialize_protocol_config(
+        ctx: Context<InitializeProtocolConfig>,
+        protocol_fee_bps: u16,
     ) -> Result<()> {
-        let cpi_accounts = Transfer {
-            from: from.to_account_info(),
-            to: to.to_account_info(),
-            authority: from.to_account_info(),
-        };
-        let cpi_program = token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, amount)?;
+        require!(
+            protocol_fee_bps <= MAX_PROTOCOL_FEE_BPS,
+            ErrorCode::ProtocolFeeTooHigh
+        );
+
+        let protocol_config = &mut ctx.accounts.protocol_config;
+        protocol_config.admin = ctx.accounts.payer.key();
+        protocol_config.guardian = ctx.accounts.payer.key();
+        protocol_config.protocol_fee_bps = protocol_fee_bps;
+        protocol_config.bump = ctx.bumps.protocol_config;
         Ok(())
     }
-}
 
-// Account structures
-#[derive(Accounts)]
-#[instruction(pool_id: Pubkey)]
-pub struct InitializeHonoraryPosition<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
+    /// Update the protocol config (protocol admin only)
+    pub fn update_protocol_config(
+        ctx: Context<UpdateProtocolConfig>,
+        params: ProtocolConfigUpdateParams,
+    ) -> Result<()> {
+        let protocol_config = &mut ctx.accounts.protocol_config;
 
-    #[account(
-        init,
-        payer = payer,
-        space = 8 + ConfigAccount::INIT_SPACE,
-        seeds = [CONFIG_SEED, pool_id.as_ref()],
-        bump
-    )]
-    pub config: Account<'info, ConfigAccount>,
+        if let Some(admin) = params.admin {
+            protocol_config.admin = admin;
+        }
+        if let Some(guardian) = params.guardian {
+            protocol_config.guardian = guardian;
+        }
+        if let Some(protocol_fee_bps) = params.protocol_fee_bps {
+            require!(
+                protocol_fee_bps <= MAX_PROTOCOL_FEE_BPS,
+                ErrorCode::ProtocolFeeTooHigh
+            );
+            protocol_config.protocol_fee_bps = protocol_fee_bps;
+        }
 
-    /// The cp-amm pool
-    /// CHECK: Validated by cp-amm program
-    pub pool: AccountInfo<'info>,
+        Ok(())
+    }
 
-    /// The quote mint (token that will accrue fees)
-    /// CHECK: Validated by token program
-    pub quote_mint: AccountInfo<'info>,
+    /// Set the config's pause bits (config guardian only)
+    pub fn set_config_paused(ctx: Context<SetConfigPaused>, paused: u8) -> Result<()> {
+        require!(paused & !PAUSE_ALL == 0, ErrorCode::InvalidPauseFlags);
+        let config = &mut ctx.accounts.config;
 
-    /// The base mint
-    /// CHECK: Validated by token program
-    pub base_mint: AccountInfo<'info>,
+        // The emergency withdrawal timelock runs from when every stage was paused
+        if paused != PAUSE_ALL {
+            config.fully_paused_at = 0;
+        } else if config.paused != PAUSE_ALL {
+            config.fully_paused_at = Clock::get()?.unix_timestamp;
+        }
+        config.paused = paused;
 
-    /// The DAMM v2 position account (owned by our PDA)
-    /// CHECK: Will be created by cp-amm program
-    pub position: AccountInfo<'info>,
+        emit!(PauseUpdated {
+            account: ctx.accounts.config.key(),
+            guardian: ctx.accounts.guardian.key(),
+            paused,
+        });
+        Ok(())
+    }
 
-    /// PDA that will own the DAMM v2 position
-    #[account(
-        seeds = [VAULT_SEED, VAULT_SEED, INVESTOR_FEE_POS_OWNER_SEED],
-        bump
-    )]
-    pub investor_fee_position_owner_pda: AccountInfo<'info>,
+    /// Set the program-wide pause bits, which apply to every config (protocol guardian only)
+    pub fn set_protocol_paused(ctx: Context<SetProtocolPaused>, paused: u8) -> Result<()> {
+        require!(paused & !PAUSE_ALL == 0, ErrorCode::InvalidPauseFlags);
+        ctx.accounts.protocol_config.paused = paused;
 
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
+        emit!(PauseUpdated {
+            account: ctx.accounts.protocol_config.key(),
+            guardian: ctx.accounts.guardian.key(),
+            paused,
+        });
+        Ok(())
+    }
+
+    /// Send the config's whole quote treasury to its recovery account (config authority only)
+    ///
+    /// Only once the config has been fully paused for `EMERGENCY_WITHDRAW_TIMELOCK_SECS`.
+    pub fn emergency_withdraw<'info>(
+        ctx: Context<'_, '_, 'info, 'info, EmergencyWithdraw<'info>>,
+        reason: u8,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let config = &ctx.accounts.config;
+
+        require!(config.paused == PAUSE_ALL, ErrorCode::NotPaused);
+        require!(
+            clock.unix_timestamp >= config.fully_paused_at.saturating_add(EMERGENCY_WITHDRAW_TIMELOCK_SECS),
+            ErrorCode::EmergencyTimelockNotElapsed
+        );
+
+        let amount = ctx.accounts.program_quote_treasury.amount;
+        if amount > 0 {
+            transfer_from_treasury(
+                &ctx.accounts.token_program,
+                &ctx.accounts.program_quote_treasury,
+                &ctx.accounts.quote_mint,
+                &ctx.accounts.program_authority,
+                &ctx.accounts.recovery_ata.to_account_info(),
+                ctx.remaining_accounts,
+                &ctx.accounts.config.key(),
+                ctx.bumps.program_authority,
+                amount,
+            )?;
+        }
+
+        emit!(EmergencyWithdrawal {
+            config: config.key(),
+            recovery_ata: ctx.accounts.recovery_ata.key(),
+            amount,
+            reason,
+            ts: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Start the notice period before the config can be decommissioned (config authority only)
+    pub fn request_decommission(ctx: Context<RequestDecommission>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(!config.closed, ErrorCode::ConfigClosed);
+
+        let ts = Clock::get()?.unix_timestamp;
+        config.decommission_requested_at = ts;
+
+        emit!(DecommissionRequested {
+            config: config.key(),
+            ts,
+        });
+        Ok(())
+    }
+
+    /// End-of-life for a config (config authority only)
+    ///
+    /// Only once `DECOMMISSION_NOTICE_SECS` have passed since `request_decommission`, with
+    /// every claimed day finalized and today not yet claimed. Makes today's claim from every
+    /// honorary position, removes their liquidity, closes them and marks the config closed.
+    /// The final claim is paid out like any other day's, by the crank and `finalize_day`,
+    /// which then sweeps what is left of the quote treasury. The base treasury, holding
+    /// base fees and removed base liquidity, is swept here.
+    pub fn decommission<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Decommission<'info>>,
+        day_id: i64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let current_time = clock.unix_timestamp;
+        let config = &ctx.accounts.config;
+        require!(!config.closed, ErrorCode::ConfigClosed);
+        require_not_paused(config, &ctx.accounts.protocol_config, PAUSE_ALL)?;
+        require!(
+            config.decommission_requested_at > 0
+                && current_time >= config.decommission_requested_at.saturating_add(DECOMMISSION_NOTICE_SECS),
+            ErrorCode::DecommissionNoticeNotElapsed
+        );
+        require!(config.open_days == 0, ErrorCode::DayNotFinalized);
+        require!(
+            day_id == current_time / DAY_IN_SECONDS,
+            ErrorCode::InvalidProgressAccount
+        );
+
+        // The final claim is today's, so today must not have been claimed yet
+        let mut progress = load_or_create_progress(
+            &ctx.accounts.authority,
+            &ctx.accounts.progress,
+            &ctx.accounts.system_program,
+            &config.key(),
+            day_id,
+        )?;
+        require!(progress.last_distribution_ts == 0, ErrorCode::DayAlreadyClaimed);
+
+        // Final claim, then pull any liquidity and close every honorary position
+        let cp_amm = decommission_cp_amm_accounts(
+            ctx.accounts,
+            ctx.remaining_accounts,
+            ctx.bumps.investor_fee_position_owner_pda,
+            ctx.bumps.program_authority,
+        )?;
+        let mut position_claims = Vec::with_capacity(MAX_HONORARY_POSITIONS);
+        let mut removed_quote = 0u64;
+        let mut removed_base = 0u64;
+        for (position, pool) in ctx.accounts.config.honorary_positions() {
+            let position_info = if position == ctx.accounts.position.key() {
+                &ctx.accounts.position
+            } else {
+                ctx.remaining_accounts
+                    .iter()
+                    .find(|account| account.key == &position)
+                    .ok_or(ErrorCode::MissingHonoraryPosition)?
+            };
+            let claim = cp_amm.claim_fees(position_info, &pool)?;
+            let liquidity = cp_amm.remove_all_liquidity(position_info, &pool)?;
+            cp_amm.close_position(position_info, &pool, &ctx.accounts.authority)?;
+
+            position_claims.push(PositionFeeClaim {
+                position,
+                claimed_quote: claim.quote_amount,
+                claimed_base: claim.base_amount,
+            });
+            removed_quote = removed_quote.saturating_add(liquidity.quote_amount);
+            removed_base = removed_base.saturating_add(liquidity.base_amount);
+        }
+        let claimed_quote = position_claims
+            .iter()
+            .try_fold(0u64, |total, claim| total.checked_add(claim.claimed_quote))
+            .ok_or(ErrorCode::MathOverflow)?;
+        let claimed_base = position_claims
+            .iter()
+            .try_fold(0u64, |total, claim| total.checked_add(claim.claimed_base))
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        // Skim the protocol fee off the final claim, as on any other day
+        let protocol_fee_bps = ctx.accounts.protocol_config.protocol_fee_bps;
+        let protocol_fee = ctx.accounts.protocol_config.protocol_fee_for(claimed_quote);
+        if protocol_fee > 0 {
+            let fee = transfer_from_treasury(
+                &ctx.accounts.token_program,
+                &ctx.accounts.program_quote_treasury,
+                &ctx.accounts.quote_mint,
+                &ctx.accounts.program_authority,
+                &ctx.accounts.protocol_fee_vault.to_account_info(),
+                ctx.remaining_accounts,
+                &ctx.accounts.config.key(),
+                ctx.bumps.program_authority,
+                protocol_fee,
+            )?;
+            progress.transfer_fees_withheld = progress.transfer_fees_withheld.saturating_add(fee);
+        }
+
+        // Record the claim as today's, for the crank and finalize_day to pay out
+        progress.claimed_quote_for_day = claimed_quote;
+        progress.protocol_fee = protocol_fee;
+        progress.last_distribution_ts = current_time;
+        let mut progress_data = ctx.accounts.progress.try_borrow_mut_data()?;
+        progress.try_serialize(&mut &mut progress_data[..])?;
+        drop(progress_data);
+
+        // Base fees and liquidity are never distributed, so the base treasury is swept now
+        ctx.accounts.program_base_treasury.reload()?;
+        let base_swept = ctx.accounts.program_base_treasury.amount;
+        if base_swept > 0 {
+            transfer_from_treasury(
+                &ctx.accounts.base_token_program,
+                &ctx.accounts.program_base_treasury,
+                &ctx.accounts.base_mint,
+                &ctx.accounts.program_authority,
+                &ctx.accounts.base_sweep_recipient.to_account_info(),
+                ctx.remaining_accounts,
+                &ctx.accounts.config.key(),
+                ctx.bumps.program_authority,
+                base_swept,
+            )?;
+        }
+
+        let config = &mut ctx.accounts.config;
+        config.closed = true;
+        config.open_days = config.open_days.saturating_add(1);
+        config.last_claimed_day_id = day_id;
+
+        let positions_closed = position_claims.len() as u8;
+        emit!(QuoteFeesClaimed {
+            day_id,
+            claimed_quote,
+            crank_tip: 0,
+            cranker: ctx.accounts.authority.key(),
+            position_claims,
+            protocol_fee,
+            protocol_fee_bps,
+            claimed_base,
+            swapped_quote: 0,
+        });
+        emit!(ConfigDecommissioned {
+            config: ctx.accounts.config.key(),
+            claimed_quote,
+            claimed_base,
+            removed_quote,
+            removed_base,
+            positions_closed,
+            base_swept,
+        });
+
+        Ok(())
+    }
+
+    /// Move collected protocol fees out of the fee vault (protocol admin only)
+    pub fn withdraw_protocol_fees<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawProtocolFees<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(ctx.accounts.protocol_config.paused == 0, ErrorCode::Paused);
+
+        let signer_seeds: &[&[&[u8]]] =
+            &[&[PROTOCOL_FEE_VAULT_SEED, &[ctx.bumps.protocol_fee_authority]]];
+        spl_token_2022::onchain::invoke_transfer_checked(
+            ctx.accounts.token_program.key,
+            ctx.accounts.protocol_fee_vault.to_account_info(),
+            ctx.accounts.quote_mint.to_account_info(),
+            ctx.accounts.destination.to_account_info(),
+            ctx.accounts.protocol_fee_authority.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            ctx.accounts.quote_mint.decimals,
+            signer_seeds,
+        )?;
+        Ok(())
+    }
+
+    /// Permissionless: close a finalized progress account past the retention period
+    pub fn close_progress(ctx: Context<CloseProgress>, day_id: i64) -> Result<()> {
+        let clock = Clock::get()?;
+        let current_day_id = clock.unix_timestamp / DAY_IN_SECONDS;
+        let progress = &ctx.accounts.progress;
+
+        require_not_paused(&ctx.accounts.config, &ctx.accounts.protocol_config, PAUSE_ALL)?;
+        require!(progress.day_id == day_id, ErrorCode::InvalidProgressAccount);
+        require!(progress.finalized, ErrorCode::DayNotFinalized);
+        require!(
+            current_day_id.saturating_sub(progress.day_id)
+                > ctx.accounts.config.progress_retention_days as i64,
+            ErrorCode::ProgressRetentionNotElapsed
+        );
+
+        // Once a config keeps a history, no closed day may skip it
+        require!(
+            ctx.accounts.progress_history.is_some() || !ctx.accounts.config.has_progress_history,
+            ErrorCode::MissingProgressHistory
+        );
+
+        // Fold the day's totals into the rolling history before the account goes away
+        if let Some(history) = ctx.accounts.progress_history.as_mut() {
+            let creator_amount = progress.creator_remainder();
+
+            history.days_closed = history.days_closed.saturating_add(1);
+            history.total_claimed = history.total_claimed.saturating_add(progress.claimed_quote_for_day);
+            history.total_distributed_to_investors = history
+                .total_distributed_to_investors
+                .saturating_add(progress.cumulative_distributed_today);
+            history.total_creator_amount = history.total_creator_amount.saturating_add(creator_amount);
+            history.total_crank_tips = history.total_crank_tips.saturating_add(progress.crank_tip);
+            history.total_protocol_fees = history.total_protocol_fees.saturating_add(progress.protocol_fee);
+            history.total_dust_carried = history.total_dust_carried.saturating_add(progress.carry_over);
+            history.last_closed_day_id = std::cmp::max(history.last_closed_day_id, day_id);
+        }
+
+        emit!(ProgressClosed {
+            day_id,
+            payer: progress.payer,
+            folded_into_history: ctx.accounts.progress_history.is_some(),
+        });
+
+        Ok(())
+    }
+}
+
+// Helper functions
+
+/// Fail with `Paused` when the config or the protocol has paused `flag`
+fn require_not_paused(config: &ConfigAccount, protocol_config: &ProtocolConfig, flag: u8) -> Result<()> {
+    require!((config.paused | protocol_config.paused) & flag == 0, ErrorCode::Paused);
+    Ok(())
+}
+
+fn get_progress_pda(config: &Pubkey, day_id: i64) -> Result<(Pubkey, u8)> {
+    Ok(Pubkey::find_program_address(
+        &[PROGRESS_SEED, config.as_ref(), &day_id.to_le_bytes()],
+        &crate::ID,
+    ))
+}
+
+/// Deserialize the config's progress PDA for `day_id`, creating it (funded by `payer`) on first use
+fn load_or_create_progress<'info>(
+    payer: &Signer<'info>,
+    progress: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    config: &Pubkey,
+    day_id: i64,
+) -> Result<ProgressAccount> {
+    let (progress_pda, progress_bump) = get_progress_pda(config, day_id)?;
+    require!(
+        progress.key() == progress_pda,
+        ErrorCode::InvalidProgressAccount
+    );
+
+    if progress.data_is_empty() {
+        create_progress_account(payer, progress, system_program, config, day_id, progress_bump)?;
+        Ok(ProgressAccount::new(*config, day_id, payer.key(), progress_bump))
+    } else {
+        ProgressAccount::try_deserialize(&mut &progress.data.borrow()[..])
+    }
+}
+
+/// Allocate the config's progress PDA for `day_id`, funded by `payer`
+fn create_progress_account<'info>(
+    payer: &Signer<'info>,
+    progress: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    config: &Pubkey,
+    day_id: i64,
+    bump: u8,
+) -> Result<()> {
+    let space = 8 + ProgressAccount::INIT_SPACE;
+    let lamports = Rent::get()?.minimum_balance(space);
+    let day_id_bytes = day_id.to_le_bytes();
+    let signer_seeds: &[&[&[u8]]] = &[&[PROGRESS_SEED, config.as_ref(), &day_id_bytes, &[bump]]];
+
+    system_program::create_account(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            system_program::CreateAccount {
+                from: payer.to_account_info(),
+                to: progress.clone(),
+            },
+            signer_seeds,
+        ),
+        lamports,
+        space as u64,
+        &crate::ID,
+    )
+}
+
+/// cp-amm CPI accounts for the crank
+fn crank_cp_amm_accounts<'a, 'info>(
+    accounts: &CrankDistributePage<'info>,
+    remaining_accounts: &'a [AccountInfo<'info>],
+    position_owner_bump: u8,
+    program_authority_bump: u8,
+) -> Result<CpAmmAccounts<'a, 'info>> {
+    require!(
+        accounts.config.cp_amm_program != Pubkey::default(),
+        ErrorCode::MissingCpAmmAccounts
+    );
+    let (Some(cp_amm_program), Some(base_mint), Some(program_base_treasury), Some(base_token_program)) = (
+        accounts.cp_amm_program.as_ref(),
+        accounts.base_mint.as_ref(),
+        accounts.program_base_treasury.as_ref(),
+        accounts.base_token_program.as_ref(),
+    ) else {
+        return err!(ErrorCode::MissingCpAmmAccounts);
+    };
+    Ok(CpAmmAccounts {
+        program: cp_amm_program.clone(),
+        position_owner: accounts.investor_fee_position_owner_pda.clone(),
+        position_owner_bump,
+        config: accounts.config.key(),
+        program_authority: accounts.program_authority.clone(),
+        program_authority_bump,
+        quote_mint: accounts.quote_mint.to_account_info(),
+        base_mint: base_mint.to_account_info(),
+        quote_treasury: accounts.program_quote_treasury.to_account_info(),
+        base_treasury: program_base_treasury.to_account_info(),
+        quote_token_program: accounts.token_program.to_account_info(),
+        base_token_program: base_token_program.to_account_info(),
+        remaining_accounts,
+    })
+}
+
+/// cp-amm CPI accounts for `decommission`
+fn decommission_cp_amm_accounts<'a, 'info>(
+    accounts: &Decommission<'info>,
+    remaining_accounts: &'a [AccountInfo<'info>],
+    position_owner_bump: u8,
+    program_authority_bump: u8,
+) -> Result<CpAmmAccounts<'a, 'info>> {
+    require!(
+        accounts.config.cp_amm_program != Pubkey::default(),
+        ErrorCode::MissingCpAmmAccounts
+    );
+    let cp_amm_program = accounts
+        .cp_amm_program
+        .as_ref()
+        .ok_or(ErrorCode::MissingCpAmmAccounts)?;
+    Ok(CpAmmAccounts {
+        program: cp_amm_program.clone(),
+        position_owner: accounts.investor_fee_position_owner_pda.clone(),
+        position_owner_bump,
+        config: accounts.config.key(),
+        program_authority: accounts.program_authority.clone(),
+        program_authority_bump,
+        quote_mint: accounts.quote_mint.to_account_info(),
+        base_mint: accounts.base_mint.to_account_info(),
+        quote_treasury: accounts.program_quote_treasury.to_account_info(),
+        base_treasury: accounts.program_base_treasury.to_account_info(),
+        quote_token_program: accounts.token_program.to_account_info(),
+        base_token_program: accounts.base_token_program.to_account_info(),
+        remaining_accounts,
+    })
+}
+
+/// Apply the config's base fee policy to `claimed_base`, returning the quote it was swapped into
+fn settle_base_fees<'info>(
+    accounts: &CrankDistributePage<'info>,
+    cp_amm: &CpAmmAccounts<'_, 'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    program_authority_bump: u8,
+    claimed_base: u64,
+) -> Result<u64> {
+    if claimed_base == 0 {
+        return Ok(0);
+    }
+
+    match accounts.config.base_fee_policy {
+        BaseFeePolicy::Reject => err!(ErrorCode::BaseFeeDetected),
+        BaseFeePolicy::RouteToCreator => {
+            let (Some(base_token_program), Some(base_mint), Some(program_base_treasury), Some(creator_base_ata)) = (
+                accounts.base_token_program.as_ref(),
+                accounts.base_mint.as_ref(),
+                accounts.program_base_treasury.as_ref(),
+                accounts.creator_base_ata.as_ref(),
+            ) else {
+                return err!(ErrorCode::MissingBaseFeeAccounts);
+            };
+            transfer_from_treasury(
+                base_token_program,
+                program_base_treasury,
+                base_mint,
+                &accounts.program_authority,
+                &creator_base_ata.to_account_info(),
+                remaining_accounts,
+                &accounts.config.key(),
+                program_authority_bump,
+                claimed_base,
+            )?;
+            Ok(0)
+        }
+        BaseFeePolicy::SwapToQuote => {
+            let (Some(pool), Some(base_mint)) = (accounts.pool.as_ref(), accounts.base_mint.as_ref()) else {
+                return err!(ErrorCode::MissingBaseFeeAccounts);
+            };
+            // The floor comes from the oracle, not the pool being swapped through, so
+            // moving the pool's price ahead of the crank can't lower it
+            let now = Clock::get()?.unix_timestamp;
+            let quote_price = read_oracle_price(&accounts.config, accounts.price_feed.as_ref(), now)?;
+            let base_price = read_oracle_price(&accounts.config, accounts.base_price_feed.as_ref(), now)?;
+            let expected = base_price.convert(claimed_base, base_mint.decimals, &quote_price, accounts.quote_mint.decimals)?;
+            let minimum_amount_out = cp_amm::min_amount_out(expected, accounts.config.max_swap_slippage_bps);
+            let swapped_quote = cp_amm.swap_base_for_quote(pool, claimed_base, minimum_amount_out)?;
+            require!(swapped_quote >= minimum_amount_out, ErrorCode::SlippageExceeded);
+            Ok(swapped_quote)
+        }
+    }
+}
+
+/// Read and validate the price in one of the config's price feeds
+fn read_oracle_price(config: &ConfigAccount, price_feed: Option<&AccountInfo>, now: i64) -> Result<Price> {
+    let price_feed = price_feed.ok_or(ErrorCode::MissingPriceFeed)?;
+    require_keys_eq!(*price_feed.owner, config.oracle_program, ErrorCode::InvalidPriceFeed);
+    let price = PythPriceSource.read_price(price_feed)?;
+    price.validate(now, config.max_price_age_secs, config.max_price_confidence_bps)?;
+    Ok(price)
+}
+
+/// Read an investor's lock from `remaining_accounts` through the config's lock provider
+///
+/// Cancelled, closed and transferred locks count as nothing locked.
+fn read_investor_lock<'info>(
+    config: &ConfigAccount,
+    remaining_accounts: &'info [AccountInfo<'info>],
+    investor: &InvestorRecord,
+    timestamp: i64,
+) -> Result<LockState> {
+    let lock_info = remaining_accounts
+        .iter()
+        .find(|account| account.key == &investor.stream_pubkey)
+        .ok_or(ErrorCode::StreamAccountNotFound)?;
+    require_keys_eq!(
+        *lock_info.owner,
+        config.lock_program,
+        ErrorCode::InvalidLockProgram
+    );
+    let lock = config.lock_provider.read_lock(lock_info, timestamp)?;
+
+    // The lock must still pay the owner of the ATA we distribute to
+    let investor_quote_ata = find_remaining_account(remaining_accounts, &investor.investor_quote_ata)?;
+    let investor = TokenAccount::try_deserialize(&mut &investor_quote_ata.data.borrow()[..])?.owner;
+
+    Ok(lock.for_investor(&investor))
+}
+
+/// Look up an account passed through `remaining_accounts` by its key
+fn find_remaining_account<'info>(
+    remaining_accounts: &'info [AccountInfo<'info>],
+    key: &Pubkey,
+) -> Result<&'info AccountInfo<'info>> {
+    remaining_accounts
+        .iter()
+        .find(|account| account.key == key)
+        .ok_or_else(|| error!(ErrorCode::MissingInvestorATA))
+}
+
+/// Fold a page's stream keys into a running digest, requiring them to continue
+/// `cursor` in strictly increasing order so no investor is counted twice
+fn fold_investor_keys(cursor: &mut Pubkey, digest: &mut [u8; 32], page_investors: &[InvestorRecord]) -> Result<()> {
+    for investor in page_investors {
+        require!(investor.stream_pubkey > *cursor, ErrorCode::InvestorOutOfOrder);
+        *cursor = investor.stream_pubkey;
+        *digest = hashv(&[digest.as_ref(), investor.stream_pubkey.as_ref()]).to_bytes();
+    }
+    Ok(())
+}
+
+/// Transfer tokens out of `config`'s treasury, signed by its program authority PDA
+///
+/// Accounts required by the mint's transfer hook are resolved from its
+/// ExtraAccountMetaList out of `additional_accounts`. Returns the Token-2022
+/// transfer fee withheld from the recipient, if any.
+#[allow(clippy::too_many_arguments)]
+fn transfer_from_treasury<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    treasury: &InterfaceAccount<'info, TokenAccount>,
+    quote_mint: &InterfaceAccount<'info, Mint>,
+    program_authority: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    additional_accounts: &[AccountInfo<'info>],
+    config: &Pubkey,
+    program_authority_bump: u8,
+    amount: u64,
+) -> Result<u64> {
+    let signer_seeds: &[&[&[u8]]] = &[&[PROGRAM_AUTHORITY_SEED, config.as_ref(), &[program_authority_bump]]];
+    spl_token_2022::onchain::invoke_transfer_checked(
+        token_program.key,
+        treasury.to_account_info(),
+        quote_mint.to_account_info(),
+        to.clone(),
+        program_authority.clone(),
+        additional_accounts,
+        amount,
+        quote_mint.decimals,
+        signer_seeds,
+    )?;
+    quote_mint::transfer_fee(&quote_mint.to_account_info(), amount)
+}
+
+// Account structures
+#[derive(Accounts)]
+#[instruction(pool_id: Pubkey)]
+pub struct InitializeHonoraryPosition<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ConfigAccount::INIT_SPACE,
+        seeds = [CONFIG_SEED, pool_id.as_ref()],
+        bump
+    )]
+    pub config: Account<'info, ConfigAccount>,
+
+    /// The cp-amm pool
+    /// CHECK: Validated by cp-amm program
+    pub pool: AccountInfo<'info>,
+
+    /// The quote mint (token that will accrue fees), SPL Token or Token-2022
+    #[account(mint::token_program = token_program)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    /// The base mint
+    /// CHECK: Validated by token program
+    pub base_mint: AccountInfo<'info>,
+
+    /// The DAMM v2 position account (owned by our PDA)
+    /// CHECK: Will be created by cp-amm program
+    pub position: AccountInfo<'info>,
+
+    /// PDA that will own the DAMM v2 position
+    #[account(
+        seeds = [VAULT_SEED, config.key().as_ref(), INVESTOR_FEE_POS_OWNER_SEED],
+        bump
+    )]
+    pub investor_fee_position_owner_pda: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct AddHonoraryPosition<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.pool_id.as_ref()],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ConfigAccount>,
+
+    /// The additional DAMM v2 position account (owned by our PDA)
+    /// CHECK: Read through the cp-amm program and checked against the pool and position owner PDA
+    pub position: AccountInfo<'info>,
+
+    /// PDA that owns the DAMM v2 positions
+    #[account(
+        seeds = [VAULT_SEED, config.key().as_ref(), INVESTOR_FEE_POS_OWNER_SEED],
+        bump
+    )]
+    pub investor_fee_position_owner_pda: AccountInfo<'info>,
+
+    /// The config's cp-amm program, which must own the position
+    /// CHECK: Must be the config's cp-amm program, once one is set
+    #[account(
+        address = config.cp_amm_program @ ErrorCode::InvalidCpAmmAccount,
+        constraint = config.cp_amm_program != Pubkey::default() @ ErrorCode::MissingCpAmmAccounts
+    )]
+    pub cp_amm_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.pool_id.as_ref()],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ConfigAccount>,
+}
+
+#[derive(Accounts)]
+pub struct TallyInvestorPage<'info> {
+    /// Pays for the day's progress account if it does not exist yet
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// The config account
+    #[account(
+        seeds = [CONFIG_SEED, config.pool_id.as_ref()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ConfigAccount>,
+
+    /// The progress account for this day
+    /// CHECK: Will be initialized by the instruction if needed
+    #[account(mut)]
+    pub progress: AccountInfo<'info>,
+
+    /// Program-wide protocol config, for its pause bits
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// The lock program investor locks are read from
+    /// CHECK: Must be the config's lock program, once one is set
+    #[account(
+        address = config.lock_program @ ErrorCode::InvalidLockProgram,
+        constraint = config.lock_program != Pubkey::default() @ ErrorCode::LockProgramNotSet
+    )]
+    pub lock_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CrankDistributePage<'info> {
+    /// Pays for the day's progress account on the first page
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    /// The config account
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.pool_id.as_ref()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ConfigAccount>,
+
+    /// The progress account for this day
+    /// CHECK: Will be initialized by the instruction if needed
+    #[account(mut)]
+    pub progress: AccountInfo<'info>,
+
+    /// The honorary position account
+    /// CHECK: Validated by cp-amm program
+    #[account(mut)]
+    pub position: AccountInfo<'info>,
+
+    /// The position owner PDA
+    #[account(
+        seeds = [VAULT_SEED, config.key().as_ref(), INVESTOR_FEE_POS_OWNER_SEED],
+        bump
+    )]
+    pub investor_fee_position_owner_pda: AccountInfo<'info>,
+
+    /// The quote mint, needed for checked transfers
+    #[account(
+        address = config.quote_mint,
+        mint::token_program = token_program
+    )]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    /// The config's quote treasury ATA
+    #[account(
+        mut,
+        associated_token::mint = config.quote_mint,
+        associated_token::authority = program_authority,
+        associated_token::token_program = token_program
+    )]
+    pub program_quote_treasury: InterfaceAccount<'info, TokenAccount>,
+
+    /// The config's program authority (for treasury operations)
+    /// CHECK: PDA signer for the treasury, validated by seeds
+    #[account(
+        seeds = [PROGRAM_AUTHORITY_SEED, config.key().as_ref()],
+        bump
+    )]
+    pub program_authority: AccountInfo<'info>,
+
+    /// The cranker's quote ATA, receives the crank tip on the first page
+    #[account(
+        mut,
+        associated_token::mint = config.quote_mint,
+        associated_token::authority = cranker,
+        associated_token::token_program = token_program
+    )]
+    pub cranker_quote_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Program-wide protocol config (protocol fee rate)
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Authority of the protocol fee vault
+    /// CHECK: PDA owner of the fee vault, validated by seeds
+    #[account(
+        seeds = [PROTOCOL_FEE_VAULT_SEED],
+        bump
+    )]
+    pub protocol_fee_authority: AccountInfo<'info>,
+
+    /// Program-wide fee vault for the quote mint, receives the protocol fee
+    #[account(
+        mut,
+        associated_token::mint = config.quote_mint,
+        associated_token::authority = protocol_fee_authority,
+        associated_token::token_program = token_program
+    )]
+    pub protocol_fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Oracle price of the quote mint, required when the config sets a USD daily cap
+    /// CHECK: Must be the config's price feed, owned by its oracle program
+    #[account(address = config.price_feed @ ErrorCode::InvalidPriceFeed)]
+    pub price_feed: Option<AccountInfo<'info>>,
+
+    /// Oracle price of the base mint, required with `price_feed` to swap base fees under `SwapToQuote`
+    /// CHECK: Must be the config's base price feed, owned by its oracle program
+    #[account(address = config.base_price_feed @ ErrorCode::InvalidPriceFeed)]
+    pub base_price_feed: Option<AccountInfo<'info>>,
+
+    /// The config's pool, required to swap base fees under `SwapToQuote`
+    /// CHECK: Must be the config's pool, validated by cp-amm
+    #[account(mut, address = config.pool_id @ ErrorCode::MissingBaseFeeAccounts)]
+    pub pool: Option<AccountInfo<'info>>,
+
+    /// The base mint, required to route base fees under `RouteToCreator`
+    #[account(address = config.base_mint)]
+    pub base_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    /// The config's base treasury, where claimed base fees land
+    #[account(
+        mut,
+        token::mint = config.base_mint,
+        token::authority = program_authority
+    )]
+    pub program_base_treasury: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// The creator's base token account, receives base fees under `RouteToCreator`
+    #[account(mut, address = config.creator_base_ata @ ErrorCode::MissingBaseFeeAccounts)]
+    pub creator_base_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Token program of the base mint
+    pub base_token_program: Option<Interface<'info, TokenInterface>>,
+
+    /// Window of daily totals, required when the config sets rolling caps
+    #[account(
+        mut,
+        seeds = [CAP_WINDOW_SEED, config.key().as_ref()],
+        bump = cap_window.bump
+    )]
+    pub cap_window: Option<Account<'info, CapWindow>>,
+
+    /// The cp-amm program, required to claim and swap through CPI once the config sets one
+    /// CHECK: Must be the config's cp-amm program
+    #[account(address = config.cp_amm_program @ ErrorCode::InvalidCpAmmAccount)]
+    pub cp_amm_program: Option<AccountInfo<'info>>,
+
+    /// The lock program investor locks are read from
+    /// CHECK: Must be the config's lock program, once one is set
+    #[account(
+        address = config.lock_program @ ErrorCode::InvalidLockProgram,
+        constraint = config.lock_program != Pubkey::default() @ ErrorCode::LockProgramNotSet
+    )]
+    pub lock_program: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(day_id: i64)]
+pub struct FinalizeDay<'info> {
+    /// Pays for the day summary account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The config account
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.pool_id.as_ref()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ConfigAccount>,
+
+    /// The progress account for the day being closed
+    /// CHECK: Validated by seeds and deserialized by the instruction
+    #[account(
+        mut,
+        seeds = [PROGRESS_SEED, config.key().as_ref(), &day_id.to_le_bytes()],
+        bump
+    )]
+    pub progress: AccountInfo<'info>,
+
+    /// Immutable record of the day's distribution
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + DaySummary::INIT_SPACE,
+        seeds = [DAY_SUMMARY_SEED, config.key().as_ref(), &day_id.to_le_bytes()],
+        bump
+    )]
+    pub day_summary: Account<'info, DaySummary>,
+
+    /// The quote mint, needed for checked transfers
+    #[account(
+        address = config.quote_mint,
+        mint::token_program = token_program
+    )]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    /// The config's quote treasury ATA
+    #[account(
+        mut,
+        associated_token::mint = config.quote_mint,
+        associated_token::authority = program_authority,
+        associated_token::token_program = token_program
+    )]
+    pub program_quote_treasury: InterfaceAccount<'info, TokenAccount>,
+
+    /// The config's program authority (for treasury operations)
+    /// CHECK: PDA signer for the treasury, validated by seeds
+    #[account(
+        seeds = [PROGRAM_AUTHORITY_SEED, config.key().as_ref()],
+        bump
+    )]
+    pub program_authority: AccountInfo<'info>,
+
+    /// The creator's quote ATA
+    #[account(
+        mut,
+        associated_token::mint = config.quote_mint,
+        associated_token::authority = creator,
+        associated_token::token_program = token_program
+    )]
+    pub creator_quote_ata: InterfaceAccount<'info, TokenAccount>,
+
+    /// The config's creator
+    /// CHECK: Must be the creator recorded on the config
+    #[account(address = config.creator @ ErrorCode::InvalidCreator)]
+    pub creator: AccountInfo<'info>,
+
+    /// Program-wide protocol config, for its pause bits
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Window holding carried and retained overflow, required when the day has overflow to withhold
+    #[account(
+        mut,
+        seeds = [CAP_WINDOW_SEED, config.key().as_ref()],
+        bump = cap_window.bump
+    )]
+    pub cap_window: Option<Account<'info, CapWindow>>,
+
+    /// Receives what is left in the treasury, required to finalize a decommissioned config's last day
+    #[account(
+        mut,
+        address = config.quote_sweep_recipient @ ErrorCode::InvalidSweepRecipient,
+        token::mint = config.quote_mint,
+        token::token_program = token_program
+    )]
+    pub quote_sweep_recipient: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeProgressHistory<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.pool_id.as_ref()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ConfigAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ProgressHistory::INIT_SPACE,
+        seeds = [PROGRESS_HISTORY_SEED, config.key().as_ref()],
+        bump
+    )]
+    pub progress_history: Account<'info, ProgressHistory>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeCapWindow<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [CONFIG_SEED, config.pool_id.as_ref()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ConfigAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + CapWindow::INIT_SPACE,
+        seeds = [CAP_WINDOW_SEED, config.key().as_ref()],
+        bump
+    )]
+    pub cap_window: Account<'info, CapWindow>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeProtocolConfig<'info> {
+    /// Must be the program's upgrade authority
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::DammV2FeeDistributor>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(payer.key())
+            @ ErrorCode::NotUpgradeAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ProtocolConfig::INIT_SPACE,
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateProtocolConfig<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump,
+        has_one = admin @ ErrorCode::NotProtocolAdmin
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetConfigPaused<'info> {
+    pub guardian: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.pool_id.as_ref()],
+        bump = config.bump,
+        has_one = guardian @ ErrorCode::NotGuardian
+    )]
+    pub config: Account<'info, ConfigAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SetProtocolPaused<'info> {
+    pub guardian: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump,
+        has_one = guardian @ ErrorCode::NotGuardian
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct EmergencyWithdraw<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [CONFIG_SEED, config.pool_id.as_ref()],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ConfigAccount>,
+
+    /// The quote mint, needed for checked transfers
+    #[account(
+        address = config.quote_mint,
+        mint::token_program = token_program
+    )]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    /// The config's quote treasury ATA, emptied into the recovery account
+    #[account(
+        mut,
+        associated_token::mint = config.quote_mint,
+        associated_token::authority = program_authority,
+        associated_token::token_program = token_program
+    )]
+    pub program_quote_treasury: InterfaceAccount<'info, TokenAccount>,
+
+    /// The config's program authority (for treasury operations)
+    /// CHECK: PDA signer for the treasury, validated by seeds
+    #[account(
+        seeds = [PROGRAM_AUTHORITY_SEED, config.key().as_ref()],
+        bump
+    )]
+    pub program_authority: AccountInfo<'info>,
+
+    /// The recovery account registered on the config
+    #[account(
+        mut,
+        address = config.recovery_ata @ ErrorCode::InvalidRecoveryAccount,
+        token::mint = config.quote_mint,
+        token::token_program = token_program
+    )]
+    pub recovery_ata: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
-pub struct CrankDistributePage<'info> {
-    /// The config account
+pub struct RequestDecommission<'info> {
+    pub authority: Signer<'info>,
+
     #[account(
+        mut,
         seeds = [CONFIG_SEED, config.pool_id.as_ref()],
-        bump = config.bump
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
     )]
     pub config: Account<'info, ConfigAccount>,
+}
 
-    /// The progress account for this day
-    /// CHECK: Will be initialized by the instruction if needed
+#[derive(Accounts)]
+#[instruction(day_id: i64)]
+pub struct Decommission<'info> {
+    /// Pays for today's progress account and receives the rent of the closed positions
     #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.pool_id.as_ref()],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ConfigAccount>,
+
+    /// Program-wide protocol config, for its pause bits and protocol fee rate
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Authority of the protocol fee vault
+    /// CHECK: PDA owner of the fee vault, validated by seeds
+    #[account(
+        seeds = [PROTOCOL_FEE_VAULT_SEED],
+        bump
+    )]
+    pub protocol_fee_authority: AccountInfo<'info>,
+
+    /// Program-wide fee vault for the quote mint, receives the protocol fee on the final claim
+    #[account(
+        mut,
+        associated_token::mint = config.quote_mint,
+        associated_token::authority = protocol_fee_authority,
+        associated_token::token_program = token_program
+    )]
+    pub protocol_fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Today's progress account, created by the instruction to record the final claim
+    /// CHECK: Validated by seeds and created or deserialized by the instruction
+    #[account(
+        mut,
+        seeds = [PROGRESS_SEED, config.key().as_ref(), &day_id.to_le_bytes()],
+        bump
+    )]
     pub progress: AccountInfo<'info>,
 
-    /// The honorary position account
-    /// CHECK: Validated by cp-amm program
+    /// The config's primary honorary position
+    /// CHECK: Must be the config's position, validated by cp-amm
+    #[account(mut, address = config.position_id @ ErrorCode::MissingHonoraryPosition)]
     pub position: AccountInfo<'info>,
 
     /// The position owner PDA
+    /// CHECK: PDA owner of the honorary positions, validated by seeds
     #[account(
-        seeds = [VAULT_SEED, VAULT_SEED, INVESTOR_FEE_POS_OWNER_SEED],
+        seeds = [VAULT_SEED, config.key().as_ref(), INVESTOR_FEE_POS_OWNER_SEED],
         bump
     )]
     pub investor_fee_position_owner_pda: AccountInfo<'info>,
 
-    /// The program's quote treasury ATA
+    #[account(
+        address = config.quote_mint,
+        mint::token_program = token_program
+    )]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        address = config.base_mint,
+        mint::token_program = base_token_program
+    )]
+    pub base_mint: InterfaceAccount<'info, Mint>,
+
+    /// The config's quote treasury ATA, receives the final claim and removed quote liquidity
     #[account(
         mut,
         associated_token::mint = config.quote_mint,
-        associated_token::authority = program_authority
+        associated_token::authority = program_authority,
+        associated_token::token_program = token_program
+    )]
+    pub program_quote_treasury: InterfaceAccount<'info, TokenAccount>,
+
+    /// The config's base treasury ATA, receives base fees and removed base liquidity and is swept to `base_sweep_recipient`
+    #[account(
+        mut,
+        associated_token::mint = config.base_mint,
+        associated_token::authority = program_authority,
+        associated_token::token_program = base_token_program
     )]
-    pub program_quote_treasury: Account<'info, TokenAccount>,
+    pub program_base_treasury: InterfaceAccount<'info, TokenAccount>,
 
-    /// The program authority (for treasury operations)
-    /// CHECK: This should be a PDA derived from the program
+    /// The config's program authority (for treasury operations)
+    /// CHECK: PDA signer for the treasuries, validated by seeds
+    #[account(
+        seeds = [PROGRAM_AUTHORITY_SEED, config.key().as_ref()],
+        bump
+    )]
     pub program_authority: AccountInfo<'info>,
 
-    /// The creator's quote ATA
     #[account(
         mut,
-        associated_token::mint = config.quote_mint,
-        associated_token::authority = creator
+        address = config.base_sweep_recipient @ ErrorCode::InvalidSweepRecipient,
+        token::mint = config.base_mint,
+        token::token_program = base_token_program
     )]
-    pub creator_quote_ata: Account<'info, TokenAccount>,
-
-    /// The creator
-    /// CHECK: Validated by the caller
-    pub creator: AccountInfo<'info>,
+    pub base_sweep_recipient: InterfaceAccount<'info, TokenAccount>,
 
-    /// The Streamflow program
-    /// CHECK: Validated by the caller
-    pub streamflow_program: AccountInfo<'info>,
+    /// The cp-amm program, required to claim and close through CPI once the config sets one
+    /// CHECK: Must be the config's cp-amm program
+    #[account(address = config.cp_amm_program @ ErrorCode::InvalidCpAmmAccount)]
+    pub cp_amm_program: Option<AccountInfo<'info>>,
 
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// Token program of the quote mint
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the base mint
+    pub base_token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct WithdrawProtocolFees<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump,
+        has_one = admin @ ErrorCode::NotProtocolAdmin
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Authority of the protocol fee vault
+    /// CHECK: PDA owner of the fee vault, validated by seeds
+    #[account(
+        seeds = [PROTOCOL_FEE_VAULT_SEED],
+        bump
+    )]
+    pub protocol_fee_authority: AccountInfo<'info>,
+
+    /// Mint of the fees being withdrawn
+    #[account(mint::token_program = token_program)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    /// The fee vault for `quote_mint`
+    #[account(
+        mut,
+        associated_token::mint = quote_mint,
+        associated_token::authority = protocol_fee_authority,
+        associated_token::token_program = token_program
+    )]
+    pub protocol_fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Receives the withdrawn fees
+    #[account(mut, token::mint = quote_mint, token::token_program = token_program)]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(day_id: i64)]
+pub struct CloseProgress<'info> {
+    /// The config account (provides the retention period)
+    #[account(
+        seeds = [CONFIG_SEED, config.pool_id.as_ref()],
+        bump = config.bump
+    )]
+    pub config: Account<'info, ConfigAccount>,
+
+    /// Program-wide protocol config, for its pause bits
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// The progress account being closed
+    #[account(
+        mut,
+        seeds = [PROGRESS_SEED, config.key().as_ref(), &day_id.to_le_bytes()],
+        bump = progress.bump,
+        has_one = config @ ErrorCode::InvalidProgressAccount,
+        close = payer
+    )]
+    pub progress: Account<'info, ProgressAccount>,
+
+    /// The original payer of the progress account, receives the rent refund
+    /// CHECK: Must match the payer recorded in the progress account
+    #[account(mut, address = progress.payer @ ErrorCode::InvalidRentRecipient)]
+    pub payer: AccountInfo<'info>,
+
+    /// The config's rolling history to fold the day's totals into, required once it exists
+    #[account(
+        mut,
+        seeds = [PROGRESS_HISTORY_SEED, config.key().as_ref()],
+        bump = progress_history.bump
+    )]
+    pub progress_history: Option<Account<'info, ProgressHistory>>,
+}
+
 // Data structures
 #[account]
 pub struct ConfigAccount {
@@ -419,32 +2247,554 @@ pub struct ConfigAccount {
     pub tick_lower: i32,
     pub tick_upper: i32,
     pub creation_ts: i64,
+    pub authority: Pubkey,
+    pub progress_retention_days: u16,
+    pub crank_tip_bps: u16,
+    pub crank_tip_max: u64,
+    pub lock_provider: LockProviderKind,
+    pub lock_program: Pubkey,
+    /// Positions registered after initialization, claimed alongside `position_id`
+    pub additional_positions: Vec<HonoraryPosition>,
+    pub remainder_split: RemainderSplit,
+    pub fee_share_schedule: FeeShareSchedule,
+    pub weighting_mode: WeightingMode,
+    /// Most of a day's investor pool one investor may receive, 0 for no cap
+    pub max_investor_share_bps: u16,
+    pub investor_cap_policy: InvestorCapPolicy,
+    /// Most investors may receive per day in micro-USD, 0 for no USD cap
+    pub usd_daily_cap: u64,
+    /// Pyth-style price account quoting the quote mint in USD
+    pub price_feed: Pubkey,
+    /// Program that must own `price_feed`
+    pub oracle_program: Pubkey,
+    pub max_price_age_secs: u32,
+    pub max_price_confidence_bps: u16,
+    /// Caps on what investors may receive over rolling windows of days
+    pub rolling_caps: RollingCaps,
+    pub cap_overflow_policy: CapOverflowPolicy,
+    /// May set `paused`; defaults to the initializer
+    pub guardian: Pubkey,
+    /// `PAUSE_*` bits stopping claims, investor payouts or creator payouts for this config
+    pub paused: u8,
+    /// When every stage was last paused, 0 while any stage runs
+    pub fully_paused_at: i64,
+    /// Quote token account `emergency_withdraw` sends the treasury to
+    pub recovery_ata: Pubkey,
+    /// Token accounts `decommission` sweeps the quote and base treasuries to
+    pub quote_sweep_recipient: Pubkey,
+    pub base_sweep_recipient: Pubkey,
+    /// Set by `decommission`; cranks then only pay out the day of its final claim
+    pub closed: bool,
+    pub base_fee_policy: BaseFeePolicy,
+    /// Most a `SwapToQuote` swap may fall short of the oracle price
+    pub max_swap_slippage_bps: u16,
+    /// Pyth-style price account quoting the base mint in USD, owned by `oracle_program`
+    pub base_price_feed: Pubkey,
+    /// Base token account that receives base fees under `RouteToCreator`
+    pub creator_base_ata: Pubkey,
+    /// cp-amm program the honorary positions live in; claims fail until it is set
+    pub cp_amm_program: Pubkey,
+    /// When `request_decommission` was called, 0 if it never was
+    pub decommission_requested_at: i64,
+    /// Days whose fees were claimed but which are not finalized yet
+    pub open_days: u32,
+    /// The last day the crank claimed fees on; each day is claimed at most once
+    pub last_claimed_day_id: i64,
+    /// Set by `initialize_progress_history`; `close_progress` then always folds into it
+    pub has_progress_history: bool,
+    /// Owner of the quote ATA `finalize_day` pays the remainder to; defaults to the initializer
+    pub creator: Pubkey,
     pub bump: u8,
 }
 
 impl ConfigAccount {
-    pub const INIT_SPACE: usize = 32 + 32 + 32 + 32 + 4 + 4 + 8 + 1;
+    pub const INIT_SPACE: usize = 32 + 32 + 32 + 32 + 4 + 4 + 8 + 32 + 2 + 2 + 8 + 1 + 32
+        + 4 + HonoraryPosition::INIT_SPACE * (MAX_HONORARY_POSITIONS - 1)
+        + RemainderSplit::INIT_SPACE
+        + FeeShareSchedule::INIT_SPACE
+        + 1
+        + 2 + 1
+        + 8 + 32 + 32 + 4 + 2
+        + RollingCaps::INIT_SPACE + 1
+        + 32 + 1 + 8 + 32
+        + 32 + 32 + 1
+        + 1 + 2 + 32 + 32
+        + 32
+        + 8 + 4 + 8 + 1
+        + 32
+        + 1;
+
+    /// Every honorary position the crank claims from, with its pool, starting with `position_id`
+    pub fn honorary_positions(&self) -> impl Iterator<Item = (Pubkey, Pubkey)> + '_ {
+        std::iter::once((self.position_id, self.pool_id))
+            .chain(self.additional_positions.iter().map(|position| (position.position, position.pool)))
+    }
+
+    /// Tip owed to the cranker for a day's claim: `crank_tip_bps` of it, capped at `crank_tip_max`
+    pub fn crank_tip_for(&self, claimed_quote: u64) -> u64 {
+        let tip = (claimed_quote as u128 * self.crank_tip_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+        std::cmp::min(tip, self.crank_tip_max)
+    }
+}
+
+/// Program-wide settings controlled by the protocol admin
+#[account]
+pub struct ProtocolConfig {
+    pub admin: Pubkey,
+    /// Share of every day's claim sent to the protocol fee vault, at most `MAX_PROTOCOL_FEE_BPS`
+    pub protocol_fee_bps: u16,
+    /// May set `paused`; defaults to the admin
+    pub guardian: Pubkey,
+    /// `PAUSE_*` bits applied to every config
+    pub paused: u8,
+    pub bump: u8,
+}
+
+impl ProtocolConfig {
+    pub const INIT_SPACE: usize = 32 + 2 + 32 + 1 + 1;
+
+    /// Protocol fee owed on a day's claim
+    pub fn protocol_fee_for(&self, claimed_quote: u64) -> u64 {
+        (claimed_quote as u128 * self.protocol_fee_bps as u128 / BPS_DENOMINATOR as u128) as u64
+    }
+}
+
+/// Optional protocol config changes applied by `update_protocol_config`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct ProtocolConfigUpdateParams {
+    pub admin: Option<Pubkey>,
+    pub protocol_fee_bps: Option<u16>,
+    pub guardian: Option<Pubkey>,
+}
+
+/// Optional config changes applied by `update_config`; `None` leaves a field untouched
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct ConfigUpdateParams {
+    pub progress_retention_days: Option<u16>,
+    pub crank_tip_bps: Option<u16>,
+    pub crank_tip_max: Option<u64>,
+    pub lock_provider: Option<LockProviderKind>,
+    pub lock_program: Option<Pubkey>,
+    pub remainder_split: Option<RemainderSplit>,
+    pub fee_share_schedule: Option<FeeShareSchedule>,
+    pub weighting_mode: Option<WeightingMode>,
+    pub max_investor_share_bps: Option<u16>,
+    pub investor_cap_policy: Option<InvestorCapPolicy>,
+    pub usd_daily_cap: Option<u64>,
+    pub price_feed: Option<Pubkey>,
+    pub oracle_program: Option<Pubkey>,
+    pub max_price_age_secs: Option<u32>,
+    pub max_price_confidence_bps: Option<u16>,
+    pub rolling_caps: Option<RollingCaps>,
+    pub cap_overflow_policy: Option<CapOverflowPolicy>,
+    pub guardian: Option<Pubkey>,
+    pub recovery_ata: Option<Pubkey>,
+    pub quote_sweep_recipient: Option<Pubkey>,
+    pub base_sweep_recipient: Option<Pubkey>,
+    pub base_fee_policy: Option<BaseFeePolicy>,
+    pub max_swap_slippage_bps: Option<u16>,
+    pub base_price_feed: Option<Pubkey>,
+    pub creator_base_ata: Option<Pubkey>,
+    pub cp_amm_program: Option<Pubkey>,
+    pub creator: Option<Pubkey>,
+}
+
+/// Piecewise-linear investor fee share over time, anchored at the config's `creation_ts`
+///
+/// Empty means the crank's `investor_fee_share_bps` argument is used as a flat share.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct FeeShareSchedule {
+    pub points: Vec<FeeSharePoint>,
+}
+
+impl FeeShareSchedule {
+    pub const INIT_SPACE: usize = 4 + FeeSharePoint::INIT_SPACE * MAX_FEE_SHARE_POINTS;
+
+    /// Up to twelve points with strictly increasing offsets and shares of at most 10000 bps
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.points.len() <= MAX_FEE_SHARE_POINTS,
+            ErrorCode::InvalidFeeShareSchedule
+        );
+        require!(
+            self.points
+                .iter()
+                .all(|point| point.offset_seconds >= 0 && point.share_bps as u64 <= BPS_DENOMINATOR),
+            ErrorCode::InvalidFeeShareSchedule
+        );
+        require!(
+            self.points
+                .windows(2)
+                .all(|pair| pair[0].offset_seconds < pair[1].offset_seconds),
+            ErrorCode::InvalidFeeShareSchedule
+        );
+        Ok(())
+    }
+
+    /// Share `elapsed` seconds after creation, interpolated between the surrounding points
+    /// and held flat before the first and after the last one
+    pub fn share_bps_at(&self, elapsed: i64) -> Option<u16> {
+        let first = self.points.first()?;
+        if elapsed <= first.offset_seconds {
+            return Some(first.share_bps);
+        }
+        for pair in self.points.windows(2) {
+            let (start, end) = (&pair[0], &pair[1]);
+            if elapsed < end.offset_seconds {
+                let progress = (elapsed - start.offset_seconds) as i128;
+                let span = (end.offset_seconds - start.offset_seconds) as i128;
+                let delta = end.share_bps as i128 - start.share_bps as i128;
+                return Some((start.share_bps as i128 + delta * progress / span) as u16);
+            }
+        }
+        self.points.last().map(|point| point.share_bps)
+    }
+}
+
+/// Investor share reached `offset_seconds` after the config was created
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct FeeSharePoint {
+    pub offset_seconds: i64,
+    pub share_bps: u16,
+}
+
+impl FeeSharePoint {
+    pub const INIT_SPACE: usize = 8 + 2;
+}
+
+/// Limits on what investors may receive over rolling windows, e.g. 7 and 30 days
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct RollingCaps {
+    pub caps: Vec<RollingCap>,
+}
+
+impl RollingCaps {
+    pub const INIT_SPACE: usize = 4 + RollingCap::INIT_SPACE * cap_window::MAX_ROLLING_CAPS;
+
+    /// Up to four caps, each over a window of 1 to 30 days
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.caps.len() <= cap_window::MAX_ROLLING_CAPS,
+            ErrorCode::InvalidRollingCaps
+        );
+        require!(
+            self.caps.iter().all(|cap| {
+                (1..=cap_window::MAX_CAP_WINDOW_DAYS as u16).contains(&cap.window_days)
+            }),
+            ErrorCode::InvalidRollingCaps
+        );
+        Ok(())
+    }
+
+    /// Most `day_id` may still distribute before any of the caps binds
+    pub fn room(&self, cap_window: &CapWindow, day_id: i64) -> u64 {
+        self.caps
+            .iter()
+            .map(|cap| cap.cap.saturating_sub(cap_window.total(day_id, cap.window_days)))
+            .min()
+            .unwrap_or(u64::MAX)
+    }
+}
+
+/// At most `cap` quote distributed over the `window_days` days ending today
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RollingCap {
+    pub window_days: u16,
+    pub cap: u64,
+}
+
+impl RollingCap {
+    pub const INIT_SPACE: usize = 2 + 8;
+}
+
+/// How `finalize_day` divides the creator remainder; empty pays it all to the creator
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct RemainderSplit {
+    pub recipients: Vec<RemainderRecipient>,
+}
+
+impl RemainderSplit {
+    pub const INIT_SPACE: usize = 4 + RemainderRecipient::INIT_SPACE * MAX_REMAINDER_RECIPIENTS;
+
+    /// A split is either empty or up to eight shares summing to exactly 10000 bps
+    pub fn validate(&self) -> Result<()> {
+        if self.recipients.is_empty() {
+            return Ok(());
+        }
+        require!(
+            self.recipients.len() <= MAX_REMAINDER_RECIPIENTS,
+            ErrorCode::InvalidRemainderSplit
+        );
+        let total_bps: u64 = self.recipients.iter().map(|recipient| recipient.bps as u64).sum();
+        require!(total_bps == BPS_DENOMINATOR, ErrorCode::InvalidRemainderSplit);
+        Ok(())
+    }
+
+    /// Each recipient's share of `amount`, rounded down; the last recipient absorbs the dust
+    pub fn amounts(&self, amount: u64) -> Vec<u64> {
+        let mut remaining = amount;
+        let mut amounts = Vec::with_capacity(self.recipients.len());
+        for (index, recipient) in self.recipients.iter().enumerate() {
+            let share = if index + 1 == self.recipients.len() {
+                remaining
+            } else {
+                (amount as u128 * recipient.bps as u128 / BPS_DENOMINATOR as u128) as u64
+            };
+            remaining = remaining.saturating_sub(share);
+            amounts.push(share);
+        }
+        amounts
+    }
+}
+
+/// One recipient of the creator remainder
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RemainderRecipient {
+    /// Quote token account that receives this share
+    pub recipient: Pubkey,
+    pub bps: u16,
+}
+
+impl RemainderRecipient {
+    pub const INIT_SPACE: usize = 32 + 2;
 }
 
 #[account]
 pub struct ProgressAccount {
+    /// The config this day belongs to
+    pub config: Pubkey,
     pub day_id: i64,
+    /// Instant at which every lock is evaluated for this day (the day's start)
+    pub snapshot_ts: i64,
     pub last_distribution_ts: i64,
     pub claimed_quote_for_day: u64,
+    pub crank_tip: u64,
+    /// Protocol fee skimmed off the claim into the protocol fee vault
+    pub protocol_fee: u64,
     pub cumulative_distributed_today: u64,
     pub carry_over: u64,
     pub pagination_cursor: u64,
-    // Note: For simplicity, we'll use a single progress PDA per day
-    // In production, you might want to use multiple PDAs for pagination state
+    pub locked_total: u64,
+    pub investors_paid: u64,
+    pub investors_skipped: u64,
+    /// Token-2022 transfer fees withheld from payouts out of the treasury
+    pub transfer_fees_withheld: u64,
+    /// Day-wide totals gathered by `tally_investor_page` before the first payout
+    pub investors_tallied: u64,
+    pub tally_locked_total: u64,
+    pub tally_weight_total: u128,
+    /// Largest tallied weights, descending; the only ones a per-investor cap can bind
+    pub tally_top_weights: Vec<u128>,
+    /// Last stream key tallied and a running hash of every tallied key, in order
+    pub tally_cursor: Pubkey,
+    pub tally_digest: [u8; 32],
+    /// The same over the investors cranked on a tallied day, checked against the tally at finalize
+    pub payout_cursor: Pubkey,
+    pub payout_digest: [u8; 32],
+    /// Investor pool, per-investor cap and rate fixed by the first payout page
+    pub investor_pool: u64,
+    pub investor_cap: u64,
+    pub cap_rate_numerator: u128,
+    pub cap_rate_denominator: u128,
+    /// Overflow held back on earlier days and added to today's investor pool
+    pub held_back_reserved: u64,
+    /// The part of the reserve investors were actually paid or carried, fixed by `finalize_day`
+    pub held_back_released: u64,
+    /// Most the rolling caps let the day distribute
+    pub window_room: u64,
+    /// The part of the day's investor pool above the daily and rolling caps
+    pub cap_overflow: u64,
+    /// Overflow held back or retained instead of paid to the creator
+    pub cap_overflow_withheld: u64,
+    pub finalized: bool,
+    pub payer: Pubkey,
+    pub bump: u8,
 }
 
 impl ProgressAccount {
-    pub const INIT_SPACE: usize = 8 + 8 + 8 + 8 + 8 + 8 + 8;
+    pub const INIT_SPACE: usize = 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8
+        + 8 + 8 + 16 + 4 + 16 * payout_cap::MAX_TRACKED_WEIGHTS
+        + 32 + 32 + 32 + 32
+        + 8 + 8 + 16 + 16
+        + 8 + 8 + 8 + 8 + 8
+        + 1 + 32 + 1;
+
+    /// Fresh progress for `config`'s `day_id`, with locks snapshotted at the start of the day
+    pub fn new(config: Pubkey, day_id: i64, payer: Pubkey, bump: u8) -> Self {
+        Self {
+            config,
+            day_id,
+            snapshot_ts: day_id * DAY_IN_SECONDS,
+            last_distribution_ts: 0,
+            claimed_quote_for_day: 0,
+            crank_tip: 0,
+            protocol_fee: 0,
+            cumulative_distributed_today: 0,
+            carry_over: 0,
+            pagination_cursor: 0,
+            locked_total: 0,
+            investors_paid: 0,
+            investors_skipped: 0,
+            transfer_fees_withheld: 0,
+            investors_tallied: 0,
+            tally_locked_total: 0,
+            tally_weight_total: 0,
+            tally_top_weights: Vec::with_capacity(payout_cap::MAX_TRACKED_WEIGHTS),
+            tally_cursor: Pubkey::default(),
+            tally_digest: [0; 32],
+            payout_cursor: Pubkey::default(),
+            payout_digest: [0; 32],
+            investor_pool: 0,
+            investor_cap: 0,
+            cap_rate_numerator: 0,
+            cap_rate_denominator: 0,
+            held_back_reserved: 0,
+            held_back_released: 0,
+            window_room: 0,
+            cap_overflow: 0,
+            cap_overflow_withheld: 0,
+            finalized: false,
+            payer,
+            bump,
+        }
+    }
+
+    /// Claimed quote left for investors and the creator after the protocol fee and crank tip
+    pub fn distributable_quote(&self) -> u64 {
+        self.claimed_quote_for_day
+            .saturating_sub(self.protocol_fee)
+            .saturating_sub(self.crank_tip)
+    }
+
+    /// Everything that was neither paid to investors, carried as dust nor withheld as cap overflow
+    ///
+    /// Only the held-back overflow investors were paid or carried counts as available;
+    /// the rest of the reserve goes back to the cap window.
+    pub fn creator_remainder(&self) -> u64 {
+        self.distributable_quote()
+            .saturating_add(self.held_back_released)
+            .saturating_sub(self.cumulative_distributed_today)
+            .saturating_sub(self.carry_over)
+            .saturating_sub(self.cap_overflow_withheld)
+    }
+
+    /// Release the part of the held-back reserve the day's payouts and dust drew on,
+    /// returning the rest
+    ///
+    /// Payouts and dust draw on the reserve first.
+    pub fn release_held_back(&mut self) -> u64 {
+        let returned = self
+            .held_back_reserved
+            .saturating_sub(self.cumulative_distributed_today.saturating_add(self.carry_over));
+        self.held_back_released = self.held_back_reserved - returned;
+        returned
+    }
+
+    /// Add one investor to the day-wide totals
+    pub fn tally(&mut self, locked_amount: u64, weight: u128) {
+        self.tally_locked_total = self.tally_locked_total.saturating_add(locked_amount);
+        self.tally_weight_total = self.tally_weight_total.saturating_add(weight);
+        payout_cap::track_weight(&mut self.tally_top_weights, weight);
+        self.investors_tallied = self.investors_tallied.saturating_add(1);
+    }
+}
+
+/// Immutable per-day record written by `finalize_day`
+#[account]
+pub struct DaySummary {
+    pub day_id: i64,
+    pub claimed_quote: u64,
+    pub crank_tip: u64,
+    pub protocol_fee: u64,
+    pub distributed_to_investors: u64,
+    pub creator_amount: u64,
+    pub dust_carried: u64,
+    pub investors_paid: u64,
+    pub investors_skipped: u64,
+    pub locked_total: u64,
+    pub transfer_fees_withheld: u64,
+    pub finalized_ts: i64,
+    pub bump: u8,
+}
+
+impl DaySummary {
+    pub const INIT_SPACE: usize = 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1;
+}
+
+/// Rolling per-config totals of progress accounts closed by `close_progress`
+#[account]
+pub struct ProgressHistory {
+    pub config: Pubkey,
+    pub days_closed: u64,
+    pub total_claimed: u64,
+    pub total_distributed_to_investors: u64,
+    pub total_creator_amount: u64,
+    pub total_crank_tips: u64,
+    pub total_protocol_fees: u64,
+    pub total_dust_carried: u64,
+    pub last_closed_day_id: i64,
+    pub bump: u8,
+}
+
+impl ProgressHistory {
+    pub const INIT_SPACE: usize = 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1;
+}
+
+/// Per-config ring buffer of daily investor totals that rolling caps are enforced against
+#[account]
+pub struct CapWindow {
+    pub config: Pubkey,
+    /// Slot `day_id % MAX_CAP_WINDOW_DAYS` holds that day's total
+    pub slots: [CapWindowSlot; cap_window::MAX_CAP_WINDOW_DAYS],
+    /// Overflow held back under the `CarryForward` policy, released into later days'
+    /// investor pools as far as each day's cap allows
+    pub held_back: u64,
+    /// Overflow kept in the treasury under the `Retain` policy
+    pub retained: u64,
+    pub bump: u8,
+}
+
+impl CapWindow {
+    pub const INIT_SPACE: usize = 32
+        + CapWindowSlot::INIT_SPACE * cap_window::MAX_CAP_WINDOW_DAYS
+        + 8 + 8 + 1;
+
+    /// Add a page's payouts to `day_id`'s total
+    pub fn record(&mut self, day_id: i64, amount: u64) {
+        cap_window::record(&mut self.slots, day_id, amount);
+    }
+
+    /// Quote distributed over the `window_days` days ending with `day_id`
+    pub fn total(&self, day_id: i64, window_days: u16) -> u64 {
+        cap_window::window_total(&self.slots, day_id, window_days)
+    }
+}
+
+/// An honorary position registered with `add_honorary_position`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct HonoraryPosition {
+    pub position: Pubkey,
+    pub pool: Pubkey,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+}
+
+impl HonoraryPosition {
+    pub const INIT_SPACE: usize = 32 + 32 + 4 + 4;
+}
+
+/// Fees one honorary position's claim landed in the treasuries, net of transfer fees
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PositionFeeClaim {
+    pub position: Pubkey,
+    pub claimed_quote: u64,
+    pub claimed_base: u64,
 }
 
 // Investor record for pagination
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct InvestorRecord {
+    /// The investor's lock account (a Streamflow stream or another supported lock)
     pub stream_pubkey: Pubkey,
     pub investor_quote_ata: Pubkey,
 }
@@ -462,6 +2812,16 @@ pub struct HonoraryPositionInitialized {
 pub struct QuoteFeesClaimed {
     pub day_id: i64,
     pub claimed_quote: u64,
+    pub crank_tip: u64,
+    pub cranker: Pubkey,
+    /// Quote each honorary position's claim landed in the treasury
+    pub position_claims: Vec<PositionFeeClaim>,
+    pub protocol_fee: u64,
+    pub protocol_fee_bps: u16,
+    /// Base fees claimed across all positions, handled by the config's base fee policy
+    pub claimed_base: u64,
+    /// Quote received for base fees under `SwapToQuote`, included in `claimed_quote`
+    pub swapped_quote: u64,
 }
 
 #[event]
@@ -469,12 +2829,86 @@ pub struct InvestorPayoutPage {
     pub day_id: i64,
     pub page_id: u64,
     pub page_total_distributed: u64,
+    pub page_transfer_fees: u64,
+}
+
+#[event]
+pub struct InvestorPageTallied {
+    pub day_id: i64,
+    pub investors: u64,
+    pub page_weight_total: u128,
+}
+
+#[event]
+pub struct InvestorStreamInactive {
+    pub day_id: i64,
+    pub stream: Pubkey,
+    pub investor_quote_ata: Pubkey,
+    pub status: LockStatus,
 }
 
 #[event]
 pub struct CreatorPayoutDayClosed {
     pub day_id: i64,
     pub creator_amount: u64,
+    /// Where the creator amount went: the creator ATA, or each remainder split recipient
+    pub remainder_payouts: Vec<RemainderPayout>,
+    /// Cap overflow held back or retained instead of paid out
+    pub cap_overflow_withheld: u64,
+    /// Treasury balance sent to the quote sweep recipient on a decommissioned config's last day
+    pub quote_swept: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RemainderPayout {
+    pub recipient: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct PauseUpdated {
+    /// The config or protocol config whose pause bits changed
+    pub account: Pubkey,
+    pub guardian: Pubkey,
+    pub paused: u8,
+}
+
+#[event]
+pub struct EmergencyWithdrawal {
+    pub config: Pubkey,
+    pub recovery_ata: Pubkey,
+    /// Treasury balance sent to the recovery account
+    pub amount: u64,
+    /// Caller-supplied code recording why the withdrawal was made
+    pub reason: u8,
+    pub ts: i64,
+}
+
+#[event]
+pub struct DecommissionRequested {
+    pub config: Pubkey,
+    pub ts: i64,
+}
+
+#[event]
+pub struct ConfigDecommissioned {
+    pub config: Pubkey,
+    /// Final fee claim across all honorary positions, paid out as the day's claim
+    pub claimed_quote: u64,
+    pub claimed_base: u64,
+    /// Liquidity withdrawn from the honorary positions before closing them
+    pub removed_quote: u64,
+    pub removed_base: u64,
+    pub positions_closed: u8,
+    /// Base treasury balance sent to the base sweep recipient
+    pub base_swept: u64,
+}
+
+#[event]
+pub struct ProgressClosed {
+    pub day_id: i64,
+    pub payer: Pubkey,
+    pub folded_into_history: bool,
 }
 
 // Error codes
@@ -486,13 +2920,13 @@ pub enum ErrorCode {
     BaseFeeDetected,
     #[msg("Invalid pool configuration for quote-only fees")]
     InvalidPoolForQuoteOnly,
-    #[msg("Day gate not passed - must wait 24 hours between distributions")]
+    #[msg("Day gate not passed - today's fees have already been claimed")]
     DayGateNotPassed,
     #[msg("Page already processed - idempotency check failed")]
     AlreadyProcessedPage,
     #[msg("Missing investor ATA - could not create associated token account")]
     MissingInvestorATA,
-    #[msg("Daily cap exceeded")]
+    #[msg("Daily cap is below what the day has already distributed")]
     CapExceeded,
     #[msg("Insufficient claimed quote for expected payouts")]
     InsufficientClaimedQuote,
@@ -504,4 +2938,102 @@ pub enum ErrorCode {
     MathOverflow,
     #[msg("Invalid progress account")]
     InvalidProgressAccount,
+    #[msg("Day already finalized")]
+    DayAlreadyFinalized,
+    #[msg("Day not finalized")]
+    DayNotFinalized,
+    #[msg("Progress retention period has not elapsed")]
+    ProgressRetentionNotElapsed,
+    #[msg("Rent recipient does not match the recorded payer")]
+    InvalidRentRecipient,
+    #[msg("Signer is not the config authority")]
+    Unauthorized,
+    #[msg("Basis points must not exceed 10000")]
+    InvalidBasisPoints,
+    #[msg("Cranker quote ATA is required to pay the crank tip")]
+    MissingCrankerQuoteAta,
+    #[msg("Lock account is not owned by the configured lock program")]
+    InvalidLockProgram,
+    #[msg("Quote mint has an extension the distributor does not support")]
+    UnsupportedMintExtension,
+    #[msg("Honorary position is already registered")]
+    DuplicateHonoraryPosition,
+    #[msg("Config already has the maximum number of honorary positions")]
+    TooManyHonoraryPositions,
+    #[msg("Honorary position account missing from remaining accounts")]
+    MissingHonoraryPosition,
+    #[msg("Remainder split needs at most 8 recipients whose bps sum to 10000")]
+    InvalidRemainderSplit,
+    #[msg("Remainder split recipient missing from remaining accounts")]
+    MissingRemainderRecipient,
+    #[msg("Protocol fee exceeds the maximum")]
+    ProtocolFeeTooHigh,
+    #[msg("Signer is not the protocol admin")]
+    NotProtocolAdmin,
+    #[msg("Fee share schedule needs at most 12 points with increasing offsets and shares up to 10000 bps")]
+    InvalidFeeShareSchedule,
+    #[msg("Per-investor cap must be 0 or between 500 and 10000 bps")]
+    InvalidInvestorCap,
+    #[msg("Per-investor cap requires the day's investors to be tallied before payouts")]
+    InvestorTallyRequired,
+    #[msg("Investor tally is closed once payouts have started")]
+    InvestorTallyClosed,
+    #[msg("A USD daily cap or a SwapToQuote swap requires the config's price feeds")]
+    MissingPriceFeed,
+    #[msg("Price feed is not the config's feed or is not a valid price account")]
+    InvalidPriceFeed,
+    #[msg("Oracle price must be positive")]
+    InvalidPrice,
+    #[msg("Oracle price is older than the config allows")]
+    StalePrice,
+    #[msg("Oracle price confidence interval is wider than the config allows")]
+    PriceConfidenceTooWide,
+    #[msg("Rolling caps need at most 4 entries with windows of 1 to 30 days")]
+    InvalidRollingCaps,
+    #[msg("Rolling caps and withheld cap overflow require the config's cap window")]
+    MissingCapWindow,
+    #[msg("Instruction is paused")]
+    Paused,
+    #[msg("Signer is not the guardian")]
+    NotGuardian,
+    #[msg("Pause flags may only set the claim, investor payout and creator payout bits")]
+    InvalidPauseFlags,
+    #[msg("Emergency withdrawal requires every stage of the config to be paused")]
+    NotPaused,
+    #[msg("Emergency withdrawal timelock has not elapsed")]
+    EmergencyTimelockNotElapsed,
+    #[msg("Recovery account is not the one registered on the config")]
+    InvalidRecoveryAccount,
+    #[msg("Config has been decommissioned")]
+    ConfigClosed,
+    #[msg("Sweep recipient is not the one registered on the config")]
+    InvalidSweepRecipient,
+    #[msg("Base fees need the pool, base treasury or creator base account for the config's base fee policy")]
+    MissingBaseFeeAccounts,
+    #[msg("Swap returned less quote than the configured slippage allows")]
+    SlippageExceeded,
+    #[msg("cp-amm CPIs need the cp-amm program, base accounts and every pool, pool authority and vault")]
+    MissingCpAmmAccounts,
+    #[msg("Account is not the config's cp-amm program or one of its pools or positions")]
+    InvalidCpAmmAccount,
+    #[msg("Signer is not the program's upgrade authority")]
+    NotUpgradeAuthority,
+    #[msg("Investors must be passed in strictly increasing stream key order")]
+    InvestorOutOfOrder,
+    #[msg("Investors paid do not match the investors tallied")]
+    TallyMismatch,
+    #[msg("Decommissioning needs request_decommission and its notice period to have passed")]
+    DecommissionNoticeNotElapsed,
+    #[msg("Today's fees have already been claimed")]
+    DayAlreadyClaimed,
+    #[msg("The config keeps a progress history, which must be passed to close_progress")]
+    MissingProgressHistory,
+    #[msg("The config has no lock program yet - set one with update_config")]
+    LockProgramNotSet,
+    #[msg("Creator does not match the config's creator")]
+    InvalidCreator,
+    #[msg("An untallied day can only be finalized once it is over")]
+    DayNotOver,
+    #[msg("Position is in another pool or not owned by the config's position owner PDA")]
+    InvalidHonoraryPosition,
 }
\ No newline at end of file
@@ -0,0 +1,107 @@
+use anchor_lang::prelude::*;
+use stream_vesting::VestingSchedule;
+
+use crate::mock_streamflow::MockStreamflowLockProvider;
+
+/// Source of investor locked balances, read directly from lock accounts
+pub trait LockProvider {
+    /// Read the lock held in `lock_account` as of `timestamp`
+    fn read_lock(&self, lock_account: &AccountInfo, timestamp: i64) -> Result<LockState>;
+}
+
+/// Lifecycle state of a lock; only `Active` locks count as locked
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockStatus {
+    Active,
+    /// Cancelled by the sender; unvested tokens went back to the sender
+    Cancelled,
+    /// Lock account closed
+    Closed,
+    /// Beneficiary changed away from the investor the allocation was made to
+    Transferred,
+}
+
+/// What a `LockProvider` reports for a single lock account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LockState {
+    pub beneficiary: Pubkey,
+    pub locked_amount: u64,
+    pub status: LockStatus,
+    /// Amount originally locked
+    pub allocation: u64,
+    /// When the lock fully unlocks
+    pub end_ts: i64,
+}
+
+impl LockState {
+    /// Mark the lock `Transferred` (and unlocked) if it no longer pays `investor`
+    pub fn for_investor(self, investor: &Pubkey) -> Self {
+        if self.status == LockStatus::Active && self.beneficiary != *investor {
+            Self {
+                locked_amount: 0,
+                status: LockStatus::Transferred,
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+}
+
+/// Lock program a config reads investor locks from
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockProviderKind {
+    /// mock-streamflow's `StreamData` accounts, for tests and demos
+    MockStreamflow,
+    LinearLock,
+}
+
+impl LockProviderKind {
+    pub fn read_lock(&self, lock_account: &AccountInfo, timestamp: i64) -> Result<LockState> {
+        match self {
+            LockProviderKind::MockStreamflow => MockStreamflowLockProvider.read_lock(lock_account, timestamp),
+            LockProviderKind::LinearLock => LinearLockProvider.read_lock(lock_account, timestamp),
+        }
+    }
+}
+
+/// Account layout of a simple linear-lock program: `total_amount` unlocks
+/// linearly from `start_ts` to `end_ts`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct LinearLockAccount {
+    pub beneficiary: Pubkey,
+    pub mint: Pubkey,
+    pub total_amount: u64,
+    pub start_ts: i64,
+    pub end_ts: i64,
+}
+
+impl LinearLockAccount {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8;
+
+    pub fn locked_amount(&self, timestamp: i64) -> u64 {
+        VestingSchedule {
+            total: self.total_amount,
+            start_ts: self.start_ts,
+            end_ts: self.end_ts,
+            ..Default::default()
+        }
+        .locked_amount(timestamp)
+    }
+}
+
+/// `LockProvider` for `LinearLockAccount`s
+pub struct LinearLockProvider;
+
+impl LockProvider for LinearLockProvider {
+    fn read_lock(&self, lock_account: &AccountInfo, timestamp: i64) -> Result<LockState> {
+        let lock = LinearLockAccount::deserialize(&mut &lock_account.data.borrow()[..])?;
+        Ok(LockState {
+            beneficiary: lock.beneficiary,
+            locked_amount: lock.locked_amount(timestamp),
+            status: LockStatus::Active,
+            allocation: lock.total_amount,
+            end_ts: lock.end_ts,
+        })
+    }
+}
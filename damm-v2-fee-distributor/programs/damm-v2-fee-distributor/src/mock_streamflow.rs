@@ -0,0 +1,29 @@
+use anchor_lang::prelude::*;
+use stream_vesting::{StreamData, StreamStatus};
+
+use crate::lock_provider::{LockProvider, LockState, LockStatus};
+
+/// `LockProvider` for the mock-streamflow program's stream accounts
+///
+/// Parses `stream_vesting::StreamData`, the mock's own layout, not the real
+/// Streamflow program's `Contract` accounts, so it is only meant for test and
+/// demo deployments where `lock_program` is mock-streamflow.
+pub struct MockStreamflowLockProvider;
+
+impl LockProvider for MockStreamflowLockProvider {
+    fn read_lock(&self, lock_account: &AccountInfo, timestamp: i64) -> Result<LockState> {
+        let stream = StreamData::deserialize(&mut &lock_account.data.borrow()[..])?;
+        let status = match stream.status(timestamp) {
+            StreamStatus::Active => LockStatus::Active,
+            StreamStatus::Cancelled => LockStatus::Cancelled,
+            StreamStatus::Closed => LockStatus::Closed,
+        };
+        Ok(LockState {
+            beneficiary: stream.recipient,
+            locked_amount: stream.locked_amount(timestamp),
+            status,
+            allocation: stream.initial_allocation,
+            end_ts: stream.end_ts,
+        })
+    }
+}
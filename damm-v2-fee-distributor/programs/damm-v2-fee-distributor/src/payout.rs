@@ -0,0 +1,200 @@
+use anchor_lang::prelude::*;
+
+use crate::payout_cap::{self, InvestorCapPolicy};
+use crate::{ErrorCode, ProgressAccount, BPS_DENOMINATOR};
+
+/// Rate a page pays investors at, per unit of weight
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PayoutRate {
+    /// `pool` shared pro-rata across `weight_total`, the page's or the day's tally
+    ProRata { pool: u64, weight_total: u128 },
+    /// The day's fixed `numerator / denominator`, never more than `cap` to one investor
+    Capped { numerator: u128, denominator: u128, cap: u64 },
+}
+
+impl PayoutRate {
+    /// Quote owed for `weight` at this rate
+    ///
+    /// Fails with `MathOverflow` rather than paying nothing when `weight` times the
+    /// pool does not fit in a u128.
+    pub fn owed(&self, weight: u128) -> Result<u64> {
+        match *self {
+            PayoutRate::ProRata { pool, weight_total } => {
+                let amount = weight.checked_mul(pool as u128).ok_or(ErrorCode::MathOverflow)?;
+                Ok(amount.checked_div(weight_total).unwrap_or(0) as u64)
+            }
+            PayoutRate::Capped { numerator, denominator, cap } => {
+                payout_cap::capped_payout(weight, numerator, denominator, cap)
+            }
+        }
+    }
+}
+
+/// What one page pays each of its investors
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PagePayouts {
+    /// Quote to transfer to each investor, in page order; 0 for skipped investors
+    pub payouts: Vec<u64>,
+    /// Sum of `payouts`
+    pub distributed: u64,
+    /// Payouts below the minimum, carried instead of transferred
+    pub dust: u64,
+    pub paid: u64,
+    /// Investors with no weight or a payout below the minimum
+    pub skipped: u64,
+}
+
+/// Split a page's payouts by `weights` at `rate`
+///
+/// Investors are paid in page order and the page's payouts and dust never exceed
+/// `pool_left`. Payouts below `min_payout` are carried as dust.
+pub fn page_payouts(weights: &[u128], rate: PayoutRate, pool_left: u64, min_payout: u64) -> Result<PagePayouts> {
+    let mut page = PagePayouts {
+        payouts: Vec::with_capacity(weights.len()),
+        ..Default::default()
+    };
+    for &weight in weights {
+        if weight == 0 {
+            page.payouts.push(0);
+            page.skipped += 1;
+            continue;
+        }
+
+        let payout = std::cmp::min(rate.owed(weight)?, pool_left - page.distributed - page.dust);
+        if payout >= min_payout {
+            page.payouts.push(payout);
+            page.distributed += payout;
+            page.paid += 1;
+        } else {
+            page.payouts.push(0);
+            page.dust += payout;
+            page.skipped += 1;
+        }
+    }
+    Ok(page)
+}
+
+/// Everything besides the day's progress that decides one page's payouts
+#[derive(Clone, Copy, Debug)]
+pub struct PageTerms<'a> {
+    /// Weight of each investor on the page, in page order
+    pub weights: &'a [u128],
+    /// Sum of the page's locked amounts
+    pub locked_total: u64,
+    /// Locked amount at which investors earn their full fee share
+    pub y0: u64,
+    pub investor_fee_share_bps: u16,
+    /// The tighter of the cranker's and the USD daily cap, if any
+    pub daily_cap: Option<u64>,
+    /// 0 when payouts are not capped per investor
+    pub max_investor_share_bps: u16,
+    pub investor_cap_policy: InvestorCapPolicy,
+    /// Overflow held back on earlier days, available to top up the pool
+    pub held_back: u64,
+    /// Most the rolling caps let the day distribute
+    pub window_room: u64,
+    pub min_payout: u64,
+}
+
+/// Work out one page's payouts and record them in `progress`
+///
+/// The first page fixes the day's held-back reserve, rolling room, cap overflow and its
+/// pool and rate, which a tallied day takes from the tally's day-wide totals and an
+/// untallied day from the first page. No page pays beyond what is left of the pool.
+pub fn pay_page(progress: &mut ProgressAccount, terms: &PageTerms) -> Result<PagePayouts> {
+    let capped = terms.max_investor_share_bps > 0;
+    let daily_cap_left = terms
+        .daily_cap
+        .map(|cap| cap.saturating_sub(progress.cumulative_distributed_today));
+    if progress.pagination_cursor == 0 {
+        fix_pool(progress, terms, daily_cap_left);
+    }
+
+    let rate = if capped {
+        PayoutRate::Capped {
+            numerator: progress.cap_rate_numerator,
+            denominator: progress.cap_rate_denominator,
+            cap: progress.investor_cap,
+        }
+    } else {
+        PayoutRate::ProRata {
+            pool: progress.investor_pool,
+            weight_total: progress.cap_rate_denominator,
+        }
+    };
+    // What the pool has left after earlier pages' payouts and dust, within today's caps
+    let window_room_left = progress
+        .window_room
+        .saturating_sub(progress.cumulative_distributed_today);
+    let pool_left = progress
+        .investor_pool
+        .saturating_sub(progress.cumulative_distributed_today)
+        .saturating_sub(progress.carry_over)
+        .min(daily_cap_left.unwrap_or(u64::MAX))
+        .min(window_room_left);
+    let page = page_payouts(terms.weights, rate, pool_left, terms.min_payout)?;
+
+    progress.cumulative_distributed_today = progress
+        .cumulative_distributed_today
+        .saturating_add(page.distributed);
+    progress.carry_over = progress.carry_over.saturating_add(page.dust);
+    progress.pagination_cursor = progress.pagination_cursor.saturating_add(1);
+    progress.locked_total = progress.locked_total.saturating_add(terms.locked_total);
+    progress.investors_paid = progress.investors_paid.saturating_add(page.paid);
+    progress.investors_skipped = progress.investors_skipped.saturating_add(page.skipped);
+    Ok(page)
+}
+
+/// Fix the day's investor pool, per-investor cap and rate on its first payout page
+fn fix_pool(progress: &mut ProgressAccount, terms: &PageTerms, daily_cap_left: Option<u64>) {
+    let (eligible_locked_total, weight_total) = if progress.investors_tallied > 0 {
+        (progress.tally_locked_total, progress.tally_weight_total)
+    } else {
+        let weight_total = terms
+            .weights
+            .iter()
+            .fold(0u128, |total, weight| total.saturating_add(*weight));
+        (terms.locked_total, weight_total)
+    };
+
+    // Calculate eligible investor share
+    let f_locked = eligible_locked_total.checked_div(terms.y0).unwrap_or(0);
+    let eligible_investor_share_bps = std::cmp::min(
+        terms.investor_fee_share_bps,
+        f_locked.saturating_mul(10000).checked_div(terms.y0).unwrap_or(0) as u16,
+    );
+
+    let investor_fee_quote = (progress.distributable_quote() as u128 * eligible_investor_share_bps as u128
+        / BPS_DENOMINATOR as u128) as u64;
+
+    // Overflow held back on earlier days tops up the pool, as far as today's daily cap
+    // allows; finalize_day returns whatever of it investors were not paid
+    let headroom = daily_cap_left.map_or(u64::MAX, |left| left.saturating_sub(investor_fee_quote));
+    progress.held_back_reserved = std::cmp::min(terms.held_back, headroom);
+    progress.window_room = terms.window_room;
+    let investor_fee_quote = investor_fee_quote.saturating_add(progress.held_back_reserved);
+
+    // The part of the pool above the daily and rolling caps is the day's overflow
+    let daily_cap_net = daily_cap_left.unwrap_or(investor_fee_quote);
+    let pool = investor_fee_quote
+        .min(daily_cap_net)
+        .min(progress.window_room);
+    progress.cap_overflow = investor_fee_quote - pool;
+
+    let (numerator, denominator, cap) = if terms.max_investor_share_bps > 0 {
+        let cap = (pool as u128 * terms.max_investor_share_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+        let (numerator, denominator) = match terms.investor_cap_policy {
+            InvestorCapPolicy::Redistribute => {
+                payout_cap::water_level(pool, cap, weight_total, &progress.tally_top_weights)
+            }
+            InvestorCapPolicy::Creator => (pool as u128, weight_total),
+        };
+        (numerator, denominator, cap)
+    } else {
+        (pool as u128, weight_total, pool)
+    };
+    progress.investor_pool = pool;
+    progress.investor_cap = cap;
+    progress.cap_rate_numerator = numerator;
+    progress.cap_rate_denominator = denominator;
+}
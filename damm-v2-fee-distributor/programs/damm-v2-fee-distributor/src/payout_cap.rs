@@ -0,0 +1,61 @@
+use anchor_lang::prelude::*;
+
+use crate::{ErrorCode, BPS_DENOMINATOR};
+
+/// Largest weights a day's tally keeps. A cap of `MIN_INVESTOR_CAP_BPS` can bind
+/// at most this many investors, so these are all the redistribution needs.
+pub const MAX_TRACKED_WEIGHTS: usize = 20;
+
+/// Smallest per-investor cap a config may set (5% of the day's investor pool)
+pub const MIN_INVESTOR_CAP_BPS: u16 = (BPS_DENOMINATOR / MAX_TRACKED_WEIGHTS as u64) as u16;
+
+/// Where the part of a payout above the per-investor cap goes
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvestorCapPolicy {
+    /// Shared pro-rata between the investors below the cap
+    Redistribute,
+    /// Left in the treasury for the creator remainder
+    Creator,
+}
+
+/// Insert `weight` into `top`, keeping only the `MAX_TRACKED_WEIGHTS` largest in descending order
+pub fn track_weight(top: &mut Vec<u128>, weight: u128) {
+    let index = top.partition_point(|tracked| *tracked >= weight);
+    if index < MAX_TRACKED_WEIGHTS {
+        top.insert(index, weight);
+        top.truncate(MAX_TRACKED_WEIGHTS);
+    }
+}
+
+/// Pro-rata rate `numerator / denominator` (quote per unit of weight) paid to
+/// investors below the cap, once every investor whose share exceeds `cap`
+/// has been pinned to it and the excess shared among the rest
+///
+/// `top_weights` holds the day's largest weights in descending order and
+/// `weight_total` the sum of all of them.
+pub fn water_level(pool: u64, cap: u64, weight_total: u128, top_weights: &[u128]) -> (u128, u128) {
+    let mut numerator = pool as u128;
+    let mut denominator = weight_total;
+    for weight in top_weights {
+        // Stop at the first investor the current rate leaves under the cap
+        if denominator == 0
+            || weight.saturating_mul(numerator) <= (cap as u128).saturating_mul(denominator)
+        {
+            break;
+        }
+        numerator = numerator.saturating_sub(cap as u128);
+        denominator -= weight;
+    }
+    (numerator, denominator)
+}
+
+/// Quote owed for `weight` at rate `numerator / denominator`, never above `cap`
+///
+/// A zero denominator means every investor was pinned to the cap, so each is owed `cap`.
+pub fn capped_payout(weight: u128, numerator: u128, denominator: u128, cap: u64) -> Result<u64> {
+    if denominator == 0 {
+        return Ok(cap);
+    }
+    let payout = weight.checked_mul(numerator).ok_or(ErrorCode::MathOverflow)? / denominator;
+    Ok(std::cmp::min(payout, cap as u128) as u64)
+}
@@ -0,0 +1,121 @@
+use anchor_lang::prelude::*;
+
+use crate::{ErrorCode, BPS_DENOMINATOR};
+
+/// USD amounts in config are fixed-point with this many decimals
+pub const USD_DECIMALS: u32 = 6;
+
+/// Source of quote-token USD prices, read directly from a price account
+pub trait PriceSource {
+    /// Read the price held in `price_feed`
+    fn read_price(&self, price_feed: &AccountInfo) -> Result<Price>;
+}
+
+/// A price of `price * 10^expo` USD per whole quote token, give or take `conf`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Price {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+impl Price {
+    /// Reject prices older than `max_age_secs` or less certain than `max_confidence_bps`
+    pub fn validate(&self, now: i64, max_age_secs: u32, max_confidence_bps: u16) -> Result<()> {
+        require!(self.price > 0, ErrorCode::InvalidPrice);
+        require!(
+            now.saturating_sub(self.publish_time) <= max_age_secs as i64,
+            ErrorCode::StalePrice
+        );
+        require!(
+            self.conf as u128 * BPS_DENOMINATOR as u128
+                <= self.price as u128 * max_confidence_bps as u128,
+            ErrorCode::PriceConfidenceTooWide
+        );
+        Ok(())
+    }
+
+    /// Quote base units worth `usd_amount` (with `USD_DECIMALS` decimals) for a mint with `decimals`
+    pub fn usd_to_quote(&self, usd_amount: u64, decimals: u8) -> Result<u64> {
+        // quote = usd / 10^USD_DECIMALS / (price * 10^expo) * 10^decimals
+        let mut numerator = (usd_amount as u128)
+            .checked_mul(pow10(decimals as u32)?)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let mut denominator = (self.price as u128)
+            .checked_mul(pow10(USD_DECIMALS)?)
+            .ok_or(ErrorCode::MathOverflow)?;
+        if self.expo < 0 {
+            numerator = numerator
+                .checked_mul(pow10(self.expo.unsigned_abs())?)
+                .ok_or(ErrorCode::MathOverflow)?;
+        } else {
+            denominator = denominator
+                .checked_mul(pow10(self.expo as u32)?)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+        let quote = numerator / denominator;
+        Ok(std::cmp::min(quote, u64::MAX as u128) as u64)
+    }
+
+    /// Base units of a mint priced at `quote` worth `amount` base units of a mint priced at `self`
+    pub fn convert(&self, amount: u64, decimals: u8, quote: &Price, quote_decimals: u8) -> Result<u64> {
+        // out = amount / 10^decimals * (price * 10^expo) / (quote.price * 10^quote.expo) * 10^quote_decimals
+        let mut numerator = (amount as u128)
+            .checked_mul(self.price as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_mul(pow10(quote_decimals as u32)?)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let mut denominator = (quote.price as u128)
+            .checked_mul(pow10(decimals as u32)?)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let expo = self.expo.checked_sub(quote.expo).ok_or(ErrorCode::MathOverflow)?;
+        if expo < 0 {
+            denominator = denominator
+                .checked_mul(pow10(expo.unsigned_abs())?)
+                .ok_or(ErrorCode::MathOverflow)?;
+        } else {
+            numerator = numerator
+                .checked_mul(pow10(expo as u32)?)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+        let out = numerator / denominator;
+        Ok(std::cmp::min(out, u64::MAX as u128) as u64)
+    }
+}
+
+fn pow10(exponent: u32) -> Result<u128> {
+    10u128
+        .checked_pow(exponent)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))
+}
+
+/// Magic number at the start of every Pyth-style price account
+pub const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+
+/// The leading fields of a Pyth-style price account
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PythPriceAccount {
+    pub magic: u32,
+    pub version: u32,
+    pub expo: i32,
+    pub price: i64,
+    pub conf: u64,
+    pub publish_time: i64,
+}
+
+/// `PriceSource` for Pyth-style price accounts
+pub struct PythPriceSource;
+
+impl PriceSource for PythPriceSource {
+    fn read_price(&self, price_feed: &AccountInfo) -> Result<Price> {
+        let account = PythPriceAccount::deserialize(&mut &price_feed.data.borrow()[..])?;
+        require!(account.magic == PYTH_MAGIC, ErrorCode::InvalidPriceFeed);
+        Ok(Price {
+            price: account.price,
+            conf: account.conf,
+            expo: account.expo,
+            publish_time: account.publish_time,
+        })
+    }
+}
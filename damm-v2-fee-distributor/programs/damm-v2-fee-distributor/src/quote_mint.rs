@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    confidential_transfer::ConfidentialTransferMint, non_transferable::NonTransferable,
+    transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions,
+};
+use anchor_spl::token_2022::spl_token_2022::state::Mint;
+
+use crate::ErrorCode;
+
+/// Reject quote mints whose extensions the distributor cannot pay out through
+///
+/// Legacy SPL Token mints carry no extensions and always pass. Transfer hooks
+/// are allowed: payouts forward the hook's extra accounts.
+pub fn validate_quote_mint(mint: &AccountInfo) -> Result<()> {
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<Mint>::unpack(&data)?;
+
+    require!(
+        mint.get_extension::<NonTransferable>().is_err(),
+        ErrorCode::UnsupportedMintExtension
+    );
+    require!(
+        mint.get_extension::<ConfidentialTransferMint>().is_err(),
+        ErrorCode::UnsupportedMintExtension
+    );
+
+    Ok(())
+}
+
+/// Fee the mint withholds from a transfer of `amount` in the current epoch
+///
+/// Zero for mints without the transfer-fee extension.
+pub fn transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<Mint>::unpack(&data)?;
+
+    let Ok(fee_config) = mint.get_extension::<TransferFeeConfig>() else {
+        return Ok(0);
+    };
+    let epoch = Clock::get()?.epoch;
+    fee_config
+        .calculate_epoch_fee(epoch, amount)
+        .ok_or_else(|| error!(ErrorCode::MathOverflow))
+}
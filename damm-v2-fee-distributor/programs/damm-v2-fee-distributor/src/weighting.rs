@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+
+use crate::lock_provider::LockState;
+
+/// How an investor's lock translates into their share of a page's payout
+pub trait Weighting {
+    /// Weight of `lock` as of `snapshot_ts`; locks with nothing locked weigh 0
+    fn weight(&self, lock: &LockState, snapshot_ts: i64) -> u128;
+}
+
+/// Weighting a config pays investors by
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeightingMode {
+    /// Pro-rata to the amount still locked
+    LockedAmount,
+    /// Pro-rata to the lock's original allocation, while anything is still locked
+    OriginalAllocation,
+    /// Locked amount times the seconds left until the lock ends (ve-style)
+    VoteEscrowed,
+    /// Square root of the locked amount, dampening large holders
+    SqrtLocked,
+}
+
+impl Weighting for WeightingMode {
+    fn weight(&self, lock: &LockState, snapshot_ts: i64) -> u128 {
+        if lock.locked_amount == 0 {
+            return 0;
+        }
+        match self {
+            WeightingMode::LockedAmount => lock.locked_amount as u128,
+            WeightingMode::OriginalAllocation => lock.allocation as u128,
+            WeightingMode::VoteEscrowed => {
+                let remaining = lock.end_ts.saturating_sub(snapshot_ts).max(0) as u128;
+                lock.locked_amount as u128 * remaining
+            }
+            WeightingMode::SqrtLocked => isqrt(lock.locked_amount) as u128,
+        }
+    }
+}
+
+/// Integer square root, rounded down
+fn isqrt(value: u64) -> u64 {
+    if value < 2 {
+        return value;
+    }
+    // Newton's method from an initial guess at or above the root
+    let mut x = value;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
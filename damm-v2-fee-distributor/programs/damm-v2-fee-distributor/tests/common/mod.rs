@@ -0,0 +1,1015 @@
+//! Shared setup for the distributor's integration tests
+//!
+//! `Fixture::new` loads the distributor and the mock programs into an `Svm`,
+//! initializes the protocol config and a config (with its progress history) on
+//! a mock cp-amm pool, and points the config at mock cp-amm and its lock program
+//! at mock-streamflow. Cranks claim whatever fees the honorary position accrued.
+
+#![allow(dead_code)]
+
+pub mod svm;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::{system_program, sysvar};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token::spl_token;
+use damm_v2_fee_distributor::cp_amm::BaseFeePolicy;
+use damm_v2_fee_distributor::{
+    accounts, instruction, ConfigUpdateParams, InvestorRecord, ProgressAccount, ProtocolConfigUpdateParams,
+    CAP_WINDOW_SEED, CONFIG_SEED, DAY_SUMMARY_SEED, INVESTOR_FEE_POS_OWNER_SEED, PROGRAM_AUTHORITY_SEED,
+    PROGRESS_HISTORY_SEED, PROGRESS_SEED, PROTOCOL_CONFIG_SEED, PROTOCOL_FEE_VAULT_SEED, VAULT_SEED,
+};
+
+pub use svm::{Svm, DAY};
+
+pub const DISTRIBUTOR: Pubkey = damm_v2_fee_distributor::ID;
+
+/// Quote fees the tests accrue to the honorary position for a day
+pub const DAILY_FEES: u64 = 1_000_000;
+
+/// Day the fixture starts on, an hour in
+pub const START_DAY: i64 = 20_000;
+
+/// Lets the locked fraction saturate, so investors get the crank's full `investor_fee_share_bps`
+pub const Y0: u64 = 1;
+
+/// Q64.64 square root of a 1:1 price
+pub const SQRT_PRICE_ONE: u128 = 1 << 64;
+
+/// The anchor error `code` as the program error a failed instruction returns
+pub fn anchor_error(code: impl Into<u32>) -> ProgramError {
+    ProgramError::Custom(code.into())
+}
+
+/// Assert `result` failed with anchor error `code`
+#[track_caller]
+pub fn assert_error(result: std::result::Result<(), ProgramError>, code: impl Into<u32>) {
+    assert_eq!(result, Err(anchor_error(code)));
+}
+
+pub fn pda(seeds: &[&[u8]], program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(seeds, program_id).0
+}
+
+/// Create the protocol config, signed by `payer` against the distributor's `program_data`
+pub fn initialize_protocol_config_ix(payer: Pubkey, program_data: Pubkey, protocol_fee_bps: u16) -> Instruction {
+    Instruction {
+        program_id: DISTRIBUTOR,
+        accounts: accounts::InitializeProtocolConfig {
+            payer,
+            program: DISTRIBUTOR,
+            program_data,
+            protocol_config: pda(&[PROTOCOL_CONFIG_SEED], &DISTRIBUTOR),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::InitializeProtocolConfig { protocol_fee_bps }.data(),
+    }
+}
+
+pub fn progress_pda(config: &Pubkey, day_id: i64) -> Pubkey {
+    pda(&[PROGRESS_SEED, config.as_ref(), &day_id.to_le_bytes()], &DISTRIBUTOR)
+}
+
+/// The config's position owner PDA, owner of its honorary positions
+pub fn position_owner_pda(config: &Pubkey) -> Pubkey {
+    pda(&[VAULT_SEED, config.as_ref(), INVESTOR_FEE_POS_OWNER_SEED], &DISTRIBUTOR)
+}
+
+/// The config's program authority, owner of its quote and base treasuries
+pub fn program_authority_pda(config: &Pubkey) -> Pubkey {
+    pda(&[PROGRAM_AUTHORITY_SEED, config.as_ref()], &DISTRIBUTOR)
+}
+
+pub fn day_summary_pda(config: &Pubkey, day_id: i64) -> Pubkey {
+    pda(&[DAY_SUMMARY_SEED, config.as_ref(), &day_id.to_le_bytes()], &DISTRIBUTOR)
+}
+
+/// An investor with a mock-streamflow stream and a quote ATA
+#[derive(Clone, Copy, Debug)]
+pub struct Investor {
+    pub owner: Pubkey,
+    pub stream: Pubkey,
+    pub quote_ata: Pubkey,
+}
+
+impl Investor {
+    pub fn record(&self) -> InvestorRecord {
+        InvestorRecord {
+            stream_pubkey: self.stream,
+            investor_quote_ata: self.quote_ata,
+        }
+    }
+}
+
+/// Arguments to `crank_distribute_page` other than the page itself
+#[derive(Clone, Copy, Debug)]
+pub struct CrankArgs {
+    pub y0: u64,
+    pub investor_fee_share_bps: u16,
+    pub daily_cap: Option<u64>,
+    pub min_payout: u64,
+}
+
+impl Default for CrankArgs {
+    fn default() -> Self {
+        Self {
+            y0: Y0,
+            investor_fee_share_bps: 5_000,
+            daily_cap: None,
+            min_payout: 0,
+        }
+    }
+}
+
+/// A mock cp-amm pool with an honorary position in it
+#[derive(Clone, Copy, Debug)]
+pub struct MockPool {
+    pub pool: Pubkey,
+    pub pool_authority: Pubkey,
+    pub quote_vault: Pubkey,
+    pub base_vault: Pubkey,
+    pub position: Pubkey,
+}
+
+impl MockPool {
+    /// The pool accounts cp-amm CPIs look up in remaining accounts
+    pub fn remaining_accounts(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.pool, false),
+            AccountMeta::new_readonly(self.pool_authority, false),
+            AccountMeta::new(self.quote_vault, false),
+            AccountMeta::new(self.base_vault, false),
+        ]
+    }
+}
+
+pub struct Fixture {
+    pub svm: Svm,
+    /// Initializer of the config and protocol config: config authority, guardian and protocol admin
+    pub authority: Pubkey,
+    pub cranker: Pubkey,
+    pub creator: Pubkey,
+    pub quote_mint: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_token_program: Pubkey,
+    pub base_token_program: Pubkey,
+    pub mock_pool: MockPool,
+    pub config: Pubkey,
+    pub position_owner: Pubkey,
+    pub program_authority: Pubkey,
+    pub protocol_config: Pubkey,
+    pub protocol_fee_authority: Pubkey,
+    pub program_quote_treasury: Pubkey,
+    pub program_base_treasury: Pubkey,
+    pub protocol_fee_vault: Pubkey,
+    pub cranker_quote_ata: Pubkey,
+    pub creator_quote_ata: Pubkey,
+    /// Funds the mock pool's fees
+    pub funder_quote: Pubkey,
+    pub funder_base: Pubkey,
+}
+
+impl Fixture {
+    /// A config on a fresh mock pool whose quote and base mints are plain SPL Token mints
+    pub fn new() -> Self {
+        Self::with_quote_mint(|svm, mint, authority| svm.create_mint(mint, authority, 6, &spl_token::ID))
+    }
+
+    /// A fixture whose quote mint is created by `create_quote_mint(svm, mint, mint_authority)`
+    pub fn with_quote_mint(create_quote_mint: impl FnOnce(&mut Svm, Pubkey, &Pubkey)) -> Self {
+        let mut svm = Svm::new();
+        svm.add_program(DISTRIBUTOR, damm_v2_fee_distributor::entry);
+        svm.add_program(mock_streamflow::ID, mock_streamflow::entry);
+        svm.add_program(mock_cp_amm::ID, mock_cp_amm::entry);
+        svm.add_program(mock_oracle::ID, mock_oracle::entry);
+        svm.add_program(mock_transfer_hook::ID, mock_transfer_hook::entry);
+        svm.warp_to_day(START_DAY, 3_600);
+
+        let authority = Pubkey::new_unique();
+        let program_data = svm.set_upgrade_authority(DISTRIBUTOR, Some(authority));
+        let cranker = Pubkey::new_unique();
+        let creator = Pubkey::new_unique();
+        for wallet in [&authority, &cranker] {
+            svm.airdrop(wallet, 100_000_000_000);
+        }
+
+        let quote_mint = Pubkey::new_unique();
+        let base_mint = Pubkey::new_unique();
+        create_quote_mint(&mut svm, quote_mint, &authority);
+        svm.create_mint(base_mint, &authority, 6, &spl_token::ID);
+        let quote_token_program = svm.account(&quote_mint).unwrap().owner;
+        let base_token_program = spl_token::ID;
+
+        let protocol_fee_authority = pda(&[PROTOCOL_FEE_VAULT_SEED], &DISTRIBUTOR);
+        let protocol_config = pda(&[PROTOCOL_CONFIG_SEED], &DISTRIBUTOR);
+
+        let protocol_fee_vault = svm.create_ata(&quote_mint, &protocol_fee_authority);
+        let cranker_quote_ata = svm.create_ata(&quote_mint, &cranker);
+        let creator_quote_ata = svm.create_ata(&quote_mint, &creator);
+        let funder_quote = svm.create_ata(&quote_mint, &authority);
+        let funder_base = svm.create_ata(&base_mint, &authority);
+
+        let mock_pool = create_mock_pool(
+            &mut svm,
+            &authority,
+            &quote_mint,
+            &base_mint,
+            &quote_token_program,
+            &base_token_program,
+        );
+        let config = pda(&[CONFIG_SEED, mock_pool.pool.as_ref()], &DISTRIBUTOR);
+        let position_owner = position_owner_pda(&config);
+        let program_authority = program_authority_pda(&config);
+        let program_quote_treasury = svm.create_ata(&quote_mint, &program_authority);
+        let program_base_treasury = svm.create_ata(&base_mint, &program_authority);
+
+        let mut fixture = Self {
+            svm,
+            authority,
+            cranker,
+            creator,
+            quote_mint,
+            base_mint,
+            quote_token_program,
+            base_token_program,
+            mock_pool,
+            config,
+            position_owner,
+            program_authority,
+            protocol_config,
+            protocol_fee_authority,
+            program_quote_treasury,
+            program_base_treasury,
+            protocol_fee_vault,
+            cranker_quote_ata,
+            creator_quote_ata,
+            funder_quote,
+            funder_base,
+        };
+
+        let initialize_protocol_config = initialize_protocol_config_ix(authority, program_data, 0);
+        let initialize_config = fixture.initialize_config_ix(mock_pool.pool, mock_pool.position, quote_mint, -100, 100);
+        let initialize_progress_history = fixture.initialize_progress_history_ix();
+        fixture
+            .svm
+            .process_transaction(
+                &[initialize_protocol_config, initialize_config, initialize_progress_history],
+                &[authority],
+            )
+            .expect("initialize");
+        fixture
+            .update_config(ConfigUpdateParams {
+                lock_program: Some(mock_streamflow::ID),
+                cp_amm_program: Some(mock_cp_amm::ID),
+                creator: Some(creator),
+                ..Default::default()
+            })
+            .expect("set lock program, cp-amm program and creator");
+        fixture
+    }
+
+    /// Initialize another config, with the same mints, lock program and creator and a progress history,
+    /// on a fresh mock pool
+    ///
+    /// The fixture's helpers keep using the current config until `use_config` switches them.
+    pub fn add_config(&mut self) -> MockPool {
+        let mock_pool = self.add_config_without_history();
+        let previous = self.use_config(mock_pool);
+        let initialize_progress_history = self.initialize_progress_history_ix();
+        self.svm
+            .process(initialize_progress_history, &[self.authority])
+            .expect("initialize progress history");
+        self.use_config(previous);
+        mock_pool
+    }
+
+    /// `add_config`, leaving out the progress history
+    pub fn add_config_without_history(&mut self) -> MockPool {
+        let mock_pool = create_mock_pool(
+            &mut self.svm,
+            &self.authority,
+            &self.quote_mint,
+            &self.base_mint,
+            &self.quote_token_program,
+            &self.base_token_program,
+        );
+        let initialize_config =
+            self.initialize_config_ix(mock_pool.pool, mock_pool.position, self.quote_mint, -100, 100);
+        self.svm.process(initialize_config, &[self.authority]).expect("initialize config");
+
+        let previous = self.use_config(mock_pool);
+        self.svm.create_ata(&self.quote_mint, &self.program_authority);
+        self.svm.create_ata(&self.base_mint, &self.program_authority);
+        self.update_config(ConfigUpdateParams {
+            lock_program: Some(mock_streamflow::ID),
+            cp_amm_program: Some(mock_cp_amm::ID),
+            creator: Some(self.creator),
+            ..Default::default()
+        })
+        .expect("set lock program, cp-amm program and creator");
+        self.use_config(previous);
+        mock_pool
+    }
+
+    /// Point the fixture's helpers at the config on `mock_pool` and its treasuries, returning
+    /// the pool they used before
+    pub fn use_config(&mut self, mock_pool: MockPool) -> MockPool {
+        self.config = pda(&[CONFIG_SEED, mock_pool.pool.as_ref()], &DISTRIBUTOR);
+        self.position_owner = position_owner_pda(&self.config);
+        self.program_authority = program_authority_pda(&self.config);
+        self.program_quote_treasury = get_associated_token_address_with_program_id(
+            &self.program_authority,
+            &self.quote_mint,
+            &self.quote_token_program,
+        );
+        self.program_base_treasury = get_associated_token_address_with_program_id(
+            &self.program_authority,
+            &self.base_mint,
+            &self.base_token_program,
+        );
+        std::mem::replace(&mut self.mock_pool, mock_pool)
+    }
+
+    // Instructions
+
+    pub fn initialize_config_ix(
+        &self,
+        pool: Pubkey,
+        position: Pubkey,
+        quote_mint: Pubkey,
+        tick_lower: i32,
+        tick_upper: i32,
+    ) -> Instruction {
+        let token_program = self.svm.account(&quote_mint).map_or(spl_token::ID, |mint| mint.owner);
+        let config = pda(&[CONFIG_SEED, pool.as_ref()], &DISTRIBUTOR);
+        Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: accounts::InitializeHonoraryPosition {
+                payer: self.authority,
+                config,
+                pool,
+                quote_mint,
+                base_mint: self.base_mint,
+                position,
+                investor_fee_position_owner_pda: position_owner_pda(&config),
+                system_program: system_program::ID,
+                token_program,
+                rent: sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+            data: instruction::InitializeHonoraryPosition {
+                pool_id: pool,
+                tick_lower,
+                tick_upper,
+            }
+            .data(),
+        }
+    }
+
+    /// Create the config's progress history, which `close_progress` folds closed days into
+    pub fn initialize_progress_history_ix(&self) -> Instruction {
+        Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: accounts::InitializeProgressHistory {
+                payer: self.authority,
+                config: self.config,
+                progress_history: self.progress_history(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: instruction::InitializeProgressHistory {}.data(),
+        }
+    }
+
+    pub fn update_config_ix(&self, authority: Pubkey, params: ConfigUpdateParams) -> Instruction {
+        Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: accounts::UpdateConfig { authority, config: self.config }.to_account_metas(None),
+            data: instruction::UpdateConfig { params }.data(),
+        }
+    }
+
+    pub fn update_config(&mut self, params: ConfigUpdateParams) -> ProgramResult {
+        let instruction = self.update_config_ix(self.authority, params);
+        self.svm.process(instruction, &[self.authority])
+    }
+
+    pub fn update_protocol_config(&mut self, admin: Pubkey, params: ProtocolConfigUpdateParams) -> ProgramResult {
+        let instruction = Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: accounts::UpdateProtocolConfig {
+                admin,
+                protocol_config: self.protocol_config,
+            }
+            .to_account_metas(None),
+            data: instruction::UpdateProtocolConfig { params }.data(),
+        };
+        self.svm.process(instruction, &[admin])
+    }
+
+    pub fn add_honorary_position(&mut self, authority: Pubkey, position: Pubkey) -> ProgramResult {
+        let instruction = Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: accounts::AddHonoraryPosition {
+                authority,
+                config: self.config,
+                position,
+                investor_fee_position_owner_pda: self.position_owner,
+                cp_amm_program: mock_cp_amm::ID,
+            }
+            .to_account_metas(None),
+            data: instruction::AddHonoraryPosition {
+                pool_id: self.mock_pool.pool,
+                tick_lower: -100,
+                tick_upper: 100,
+            }
+            .data(),
+        };
+        self.svm.process(instruction, &[authority])
+    }
+
+    /// Crank accounts for today with the cp-amm program, pool and base accounts filled in
+    /// and every other optional account left out
+    pub fn crank_accounts(&self) -> accounts::CrankDistributePage {
+        accounts::CrankDistributePage {
+            cranker: self.cranker,
+            config: self.config,
+            progress: progress_pda(&self.config, self.svm.day_id()),
+            position: self.mock_pool.position,
+            investor_fee_position_owner_pda: self.position_owner,
+            quote_mint: self.quote_mint,
+            program_quote_treasury: self.program_quote_treasury,
+            program_authority: self.program_authority,
+            cranker_quote_ata: None,
+            protocol_config: self.protocol_config,
+            protocol_fee_authority: self.protocol_fee_authority,
+            protocol_fee_vault: self.protocol_fee_vault,
+            price_feed: None,
+            base_price_feed: None,
+            pool: Some(self.mock_pool.pool),
+            base_mint: Some(self.base_mint),
+            program_base_treasury: Some(self.program_base_treasury),
+            creator_base_ata: None,
+            base_token_program: Some(self.base_token_program),
+            cap_window: None,
+            cp_amm_program: Some(mock_cp_amm::ID),
+            lock_program: mock_streamflow::ID,
+            token_program: self.quote_token_program,
+            associated_token_program: anchor_spl::associated_token::ID,
+            system_program: system_program::ID,
+        }
+    }
+
+    /// Crank `page`, passing each investor's stream and ATA, the mock pool's accounts and then
+    /// `extra_accounts` as remaining accounts
+    pub fn crank_ix(
+        &self,
+        accounts: accounts::CrankDistributePage,
+        page: &[Investor],
+        args: CrankArgs,
+        extra_accounts: Vec<AccountMeta>,
+    ) -> Instruction {
+        let mut metas = accounts.to_account_metas(None);
+        metas.extend(investor_metas(page));
+        metas.extend(self.mock_pool.remaining_accounts());
+        metas.extend(extra_accounts);
+        Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: metas,
+            data: instruction::CrankDistributePage {
+                page_investors: page.iter().map(Investor::record).collect(),
+                y0: args.y0,
+                investor_fee_share_bps: args.investor_fee_share_bps,
+                optional_daily_cap_lamports: args.daily_cap,
+                min_payout_lamports: args.min_payout,
+            }
+            .data(),
+        }
+    }
+
+    /// Crank `page` with the default accounts and arguments
+    pub fn crank(&mut self, page: &[Investor]) -> ProgramResult {
+        self.crank_with(page, CrankArgs::default())
+    }
+
+    pub fn crank_with(&mut self, page: &[Investor], args: CrankArgs) -> ProgramResult {
+        let instruction = self.crank_ix(self.crank_accounts(), page, args, Vec::new());
+        self.svm.process(instruction, &[self.cranker])
+    }
+
+    pub fn tally(&mut self, page: &[Investor]) -> ProgramResult {
+        let mut metas = accounts::TallyInvestorPage {
+            cranker: self.cranker,
+            config: self.config,
+            progress: progress_pda(&self.config, self.svm.day_id()),
+            protocol_config: self.protocol_config,
+            lock_program: mock_streamflow::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None);
+        metas.extend(investor_metas(page));
+        let instruction = Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: metas,
+            data: instruction::TallyInvestorPage {
+                page_investors: page.iter().map(Investor::record).collect(),
+            }
+            .data(),
+        };
+        self.svm.process(instruction, &[self.cranker])
+    }
+
+    pub fn finalize_accounts(&self, day_id: i64) -> accounts::FinalizeDay {
+        accounts::FinalizeDay {
+            payer: self.cranker,
+            config: self.config,
+            progress: progress_pda(&self.config, day_id),
+            day_summary: day_summary_pda(&self.config, day_id),
+            quote_mint: self.quote_mint,
+            program_quote_treasury: self.program_quote_treasury,
+            program_authority: self.program_authority,
+            creator_quote_ata: self.creator_quote_ata,
+            creator: self.creator,
+            protocol_config: self.protocol_config,
+            cap_window: None,
+            quote_sweep_recipient: None,
+            token_program: self.quote_token_program,
+            system_program: system_program::ID,
+        }
+    }
+
+    pub fn finalize_ix(
+        &self,
+        accounts: accounts::FinalizeDay,
+        day_id: i64,
+        extra_accounts: Vec<AccountMeta>,
+    ) -> Instruction {
+        let mut metas = accounts.to_account_metas(None);
+        metas.extend(extra_accounts);
+        Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: metas,
+            data: instruction::FinalizeDay { day_id }.data(),
+        }
+    }
+
+    pub fn finalize(&mut self, day_id: i64) -> ProgramResult {
+        let instruction = self.finalize_ix(self.finalize_accounts(day_id), day_id, Vec::new());
+        self.svm.process(instruction, &[self.cranker])
+    }
+
+    pub fn close_progress_ix(&self, day_id: i64, payer: Pubkey, progress_history: Option<Pubkey>) -> Instruction {
+        Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: accounts::CloseProgress {
+                config: self.config,
+                protocol_config: self.protocol_config,
+                progress: progress_pda(&self.config, day_id),
+                payer,
+                progress_history,
+            }
+            .to_account_metas(None),
+            data: instruction::CloseProgress { day_id }.data(),
+        }
+    }
+
+    pub fn close_progress(&mut self, day_id: i64, payer: Pubkey) -> ProgramResult {
+        let instruction = self.close_progress_ix(day_id, payer, Some(self.progress_history()));
+        self.svm.process(instruction, &[])
+    }
+
+    /// Create the config's cap window, needed for rolling caps and withheld overflow
+    pub fn initialize_cap_window(&mut self) -> Pubkey {
+        let cap_window = self.cap_window();
+        let instruction = Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: accounts::InitializeCapWindow {
+                payer: self.authority,
+                config: self.config,
+                cap_window,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: instruction::InitializeCapWindow {}.data(),
+        };
+        self.svm.process(instruction, &[self.authority]).expect("initialize cap window");
+        cap_window
+    }
+
+    pub fn cap_window(&self) -> Pubkey {
+        pda(&[CAP_WINDOW_SEED, self.config.as_ref()], &DISTRIBUTOR)
+    }
+
+    pub fn progress_history(&self) -> Pubkey {
+        pda(&[PROGRESS_HISTORY_SEED, self.config.as_ref()], &DISTRIBUTOR)
+    }
+
+    pub fn set_config_paused(&mut self, guardian: Pubkey, paused: u8) -> ProgramResult {
+        let instruction = Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: accounts::SetConfigPaused { guardian, config: self.config }.to_account_metas(None),
+            data: instruction::SetConfigPaused { paused }.data(),
+        };
+        self.svm.process(instruction, &[guardian])
+    }
+
+    pub fn set_protocol_paused(&mut self, guardian: Pubkey, paused: u8) -> ProgramResult {
+        let instruction = Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: accounts::SetProtocolPaused {
+                guardian,
+                protocol_config: self.protocol_config,
+            }
+            .to_account_metas(None),
+            data: instruction::SetProtocolPaused { paused }.data(),
+        };
+        self.svm.process(instruction, &[guardian])
+    }
+
+    pub fn emergency_withdraw(&mut self, recovery_ata: Pubkey) -> ProgramResult {
+        let instruction = Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: accounts::EmergencyWithdraw {
+                authority: self.authority,
+                config: self.config,
+                quote_mint: self.quote_mint,
+                program_quote_treasury: self.program_quote_treasury,
+                program_authority: self.program_authority,
+                recovery_ata,
+                token_program: self.quote_token_program,
+            }
+            .to_account_metas(None),
+            data: instruction::EmergencyWithdraw { reason: 1 }.data(),
+        };
+        self.svm.process(instruction, &[self.authority])
+    }
+
+    /// Withdraw `amount` from the protocol fee vault to `destination`, signed by the protocol admin
+    pub fn withdraw_protocol_fees(&mut self, destination: Pubkey, amount: u64) -> ProgramResult {
+        let instruction = Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: accounts::WithdrawProtocolFees {
+                admin: self.authority,
+                protocol_config: self.protocol_config,
+                protocol_fee_authority: self.protocol_fee_authority,
+                quote_mint: self.quote_mint,
+                protocol_fee_vault: self.protocol_fee_vault,
+                destination,
+                token_program: self.quote_token_program,
+            }
+            .to_account_metas(None),
+            data: instruction::WithdrawProtocolFees { amount }.data(),
+        };
+        self.svm.process(instruction, &[self.authority])
+    }
+
+    /// Start the config's decommission notice period, signed by the config authority
+    pub fn request_decommission(&mut self) -> ProgramResult {
+        let instruction = Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: accounts::RequestDecommission {
+                authority: self.authority,
+                config: self.config,
+            }
+            .to_account_metas(None),
+            data: instruction::RequestDecommission {}.data(),
+        };
+        self.svm.process(instruction, &[self.authority])
+    }
+
+    pub fn decommission_accounts(&self, base_sweep_recipient: Pubkey) -> accounts::Decommission {
+        accounts::Decommission {
+            authority: self.authority,
+            config: self.config,
+            protocol_config: self.protocol_config,
+            protocol_fee_authority: self.protocol_fee_authority,
+            protocol_fee_vault: self.protocol_fee_vault,
+            progress: progress_pda(&self.config, self.svm.day_id()),
+            position: self.mock_pool.position,
+            investor_fee_position_owner_pda: self.position_owner,
+            quote_mint: self.quote_mint,
+            base_mint: self.base_mint,
+            program_quote_treasury: self.program_quote_treasury,
+            program_base_treasury: self.program_base_treasury,
+            program_authority: self.program_authority,
+            base_sweep_recipient,
+            cp_amm_program: Some(mock_cp_amm::ID),
+            token_program: self.quote_token_program,
+            base_token_program: self.base_token_program,
+            system_program: system_program::ID,
+        }
+    }
+
+    /// Decommission today, passing the mock pool's accounts and then `extra_accounts`
+    pub fn decommission_ix(&self, accounts: accounts::Decommission, extra_accounts: Vec<AccountMeta>) -> Instruction {
+        let mut metas = accounts.to_account_metas(None);
+        metas.extend(self.mock_pool.remaining_accounts());
+        metas.extend(extra_accounts);
+        Instruction {
+            program_id: DISTRIBUTOR,
+            accounts: metas,
+            data: instruction::Decommission { day_id: self.svm.day_id() }.data(),
+        }
+    }
+
+    // State
+
+    /// Give an investor a linear mock-streamflow stream of `allocation` from `start_ts` to `end_ts`
+    pub fn add_investor(&mut self, allocation: u64, start_ts: i64, end_ts: i64) -> Investor {
+        let owner = Pubkey::new_unique();
+        let stream = Pubkey::new_unique();
+        self.create_stream(stream, allocation, start_ts, end_ts, owner);
+        let quote_ata = self.svm.create_ata(&self.quote_mint, &owner);
+        Investor { owner, stream, quote_ata }
+    }
+
+    /// An investor whose whole `allocation` stays locked for the next year
+    pub fn add_locked_investor(&mut self, allocation: u64) -> Investor {
+        let unlock_start = self.svm.now() + 365 * DAY;
+        self.add_investor(allocation, unlock_start, unlock_start + 365 * DAY)
+    }
+
+    /// Create a linear stream through mock-streamflow's `create_stream`
+    pub fn create_stream(&mut self, stream: Pubkey, allocation: u64, start_ts: i64, end_ts: i64, recipient: Pubkey) {
+        self.svm.create_account(stream, mock_streamflow::StreamData::LEN, &mock_streamflow::ID);
+        let instruction = Instruction {
+            program_id: mock_streamflow::ID,
+            accounts: mock_streamflow::accounts::CreateStream {
+                payer: self.authority,
+                stream,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: mock_streamflow::instruction::CreateStream {
+                initial_allocation: allocation,
+                start_ts,
+                end_ts,
+                mint: self.quote_mint,
+                recipient,
+                cliff: 0,
+                cliff_amount: 0,
+                period: 0,
+                amount_per_period: 0,
+            }
+            .data(),
+        };
+        self.svm.process(instruction, &[self.authority]).expect("create stream");
+    }
+
+    /// Cancel a stream through mock-streamflow, effective from `canceled_at`
+    pub fn cancel_stream(&mut self, stream: Pubkey, canceled_at: i64) {
+        let instruction = Instruction {
+            program_id: mock_streamflow::ID,
+            accounts: mock_streamflow::accounts::UpdateStream { stream }.to_account_metas(None),
+            data: mock_streamflow::instruction::CancelStream { canceled_at }.data(),
+        };
+        self.svm.process(instruction, &[]).expect("cancel stream");
+    }
+
+    /// Mint `amount` straight into the quote treasury, outside of any claim
+    pub fn fund_treasury(&mut self, amount: u64) {
+        let treasury = self.program_quote_treasury;
+        self.svm.mint_to(&treasury, amount);
+    }
+
+    /// Write the quote mint's ExtraAccountMetaList for mock-transfer-hook, returning the
+    /// accounts its transfers need: the hook program, the list and the counter it bumps
+    pub fn initialize_transfer_hook(&mut self) -> Vec<AccountMeta> {
+        let extra_account_meta_list = pda(
+            &[mock_transfer_hook::EXTRA_ACCOUNT_METAS_SEED, self.quote_mint.as_ref()],
+            &mock_transfer_hook::ID,
+        );
+        let counter = pda(&[mock_transfer_hook::COUNTER_SEED], &mock_transfer_hook::ID);
+        let instruction = Instruction {
+            program_id: mock_transfer_hook::ID,
+            accounts: mock_transfer_hook::accounts::InitializeExtraAccountMetaList {
+                payer: self.authority,
+                extra_account_meta_list,
+                mint: self.quote_mint,
+                counter,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: mock_transfer_hook::instruction::InitializeExtraAccountMetaList {}.data(),
+        };
+        self.svm.process(instruction, &[self.authority]).expect("initialize transfer hook");
+        vec![
+            AccountMeta::new_readonly(mock_transfer_hook::ID, false),
+            AccountMeta::new_readonly(extra_account_meta_list, false),
+            AccountMeta::new(counter, false),
+        ]
+    }
+
+    /// Swap base fees to quote through mock cp-amm, with both mints priced at $1 by mock-oracle
+    ///
+    /// Returns the cp-amm crank accounts with both price feeds filled in.
+    pub fn enable_swap_to_quote(&mut self) -> accounts::CrankDistributePage {
+        let now = self.svm.now();
+        let price_feed = self.create_price_feed(1_000_000, 0, -6, now);
+        let base_price_feed = self.create_price_feed(1_000_000, 0, -6, now);
+        self.update_config(ConfigUpdateParams {
+            base_fee_policy: Some(BaseFeePolicy::SwapToQuote),
+            price_feed: Some(price_feed),
+            base_price_feed: Some(base_price_feed),
+            oracle_program: Some(mock_oracle::ID),
+            ..Default::default()
+        })
+        .expect("enable swap to quote");
+        accounts::CrankDistributePage {
+            price_feed: Some(price_feed),
+            base_price_feed: Some(base_price_feed),
+            ..self.crank_accounts()
+        }
+    }
+
+    /// Move the mock pool's price, standing in for someone trading against it
+    pub fn set_pool_sqrt_price(&mut self, sqrt_price_x64: u128) {
+        let instruction = Instruction {
+            program_id: mock_cp_amm::ID,
+            accounts: mock_cp_amm::accounts::SetSqrtPrice { pool: self.mock_pool.pool }.to_account_metas(None),
+            data: mock_cp_amm::instruction::SetSqrtPrice { sqrt_price_x64 }.data(),
+        };
+        self.svm.process(instruction, &[]).expect("set sqrt price");
+    }
+
+    /// Open another position in the config's mock pool, owned by the position owner PDA
+    pub fn create_position(&mut self) -> Pubkey {
+        let position = Pubkey::new_unique();
+        let instruction = Instruction {
+            program_id: mock_cp_amm::ID,
+            accounts: mock_cp_amm::accounts::CreatePosition {
+                payer: self.authority,
+                pool: self.mock_pool.pool,
+                position,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: mock_cp_amm::instruction::CreatePosition { owner: self.position_owner }.data(),
+        };
+        self.svm
+            .process(instruction, &[self.authority, position])
+            .expect("create position");
+        position
+    }
+
+    /// Give the honorary position liquidity, deposited into the pool's vaults
+    pub fn add_liquidity(&mut self, quote_amount: u64, base_amount: u64) {
+        let (funder_quote, funder_base) = (self.funder_quote, self.funder_base);
+        self.svm.mint_to(&funder_quote, quote_amount);
+        self.svm.mint_to(&funder_base, base_amount);
+        let instruction = Instruction {
+            program_id: mock_cp_amm::ID,
+            accounts: mock_cp_amm::accounts::AccrueFees {
+                funder: self.authority,
+                pool: self.mock_pool.pool,
+                position: self.mock_pool.position,
+                funder_quote,
+                funder_base,
+                quote_vault: self.mock_pool.quote_vault,
+                base_vault: self.mock_pool.base_vault,
+                quote_mint: self.quote_mint,
+                base_mint: self.base_mint,
+                quote_token_program: self.quote_token_program,
+                base_token_program: self.base_token_program,
+            }
+            .to_account_metas(None),
+            data: mock_cp_amm::instruction::AddLiquidity { quote_amount, base_amount }.data(),
+        };
+        self.svm.process(instruction, &[self.authority]).expect("add liquidity");
+    }
+
+    /// Credit the honorary position with fees, deposited into the pool's vaults
+    pub fn accrue_fees(&mut self, quote_amount: u64, base_amount: u64) {
+        let position = self.mock_pool.position;
+        self.accrue_fees_to(position, quote_amount, base_amount);
+    }
+
+    /// Credit `position` with fees, deposited into the pool's vaults
+    pub fn accrue_fees_to(&mut self, position: Pubkey, quote_amount: u64, base_amount: u64) {
+        let (funder_quote, funder_base) = (self.funder_quote, self.funder_base);
+        self.svm.mint_to(&funder_quote, quote_amount);
+        self.svm.mint_to(&funder_base, base_amount);
+        let instruction = Instruction {
+            program_id: mock_cp_amm::ID,
+            accounts: mock_cp_amm::accounts::AccrueFees {
+                funder: self.authority,
+                pool: self.mock_pool.pool,
+                position,
+                funder_quote,
+                funder_base,
+                quote_vault: self.mock_pool.quote_vault,
+                base_vault: self.mock_pool.base_vault,
+                quote_mint: self.quote_mint,
+                base_mint: self.base_mint,
+                quote_token_program: self.quote_token_program,
+                base_token_program: self.base_token_program,
+            }
+            .to_account_metas(None),
+            data: mock_cp_amm::instruction::AccrueFees { quote_amount, base_amount }.data(),
+        };
+        self.svm.process(instruction, &[self.authority]).expect("accrue fees");
+    }
+
+    /// Create a mock-oracle price account and write a price into it
+    pub fn create_price_feed(&mut self, price: i64, conf: u64, expo: i32, publish_time: i64) -> Pubkey {
+        let price_feed = Pubkey::new_unique();
+        self.svm.create_account(price_feed, mock_oracle::PriceFeedData::LEN, &mock_oracle::ID);
+        let instruction = Instruction {
+            program_id: mock_oracle::ID,
+            accounts: mock_oracle::accounts::SetPrice { price_feed }.to_account_metas(None),
+            data: mock_oracle::instruction::SetPrice { price, conf, expo, publish_time }.data(),
+        };
+        self.svm.process(instruction, &[]).expect("set price");
+        price_feed
+    }
+
+    pub fn progress(&self, day_id: i64) -> ProgressAccount {
+        self.svm.anchor_account(&progress_pda(&self.config, day_id))
+    }
+
+    pub fn config_account(&self) -> damm_v2_fee_distributor::ConfigAccount {
+        self.svm.anchor_account(&self.config)
+    }
+
+    pub fn quote_balance(&self, token_account: &Pubkey) -> u64 {
+        self.svm.token_balance(token_account)
+    }
+
+    pub fn quote_ata(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &self.quote_mint, &self.quote_token_program)
+    }
+}
+
+/// Each investor's stream (read-only) and quote ATA (writable), as the crank expects them
+pub fn investor_metas(page: &[Investor]) -> Vec<AccountMeta> {
+    page.iter()
+        .flat_map(|investor| {
+            [
+                AccountMeta::new_readonly(investor.stream, false),
+                AccountMeta::new(investor.quote_ata, false),
+            ]
+        })
+        .collect()
+}
+
+/// Create a mock cp-amm pool at a 1:1 price with no swap fee, and a position owned by `owner`
+fn create_mock_pool(
+    svm: &mut Svm,
+    payer: &Pubkey,
+    quote_mint: &Pubkey,
+    base_mint: &Pubkey,
+    quote_token_program: &Pubkey,
+    base_token_program: &Pubkey,
+) -> MockPool {
+    // The position is owned by the position owner PDA of the config that will be created on the pool
+    let pool = Pubkey::new_unique();
+    let owner = position_owner_pda(&pda(&[CONFIG_SEED, pool.as_ref()], &DISTRIBUTOR));
+    let position = Pubkey::new_unique();
+    let mock_pool = MockPool {
+        pool,
+        pool_authority: pda(&[mock_cp_amm::POOL_AUTHORITY_SEED, pool.as_ref()], &mock_cp_amm::ID),
+        quote_vault: pda(&[mock_cp_amm::QUOTE_VAULT_SEED, pool.as_ref()], &mock_cp_amm::ID),
+        base_vault: pda(&[mock_cp_amm::BASE_VAULT_SEED, pool.as_ref()], &mock_cp_amm::ID),
+        position,
+    };
+
+    let initialize_pool = Instruction {
+        program_id: mock_cp_amm::ID,
+        accounts: mock_cp_amm::accounts::InitializePool {
+            payer: *payer,
+            pool,
+            pool_authority: mock_pool.pool_authority,
+            quote_mint: *quote_mint,
+            base_mint: *base_mint,
+            quote_vault: mock_pool.quote_vault,
+            base_vault: mock_pool.base_vault,
+            quote_token_program: *quote_token_program,
+            base_token_program: *base_token_program,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: mock_cp_amm::instruction::InitializePool {
+            sqrt_price_x64: SQRT_PRICE_ONE,
+            swap_fee_bps: 0,
+        }
+        .data(),
+    };
+    let create_position = Instruction {
+        program_id: mock_cp_amm::ID,
+        accounts: mock_cp_amm::accounts::CreatePosition {
+            payer: *payer,
+            pool,
+            position,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: mock_cp_amm::instruction::CreatePosition { owner }.data(),
+    };
+    svm.process_transaction(&[initialize_pool, create_position], &[*payer, pool, position])
+        .expect("create mock pool");
+    mock_pool
+}
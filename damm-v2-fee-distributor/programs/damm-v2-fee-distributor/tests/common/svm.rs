@@ -0,0 +1,857 @@
+//! A minimal in-process SVM for integration tests
+//!
+//! Programs run natively: their entrypoints are linked into the test binary
+//! and cross-program invocations are routed through `solana_program`'s
+//! syscall stubs, the way `solana-program-test` runs native processors.
+//! Accounts are laid out as the loader serializes them, so `realloc`,
+//! `assign` and `close` behave as on chain.
+//!
+//! This stands in for `litesvm`, which is not available to these tests. Like
+//! the real runtime it checks every instruction, top-level or CPI, once its
+//! program returns and before each CPI it makes:
+//!
+//! - signers must have signed the transaction or be PDAs of the caller, and a
+//!   CPI may not add signer or writable privileges its caller lacks
+//! - read-only accounts keep their lamports, data and owner (`Immutable`)
+//! - only an account's owner may change its data, debit its lamports or
+//!   assign it elsewhere, and only once its data is zeroed (`IllegalOwner`)
+//! - an instruction moves lamports between accounts without creating or
+//!   destroying any (`InvalidArgument`)
+//!
+//! Violations surface as these `ProgramError`s rather than the runtime's
+//! `InstructionError`s. Compute is not metered, rent exemption is not checked
+//! and programs run natively, so their SBF builds are not exercised here.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Once;
+
+use anchor_lang::prelude::*;
+use anchor_lang::Event;
+use anchor_lang::solana_program::clock::Clock;
+use anchor_lang::solana_program::entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE};
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::program_stubs::{self, SyscallStubs};
+use anchor_lang::solana_program::rent::Rent;
+use anchor_lang::solana_program::{bpf_loader_upgradeable, system_program, sysvar};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_hook;
+use anchor_spl::token_2022::spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+use anchor_spl::token_2022::spl_token_2022::instruction::TokenInstruction;
+use spl_tlv_account_resolution::state::ExtraAccountMetaList;
+use spl_transfer_hook_interface::get_extra_account_metas_address;
+use spl_transfer_hook_interface::instruction::{self as transfer_hook_instruction, ExecuteInstruction};
+
+pub const DAY: i64 = 86_400;
+
+/// Native program entrypoint
+pub type Processor = for<'a> fn(&Pubkey, &'a [AccountInfo<'a>], &[u8]) -> ProgramResult;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Account {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+/// Execution state the syscall stubs read, one per test thread
+#[derive(Default)]
+struct InvokeContext {
+    processors: HashMap<Pubkey, Processor>,
+    program_stack: Vec<Pubkey>,
+    clock: Clock,
+    logs: Vec<String>,
+    program_data: Vec<Vec<u8>>,
+    return_data: Option<(Pubkey, Vec<u8>)>,
+    /// Account state as each running instruction last saw it, innermost last
+    frames: Vec<Frame>,
+}
+
+/// An account as its instruction's program last handed over control
+struct AccountSnapshot {
+    lamports: u64,
+    data: Vec<u8>,
+    owner: Pubkey,
+    is_writable: bool,
+}
+
+/// The accounts of one running instruction, checked when its program returns
+struct Frame {
+    accounts: HashMap<Pubkey, AccountSnapshot>,
+    lamports: u128,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<InvokeContext> = RefCell::new(InvokeContext::default());
+}
+
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+    fn sol_log(&self, message: &str) {
+        CONTEXT.with(|context| context.borrow_mut().logs.push(message.to_string()));
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        CONTEXT.with(|context| context.borrow_mut().program_data.push(fields.concat()));
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let caller = CONTEXT.with(|context| *context.borrow().program_stack.last().unwrap());
+        let pda_signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &caller))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| ProgramError::InvalidSeeds)?;
+
+        // The caller's changes so far must hold up before the callee sees them
+        CONTEXT.with(|context| {
+            let mut context = context.borrow_mut();
+            let frame = context.frames.last_mut().unwrap();
+            verify_changes(&caller, &frame.accounts, account_infos)?;
+            refresh(&mut frame.accounts, account_infos);
+            Ok::<(), ProgramError>(())
+        })?;
+
+        // Accounts listed more than once share one view, with the widest privileges
+        let mut keys: Vec<Pubkey> = Vec::new();
+        let mut views: Vec<AccountInfo> = Vec::new();
+        for meta in &instruction.accounts {
+            let info = account_infos
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            if meta.is_signer && !info.is_signer && !pda_signers.contains(&meta.pubkey) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if meta.is_writable && !info.is_writable {
+                return Err(ProgramError::InvalidArgument);
+            }
+            match keys.iter().position(|key| *key == meta.pubkey) {
+                Some(index) => {
+                    views[index].is_signer |= meta.is_signer;
+                    views[index].is_writable |= meta.is_writable;
+                }
+                None => {
+                    keys.push(meta.pubkey);
+                    // Safety: the caller's buffers outlive the CPI, which returns before they are read back
+                    views.push(unsafe { callee_view(info, meta.is_signer, meta.is_writable) });
+                }
+            }
+        }
+        let callee_infos: Vec<AccountInfo> = instruction
+            .accounts
+            .iter()
+            .map(|meta| views[keys.iter().position(|key| *key == meta.pubkey).unwrap()].clone())
+            .collect();
+
+        CONTEXT.with(|context| context.borrow_mut().return_data = None);
+        execute(&instruction.program_id, &callee_infos, &instruction.data)?;
+
+        // Pick up data the callee resized, then take its changes as the caller's new baseline
+        // Safety: every caller info points into a buffer with its length serialized ahead of its data
+        for info in account_infos {
+            unsafe { sync_data_len(info) };
+        }
+        CONTEXT.with(|context| {
+            let mut context = context.borrow_mut();
+            refresh(&mut context.frames.last_mut().unwrap().accounts, account_infos);
+        });
+        Ok(())
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = CONTEXT.with(|context| context.borrow().clock.clone());
+        // Safety: `Clock::get` passes a pointer to its own `Clock`
+        unsafe { std::ptr::write(var_addr as *mut Clock, clock) };
+        0
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        // Safety: `Rent::get` passes a pointer to its own `Rent`
+        unsafe { std::ptr::write(var_addr as *mut Rent, Rent::default()) };
+        0
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        CONTEXT.with(|context| {
+            let mut context = context.borrow_mut();
+            let program_id = *context.program_stack.last().unwrap();
+            context.return_data = Some((program_id, data.to_vec()));
+        });
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        CONTEXT.with(|context| context.borrow().return_data.clone())
+    }
+
+    fn sol_get_stack_height(&self) -> u64 {
+        CONTEXT.with(|context| context.borrow().program_stack.len() as u64)
+    }
+}
+
+/// Run `program_id`'s processor over `accounts`, then check what it changed
+fn execute<'a>(program_id: &Pubkey, accounts: &'a [AccountInfo<'a>], data: &[u8]) -> ProgramResult {
+    let mut snapshots = HashMap::new();
+    refresh(&mut snapshots, accounts);
+    let processor = CONTEXT.with(|context| {
+        let mut context = context.borrow_mut();
+        context.program_stack.push(*program_id);
+        context.frames.push(Frame {
+            accounts: snapshots,
+            lamports: total_lamports(accounts),
+        });
+        context.processors.get(program_id).copied()
+    });
+    let result = match processor {
+        Some(processor) => processor(program_id, accounts, data),
+        None => Err(ProgramError::IncorrectProgramId),
+    };
+    let frame = CONTEXT.with(|context| {
+        let mut context = context.borrow_mut();
+        context.program_stack.pop();
+        context.frames.pop().unwrap()
+    });
+    result?;
+    verify_changes(program_id, &frame.accounts, accounts)?;
+    if total_lamports(accounts) != frame.lamports {
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// Record `accounts` as they are now, keeping the widest privileges of duplicates
+fn refresh(snapshots: &mut HashMap<Pubkey, AccountSnapshot>, accounts: &[AccountInfo]) {
+    let mut seen: Vec<Pubkey> = Vec::new();
+    for info in accounts {
+        let is_writable = if seen.contains(info.key) {
+            info.is_writable || snapshots[info.key].is_writable
+        } else {
+            seen.push(*info.key);
+            info.is_writable || snapshots.get(info.key).is_some_and(|snapshot| snapshot.is_writable)
+        };
+        snapshots.insert(
+            *info.key,
+            AccountSnapshot {
+                lamports: info.lamports(),
+                data: info.data.borrow().to_vec(),
+                owner: *info.owner,
+                is_writable,
+            },
+        );
+    }
+}
+
+/// Check `program_id`'s changes to `accounts` since `snapshots` against the runtime's account rules
+fn verify_changes(
+    program_id: &Pubkey,
+    snapshots: &HashMap<Pubkey, AccountSnapshot>,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    for info in accounts {
+        let Some(before) = snapshots.get(info.key) else {
+            continue;
+        };
+        let data = info.data.borrow();
+        let lamports = info.lamports();
+        let data_changed = data[..] != before.data[..];
+        if (data_changed || lamports != before.lamports || *info.owner != before.owner) && !before.is_writable {
+            return Err(ProgramError::Immutable);
+        }
+        let owned = before.owner == *program_id;
+        if !owned && (data_changed || lamports < before.lamports) {
+            return Err(ProgramError::IllegalOwner);
+        }
+        if *info.owner != before.owner && (!owned || data.iter().any(|byte| *byte != 0)) {
+            return Err(ProgramError::IllegalOwner);
+        }
+    }
+    Ok(())
+}
+
+fn total_lamports(accounts: &[AccountInfo]) -> u128 {
+    let mut seen: Vec<&Pubkey> = Vec::new();
+    accounts
+        .iter()
+        .filter(|info| {
+            let first = !seen.contains(&info.key);
+            seen.push(info.key);
+            first
+        })
+        .map(|info| info.lamports() as u128)
+        .sum()
+}
+
+/// A fresh `AccountInfo` over the same serialized account as `info`, for a callee
+///
+/// Lamports, owner and data are shared with the caller; only the data length
+/// is the callee's own, written back by `sync_data_len` once it returns.
+unsafe fn callee_view<'b>(info: &AccountInfo, is_signer: bool, is_writable: bool) -> AccountInfo<'b> {
+    // Read through the cells' pointers: the caller may still hold a borrow of a read-only account
+    let data: &mut [u8] = *info.data.as_ptr();
+    let lamports: *mut u64 = *info.lamports.as_ptr();
+    AccountInfo {
+        key: &*(info.key as *const Pubkey),
+        lamports: Rc::new(RefCell::new(&mut *lamports)),
+        data: Rc::new(RefCell::new(std::slice::from_raw_parts_mut(data.as_mut_ptr(), data.len()))),
+        owner: &*(info.owner as *const Pubkey),
+        rent_epoch: info.rent_epoch,
+        is_signer,
+        is_writable,
+        executable: info.executable,
+    }
+}
+
+/// Point `info` at the data length now serialized ahead of its data, after a callee resized it
+unsafe fn sync_data_len(info: &AccountInfo) {
+    let data = &mut *info.data.as_ptr();
+    let data_ptr = data.as_mut_ptr();
+    let len = std::ptr::read_unaligned(data_ptr.sub(8) as *const u64) as usize;
+    *data = std::slice::from_raw_parts_mut(data_ptr, len);
+}
+
+/// The native system program: account creation, assignment and lamport transfers
+fn system_processor<'a>(_program_id: &Pubkey, accounts: &'a [AccountInfo<'a>], data: &[u8]) -> ProgramResult {
+    let tag = u32::from_le_bytes(data[..4].try_into().unwrap());
+    let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    let pubkey_at = |offset: usize| Pubkey::try_from(&data[offset..offset + 32]).unwrap();
+    let require_signer = |info: &AccountInfo| {
+        if info.is_signer {
+            Ok(())
+        } else {
+            Err(ProgramError::MissingRequiredSignature)
+        }
+    };
+    let transfer = |from: &AccountInfo, to: &AccountInfo, lamports: u64| -> ProgramResult {
+        let mut from_lamports = from.try_borrow_mut_lamports()?;
+        **from_lamports = from_lamports
+            .checked_sub(lamports)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        let mut to_lamports = to.try_borrow_mut_lamports()?;
+        **to_lamports = to_lamports
+            .checked_add(lamports)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        Ok(())
+    };
+
+    match tag {
+        // CreateAccount { lamports, space, owner }
+        0 => {
+            let (from, to) = (&accounts[0], &accounts[1]);
+            require_signer(from)?;
+            require_signer(to)?;
+            if to.lamports() > 0 || !to.data_is_empty() || *to.owner != system_program::ID {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+            transfer(from, to, u64_at(4))?;
+            to.resize(u64_at(12) as usize)?;
+            to.assign(&pubkey_at(20));
+            Ok(())
+        }
+        // Assign { owner }
+        1 => {
+            require_signer(&accounts[0])?;
+            accounts[0].assign(&pubkey_at(4));
+            Ok(())
+        }
+        // Transfer { lamports }
+        2 => {
+            require_signer(&accounts[0])?;
+            transfer(&accounts[0], &accounts[1], u64_at(4))
+        }
+        // Allocate { space }
+        8 => {
+            require_signer(&accounts[0])?;
+            accounts[0].resize(u64_at(4) as usize)
+        }
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+fn spl_token_processor<'a>(program_id: &Pubkey, accounts: &'a [AccountInfo<'a>], data: &[u8]) -> ProgramResult {
+    spl_token::processor::Processor::process(program_id, accounts, data)
+}
+
+fn spl_token_2022_processor<'a>(program_id: &Pubkey, accounts: &'a [AccountInfo<'a>], data: &[u8]) -> ProgramResult {
+    spl_token_2022::processor::Processor::process(program_id, accounts, data)?;
+    match TokenInstruction::unpack(data)? {
+        TokenInstruction::TransferChecked { amount, .. } => execute_transfer_hook(accounts, amount),
+        _ => Ok(()),
+    }
+}
+
+/// Call the mint's transfer hook for a `TransferChecked` over `accounts`
+///
+/// Token-2022 invokes hooks through `solana_cpi`, which skips the syscall
+/// stubs off chain, so the hook is run here once the transfer has gone
+/// through, resolving its extra accounts the way Token-2022 does. The
+/// accounts' `transferring` flags are already cleared by then.
+fn execute_transfer_hook(accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let [source, mint, destination, authority, additional_accounts @ ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    let hook_program_id = {
+        let mint_data = mint.try_borrow_data()?;
+        let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+        transfer_hook::get_program_id(&mint_state)
+    };
+    let Some(hook_program_id) = hook_program_id else {
+        return Ok(());
+    };
+
+    let mut instruction = transfer_hook_instruction::execute(
+        &hook_program_id,
+        source.key,
+        mint.key,
+        destination.key,
+        authority.key,
+        amount,
+    );
+    let mut account_infos = vec![source.clone(), mint.clone(), destination.clone(), authority.clone()];
+    let validation_address = get_extra_account_metas_address(mint.key, &hook_program_id);
+    if let Some(validation_info) = additional_accounts.iter().find(|info| *info.key == validation_address) {
+        instruction.accounts.push(AccountMeta::new_readonly(validation_address, false));
+        account_infos.push(validation_info.clone());
+        ExtraAccountMetaList::add_to_cpi_instruction::<ExecuteInstruction>(
+            &mut instruction,
+            &mut account_infos,
+            &validation_info.try_borrow_data()?,
+            additional_accounts,
+        )?;
+    }
+    invoke(&instruction, &account_infos)
+}
+
+/// An account serialized the way the loader lays it out, so `AccountInfo`
+/// can find the original data length ahead of the key and realloc in place
+struct SerializedAccount {
+    /// Original data length (u32) followed by the key
+    key: Box<[u8; 36]>,
+    owner: Box<Pubkey>,
+    lamports: Box<u64>,
+    /// Current data length (u64) followed by the data and room to grow
+    data: Vec<u64>,
+    data_len: usize,
+    executable: bool,
+}
+
+impl SerializedAccount {
+    fn new(key: &Pubkey, account: &Account) -> Self {
+        let mut key_bytes = Box::new([0u8; 36]);
+        key_bytes[..4].copy_from_slice(&(account.data.len() as u32).to_le_bytes());
+        key_bytes[4..].copy_from_slice(key.as_ref());
+
+        let words = (8 + account.data.len() + MAX_PERMITTED_DATA_INCREASE).div_ceil(8);
+        let mut data = vec![0u64; words];
+        data[0] = account.data.len() as u64;
+        // Safety: the words are viewed as bytes only while `data` is borrowed here
+        let bytes = unsafe { std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut u8, words * 8) };
+        bytes[8..8 + account.data.len()].copy_from_slice(&account.data);
+
+        Self {
+            key: key_bytes,
+            owner: Box::new(account.owner),
+            lamports: Box::new(account.lamports),
+            data,
+            data_len: account.data.len(),
+            executable: account.executable,
+        }
+    }
+
+    /// An `AccountInfo` over this buffer; it must not outlive `self`
+    unsafe fn account_info<'a>(&mut self, is_signer: bool, is_writable: bool) -> AccountInfo<'a> {
+        let key = &*(self.key.as_ptr().add(4) as *const Pubkey);
+        let data_ptr = (self.data.as_mut_ptr() as *mut u8).add(8);
+        AccountInfo {
+            key,
+            lamports: Rc::new(RefCell::new(&mut *(&mut *self.lamports as *mut u64))),
+            data: Rc::new(RefCell::new(std::slice::from_raw_parts_mut(data_ptr, self.data_len))),
+            owner: &*(&*self.owner as *const Pubkey),
+            rent_epoch: u64::MAX,
+            is_signer,
+            is_writable,
+            executable: self.executable,
+        }
+    }
+}
+
+/// Accounts, a clock and the programs that run against them
+pub struct Svm {
+    accounts: HashMap<Pubkey, Account>,
+    processors: HashMap<Pubkey, Processor>,
+    clock: Clock,
+    logs: Vec<String>,
+    program_data: Vec<Vec<u8>>,
+}
+
+impl Default for Svm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Svm {
+    /// An SVM with the system and token programs loaded and the clock at the Unix epoch
+    pub fn new() -> Self {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(Stubs));
+        });
+
+        let mut svm = Self {
+            accounts: HashMap::new(),
+            processors: HashMap::new(),
+            clock: Clock::default(),
+            logs: Vec::new(),
+            program_data: Vec::new(),
+        };
+        svm.add_program(system_program::ID, system_processor);
+        svm.add_program(spl_token::ID, spl_token_processor);
+        svm.add_program(spl_token_2022::ID, spl_token_2022_processor);
+        svm.add_program(anchor_spl::associated_token::ID, |_, _, _| Err(ProgramError::InvalidInstructionData));
+        svm.set_account(
+            sysvar::rent::ID,
+            Account {
+                lamports: 1,
+                data: rent_sysvar_data(&Rent::default()),
+                owner: sysvar::ID,
+                executable: false,
+            },
+        );
+        svm.sync_clock_sysvar();
+        svm
+    }
+
+    pub fn add_program(&mut self, program_id: Pubkey, processor: Processor) {
+        self.processors.insert(program_id, processor);
+        self.set_account(
+            program_id,
+            Account {
+                lamports: 1,
+                data: Vec::new(),
+                owner: bpf_loader_upgradeable::ID,
+                executable: true,
+            },
+        );
+    }
+
+    /// Give a loaded program a ProgramData account with `upgrade_authority`, returning its address
+    pub fn set_upgrade_authority(&mut self, program_id: Pubkey, upgrade_authority: Option<Pubkey>) -> Pubkey {
+        let (program_data, _) = Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::ID);
+
+        // UpgradeableLoaderState::Program { programdata_address }
+        let mut program = self.accounts[&program_id].clone();
+        program.data = [&2u32.to_le_bytes()[..], program_data.as_ref()].concat();
+        self.set_account(program_id, program);
+
+        // UpgradeableLoaderState::ProgramData { slot, upgrade_authority_address }
+        let mut data = [&3u32.to_le_bytes()[..], &0u64.to_le_bytes()[..]].concat();
+        match upgrade_authority {
+            Some(authority) => data.extend([&[1u8][..], authority.as_ref()].concat()),
+            None => data.push(0),
+        }
+        let lamports = self.minimum_balance(data.len());
+        self.set_account(
+            program_data,
+            Account { lamports, data, owner: bpf_loader_upgradeable::ID, executable: false },
+        );
+        program_data
+    }
+
+    pub fn set_account(&mut self, address: Pubkey, account: Account) {
+        self.accounts.insert(address, account);
+    }
+
+    pub fn account(&self, address: &Pubkey) -> Option<&Account> {
+        self.accounts.get(address)
+    }
+
+    /// Deserialize an Anchor account, checking its discriminator
+    pub fn anchor_account<T: AccountDeserialize>(&self, address: &Pubkey) -> T {
+        let account = self.account(address).unwrap_or_else(|| panic!("missing account {address}"));
+        T::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    /// Fund `address` with lamports, creating it as a system account if needed
+    pub fn airdrop(&mut self, address: &Pubkey, lamports: u64) {
+        let account = self.accounts.entry(*address).or_insert_with(|| Account {
+            owner: system_program::ID,
+            ..Account::default()
+        });
+        account.lamports += lamports;
+    }
+
+    pub fn minimum_balance(&self, data_len: usize) -> u64 {
+        Rent::default().minimum_balance(data_len)
+    }
+
+    // Clock
+
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    pub fn now(&self) -> i64 {
+        self.clock.unix_timestamp
+    }
+
+    pub fn day_id(&self) -> i64 {
+        self.now() / DAY
+    }
+
+    pub fn warp_to_timestamp(&mut self, unix_timestamp: i64) {
+        self.clock.unix_timestamp = unix_timestamp;
+        self.clock.slot += 1;
+        self.sync_clock_sysvar();
+    }
+
+    pub fn warp_by(&mut self, seconds: i64) {
+        self.warp_to_timestamp(self.now() + seconds);
+    }
+
+    /// Move to `seconds_into_day` seconds after the start of `day_id`
+    pub fn warp_to_day(&mut self, day_id: i64, seconds_into_day: i64) {
+        self.warp_to_timestamp(day_id * DAY + seconds_into_day);
+    }
+
+    /// Move to the same time of day, `days` days later
+    pub fn warp_days(&mut self, days: i64) {
+        self.warp_by(days * DAY);
+    }
+
+    fn sync_clock_sysvar(&mut self) {
+        let clock = &self.clock;
+        let data = [
+            clock.slot.to_le_bytes(),
+            clock.epoch_start_timestamp.to_le_bytes(),
+            clock.epoch.to_le_bytes(),
+            clock.leader_schedule_epoch.to_le_bytes(),
+            clock.unix_timestamp.to_le_bytes(),
+        ]
+        .concat();
+        self.set_account(
+            sysvar::clock::ID,
+            Account { lamports: 1, data, owner: sysvar::ID, executable: false },
+        );
+    }
+
+    // Tokens
+
+    /// Create a rent-exempt account of `space` zeroed bytes owned by `owner`
+    pub fn create_account(&mut self, address: Pubkey, space: usize, owner: &Pubkey) {
+        let lamports = self.minimum_balance(space);
+        self.set_account(address, Account { lamports, data: vec![0; space], owner: *owner, executable: false });
+    }
+
+    /// Create an initialized mint with no supply
+    pub fn create_mint(&mut self, mint: Pubkey, authority: &Pubkey, decimals: u8, token_program: &Pubkey) {
+        self.create_mint_with_extensions(mint, authority, decimals, token_program, &[], Vec::new());
+    }
+
+    /// Create a Token-2022 mint, running `extension_instructions` to set up `extensions` before
+    /// the mint itself is initialized
+    pub fn create_mint_with_extensions(
+        &mut self,
+        mint: Pubkey,
+        authority: &Pubkey,
+        decimals: u8,
+        token_program: &Pubkey,
+        extensions: &[ExtensionType],
+        extension_instructions: Vec<Instruction>,
+    ) {
+        let space = if extensions.is_empty() {
+            spl_token::state::Mint::LEN
+        } else {
+            ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions).unwrap()
+        };
+        self.create_account(mint, space, token_program);
+        let mut instructions = extension_instructions;
+        instructions.push(
+            spl_token_2022::instruction::initialize_mint2(token_program, &mint, authority, None, decimals).unwrap(),
+        );
+        self.process_transaction(&instructions, &[]).expect("initialize mint");
+    }
+
+    /// Create an empty token account, sized for any extensions its mint requires
+    pub fn create_token_account(&mut self, address: Pubkey, mint: &Pubkey, owner: &Pubkey) {
+        let token_program = self.account(mint).expect("mint").owner;
+        let space = if token_program == spl_token_2022::ID {
+            let mint_data = &self.account(mint).unwrap().data;
+            let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(mint_data).unwrap();
+            let extensions = ExtensionType::get_required_init_account_extensions(
+                &mint_state.get_extension_types().unwrap(),
+            );
+            ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(&extensions).unwrap()
+        } else {
+            spl_token::state::Account::LEN
+        };
+        self.create_account(address, space, &token_program);
+        let instruction =
+            spl_token_2022::instruction::initialize_account3(&token_program, &address, mint, owner).unwrap();
+        self.process(instruction, &[]).expect("initialize token account");
+    }
+
+    /// Create `owner`'s associated token account for `mint`, returning its address
+    pub fn create_ata(&mut self, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        let token_program = self.account(mint).expect("mint").owner;
+        let ata = get_associated_token_address_with_program_id(owner, mint, &token_program);
+        self.create_token_account(ata, mint, owner);
+        ata
+    }
+
+    /// Mint `amount` into a token account, signed by the mint authority
+    pub fn mint_to(&mut self, token_account: &Pubkey, amount: u64) {
+        let account = self.account(token_account).expect("token account");
+        let token_program = account.owner;
+        let mint = Pubkey::try_from(&account.data[..32]).unwrap();
+        let mint_data = &self.account(&mint).expect("mint").data;
+        let COption::Some(authority) = spl_token::state::Mint::unpack_from_slice(&mint_data[..82]).unwrap().mint_authority
+        else {
+            panic!("mint {mint} has no mint authority");
+        };
+        let instruction =
+            spl_token_2022::instruction::mint_to(&token_program, &mint, token_account, &authority, &[], amount).unwrap();
+        self.process(instruction, &[authority]).expect("mint to");
+    }
+
+    pub fn token_balance(&self, token_account: &Pubkey) -> u64 {
+        let account = self.account(token_account).expect("token account");
+        u64::from_le_bytes(account.data[64..72].try_into().unwrap())
+    }
+
+    // Execution
+
+    /// Process `instructions` atomically, with `signers` having signed the transaction
+    pub fn process_transaction(&mut self, instructions: &[Instruction], signers: &[Pubkey]) -> ProgramResult {
+        CONTEXT.with(|context| {
+            let mut context = context.borrow_mut();
+            context.processors = self.processors.clone();
+            context.clock = self.clock.clone();
+            context.logs.clear();
+            context.program_data.clear();
+            context.return_data = None;
+        });
+
+        let mut accounts = self.accounts.clone();
+        let result = instructions
+            .iter()
+            .try_for_each(|instruction| process_instruction(&mut accounts, instruction, signers));
+
+        CONTEXT.with(|context| {
+            let mut context = context.borrow_mut();
+            self.logs = std::mem::take(&mut context.logs);
+            self.program_data = std::mem::take(&mut context.program_data);
+        });
+        if result.is_ok() {
+            self.accounts = accounts;
+        }
+        result
+    }
+
+    pub fn process(&mut self, instruction: Instruction, signers: &[Pubkey]) -> ProgramResult {
+        self.process_transaction(&[instruction], signers)
+    }
+
+    /// Log lines of the last transaction
+    pub fn logs(&self) -> &[String] {
+        &self.logs
+    }
+
+    /// Events of type `E` emitted by the last transaction
+    pub fn events<E: Event + AnchorDeserialize>(&self) -> Vec<E> {
+        self.program_data
+            .iter()
+            .filter_map(|data| data.strip_prefix(E::DISCRIMINATOR))
+            .map(|mut data| E::deserialize(&mut data).unwrap())
+            .collect()
+    }
+}
+
+/// Run one top-level instruction against `accounts`, writing back what it changed
+fn process_instruction(
+    accounts: &mut HashMap<Pubkey, Account>,
+    instruction: &Instruction,
+    signers: &[Pubkey],
+) -> ProgramResult {
+    // Accounts listed more than once share one buffer, with the widest privileges
+    let mut keys: Vec<Pubkey> = Vec::new();
+    let mut privileges: Vec<(bool, bool)> = Vec::new();
+    for meta in &instruction.accounts {
+        if meta.is_signer && !signers.contains(&meta.pubkey) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        match keys.iter().position(|key| *key == meta.pubkey) {
+            Some(index) => {
+                privileges[index].0 |= meta.is_signer;
+                privileges[index].1 |= meta.is_writable;
+            }
+            None => {
+                keys.push(meta.pubkey);
+                privileges.push((meta.is_signer, meta.is_writable));
+            }
+        }
+    }
+
+    let mut buffers: Vec<SerializedAccount> = keys
+        .iter()
+        .map(|key| {
+            let account = accounts.get(key).cloned().unwrap_or(Account {
+                owner: system_program::ID,
+                ..Account::default()
+            });
+            SerializedAccount::new(key, &account)
+        })
+        .collect();
+
+    // Safety: `buffers` is not touched again while the infos over it are alive
+    let unique_infos: Vec<AccountInfo> = buffers
+        .iter_mut()
+        .zip(&privileges)
+        .map(|(buffer, (is_signer, is_writable))| unsafe { buffer.account_info(*is_signer, *is_writable) })
+        .collect();
+    let infos: Vec<AccountInfo> = instruction
+        .accounts
+        .iter()
+        .map(|meta| unique_infos[keys.iter().position(|key| *key == meta.pubkey).unwrap()].clone())
+        .collect();
+
+    let result = execute(&instruction.program_id, &infos, &instruction.data);
+    if result.is_ok() {
+        for (key, info) in keys.iter().zip(&unique_infos) {
+            let account = Account {
+                lamports: info.lamports(),
+                data: info.data.borrow().to_vec(),
+                owner: *info.owner,
+                executable: info.executable,
+            };
+            // Accounts left without lamports are garbage collected
+            if account.lamports == 0 {
+                accounts.remove(key);
+            } else {
+                accounts.insert(*key, account);
+            }
+        }
+    }
+    result
+}
+
+fn rent_sysvar_data(rent: &Rent) -> Vec<u8> {
+    [
+        &rent.lamports_per_byte_year.to_le_bytes()[..],
+        &rent.exemption_threshold.to_le_bytes()[..],
+        &[rent.burn_percent][..],
+    ]
+    .concat()
+}
@@ -0,0 +1,572 @@
+// `solana_program`'s system program and loader ids are deprecated in newer Solana crates
+#![allow(deprecated)]
+
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::instruction::AccountMeta;
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_2022::spl_token_2022::extension::ExtensionType;
+use common::*;
+use spl_transfer_hook_interface::error::TransferHookError;
+use damm_v2_fee_distributor::cap_window::CapOverflowPolicy;
+use damm_v2_fee_distributor::lock_provider::LockStatus;
+use damm_v2_fee_distributor::{
+    CapWindow, ConfigAccount, ConfigDecommissioned, ConfigUpdateParams, CreatorPayoutDayClosed, DaySummary, HonoraryPositionInitialized,
+    InvestorPayoutPage, InvestorStreamInactive, ProgressClosed, ProgressHistory, QuoteFeesClaimed, CONFIG_SEED, PAUSE_ALL,
+};
+
+#[test]
+fn initializes_a_config_for_a_pool() {
+    let mut fixture = Fixture::new();
+    let pool = Pubkey::new_unique();
+    let position = Pubkey::new_unique();
+    let instruction = fixture.initialize_config_ix(pool, position, fixture.quote_mint, -10, 10);
+    fixture.svm.process(instruction, &[fixture.authority]).unwrap();
+
+    let config_key = pda(&[CONFIG_SEED, pool.as_ref()], &DISTRIBUTOR);
+    let config: ConfigAccount = fixture.svm.anchor_account(&config_key);
+    assert_eq!(config.pool_id, pool);
+    assert_eq!(config.position_id, position);
+    assert_eq!(config.quote_mint, fixture.quote_mint);
+    assert_eq!((config.tick_lower, config.tick_upper), (-10, 10));
+    assert_eq!(config.creation_ts, fixture.svm.now());
+    assert_eq!(config.authority, fixture.authority);
+
+    let initialized = fixture.svm.events::<HonoraryPositionInitialized>();
+    assert_eq!(initialized.len(), 1);
+    assert_eq!(initialized[0].position_id, position);
+    assert_eq!(initialized[0].owner_pda, position_owner_pda(&config_key));
+}
+
+#[test]
+fn cranks_and_finalizes_a_single_page_day() {
+    let mut fixture = Fixture::new();
+    let investors = [fixture.add_locked_investor(600_000), fixture.add_locked_investor(400_000)];
+    fixture.accrue_fees(DAILY_FEES, 0);
+
+    fixture.crank(&investors).unwrap();
+    let claimed = fixture.svm.events::<QuoteFeesClaimed>();
+    assert_eq!(claimed[0].claimed_quote, DAILY_FEES);
+    assert_eq!(fixture.quote_balance(&investors[0].quote_ata), 300_000);
+    assert_eq!(fixture.quote_balance(&investors[1].quote_ata), 200_000);
+
+    // An untallied day can only be finalized once it is over
+    let day_id = fixture.svm.day_id();
+    fixture.svm.warp_to_day(day_id + 1, 0);
+    fixture.finalize(day_id).unwrap();
+    assert_eq!(fixture.quote_balance(&fixture.creator_quote_ata), 500_000);
+    assert_eq!(fixture.quote_balance(&fixture.program_quote_treasury), 0);
+
+    let summary: DaySummary = fixture.svm.anchor_account(&day_summary_pda(&fixture.config, day_id));
+    assert_eq!(summary.claimed_quote, DAILY_FEES);
+    assert_eq!(summary.distributed_to_investors, 500_000);
+    assert_eq!(summary.creator_amount, 500_000);
+    assert_eq!(summary.investors_paid, 2);
+    assert!(fixture.progress(day_id).finalized);
+}
+
+#[test]
+fn pays_tallied_pages_against_the_day_wide_total() {
+    let mut fixture = Fixture::new();
+    fixture
+        .update_config(ConfigUpdateParams {
+            max_investor_share_bps: Some(10_000),
+            ..Default::default()
+        })
+        .unwrap();
+    let first_page = [fixture.add_locked_investor(500_000), fixture.add_locked_investor(300_000)];
+    let second_page = [fixture.add_locked_investor(200_000)];
+    fixture.accrue_fees(DAILY_FEES, 0);
+
+    fixture.tally(&first_page).unwrap();
+    fixture.tally(&second_page).unwrap();
+    fixture.crank(&first_page).unwrap();
+    fixture.crank(&second_page).unwrap();
+
+    let page = fixture.svm.events::<InvestorPayoutPage>();
+    assert_eq!((page[0].page_id, page[0].page_total_distributed), (2, 100_000));
+    assert_eq!(fixture.quote_balance(&first_page[0].quote_ata), 250_000);
+    assert_eq!(fixture.quote_balance(&first_page[1].quote_ata), 150_000);
+    assert_eq!(fixture.quote_balance(&second_page[0].quote_ata), 100_000);
+
+    let day_id = fixture.svm.day_id();
+    let progress = fixture.progress(day_id);
+    assert_eq!(progress.pagination_cursor, 2);
+    assert_eq!(progress.investors_tallied, 3);
+    assert_eq!(progress.cumulative_distributed_today, 500_000);
+
+    // Every tallied investor was paid, so the day can close before it is over
+    fixture.finalize(day_id).unwrap();
+    let closed = fixture.svm.events::<CreatorPayoutDayClosed>();
+    assert_eq!(closed[0].creator_amount, 500_000);
+}
+
+#[test]
+fn keeps_held_back_overflow_on_a_day_nobody_can_be_paid() {
+    let mut fixture = Fixture::new();
+    fixture
+        .update_config(ConfigUpdateParams {
+            cap_overflow_policy: Some(CapOverflowPolicy::CarryForward),
+            ..Default::default()
+        })
+        .unwrap();
+    let cap_window = fixture.initialize_cap_window();
+    let investor = fixture.add_locked_investor(1_000_000);
+    let crank_accounts = |fixture: &Fixture| damm_v2_fee_distributor::accounts::CrankDistributePage {
+        cap_window: Some(cap_window),
+        ..fixture.crank_accounts()
+    };
+    let finalize_accounts = |fixture: &Fixture, day_id| damm_v2_fee_distributor::accounts::FinalizeDay {
+        cap_window: Some(cap_window),
+        ..fixture.finalize_accounts(day_id)
+    };
+
+    // A 200k daily cap holds back 300k of the 500k investor pool
+    fixture.accrue_fees(DAILY_FEES, 0);
+    let args = CrankArgs {
+        daily_cap: Some(200_000),
+        ..Default::default()
+    };
+    let instruction = fixture.crank_ix(crank_accounts(&fixture), &[investor], args, Vec::new());
+    fixture.svm.process(instruction, &[fixture.cranker]).unwrap();
+    let day_id = fixture.svm.day_id();
+    fixture.svm.warp_to_day(day_id + 1, 0);
+    let instruction = fixture.finalize_ix(finalize_accounts(&fixture, day_id), day_id, Vec::new());
+    fixture.svm.process(instruction, &[fixture.cranker]).unwrap();
+    let window: CapWindow = fixture.svm.anchor_account(&cap_window);
+    assert_eq!(window.held_back, 300_000);
+
+    // The next day the only stream is cancelled at the snapshot, so nobody has weight
+    fixture.cancel_stream(investor.stream, fixture.svm.day_id() * DAY);
+    fixture.accrue_fees(DAILY_FEES, 0);
+    let instruction = fixture.crank_ix(crank_accounts(&fixture), &[investor], CrankArgs::default(), Vec::new());
+    fixture.svm.process(instruction, &[fixture.cranker]).unwrap();
+    let day_id = fixture.svm.day_id();
+    fixture.svm.warp_to_day(day_id + 1, 0);
+    let instruction = fixture.finalize_ix(finalize_accounts(&fixture, day_id), day_id, Vec::new());
+    fixture.svm.process(instruction, &[fixture.cranker]).unwrap();
+
+    // The creator gets the day's claim and the reserve stays held back
+    let closed = fixture.svm.events::<CreatorPayoutDayClosed>();
+    assert_eq!(closed[0].creator_amount, DAILY_FEES);
+    let progress = fixture.progress(day_id);
+    assert_eq!((progress.held_back_reserved, progress.held_back_released), (300_000, 0));
+    let window: CapWindow = fixture.svm.anchor_account(&cap_window);
+    assert_eq!(window.held_back, 300_000);
+}
+
+#[test]
+fn skips_cancelled_streams() {
+    let mut fixture = Fixture::new();
+    let investors = [fixture.add_locked_investor(500_000), fixture.add_locked_investor(500_000)];
+    let day_start = fixture.svm.day_id() * DAY;
+    fixture.cancel_stream(investors[1].stream, day_start);
+    fixture.accrue_fees(DAILY_FEES, 0);
+
+    fixture.crank(&investors).unwrap();
+    let inactive = fixture.svm.events::<InvestorStreamInactive>();
+    assert_eq!(inactive.len(), 1);
+    assert_eq!(inactive[0].stream, investors[1].stream);
+    assert_eq!(inactive[0].status, LockStatus::Cancelled);
+    assert_eq!(fixture.quote_balance(&investors[0].quote_ata), 500_000);
+    assert_eq!(fixture.quote_balance(&investors[1].quote_ata), 0);
+    assert_eq!(fixture.progress(fixture.svm.day_id()).investors_skipped, 1);
+}
+
+#[test]
+fn claims_again_right_after_midnight() {
+    // The gate is the day, not 24h since the last claim, so a late crank doesn't push back the next day
+    let mut fixture = Fixture::new();
+    let investors = [fixture.add_locked_investor(1_000_000)];
+    let first_day = fixture.svm.day_id();
+    fixture.svm.warp_to_day(first_day, DAY - 60);
+    fixture.accrue_fees(DAILY_FEES, 0);
+    fixture.crank(&investors).unwrap();
+
+    fixture.svm.warp_to_day(first_day + 1, 0);
+    fixture.finalize(first_day).unwrap();
+    fixture.accrue_fees(DAILY_FEES, 0);
+    fixture.crank(&investors).unwrap();
+    assert_eq!(fixture.progress(first_day + 1).claimed_quote_for_day, DAILY_FEES);
+}
+
+#[test]
+fn runs_a_fresh_day_after_the_day_boundary_and_closes_old_progress() {
+    let mut fixture = Fixture::new();
+    let investors = [fixture.add_locked_investor(1_000_000)];
+    let first_day = fixture.svm.day_id();
+    fixture.accrue_fees(DAILY_FEES, 0);
+    fixture.crank(&investors).unwrap();
+
+    fixture.svm.warp_days(1);
+    fixture.finalize(first_day).unwrap();
+    let second_day = fixture.svm.day_id();
+    assert_eq!(second_day, first_day + 1);
+    fixture.accrue_fees(DAILY_FEES, 0);
+    fixture.crank(&investors).unwrap();
+    fixture.svm.warp_days(1);
+    fixture.finalize(second_day).unwrap();
+    assert_eq!(fixture.quote_balance(&investors[0].quote_ata), 1_000_000);
+    assert_eq!(fixture.quote_balance(&fixture.creator_quote_ata), 1_000_000);
+    assert_eq!(fixture.progress(second_day).snapshot_ts, second_day * DAY);
+
+    // Past the default 30-day retention the first day's progress can be closed
+    fixture.svm.warp_to_day(first_day + 31, 0);
+    let cranker_lamports = fixture.svm.account(&fixture.cranker).unwrap().lamports;
+    let progress_lamports = fixture.svm.account(&progress_pda(&fixture.config, first_day)).unwrap().lamports;
+    fixture.close_progress(first_day, fixture.cranker).unwrap();
+
+    assert!(fixture.svm.account(&progress_pda(&fixture.config, first_day)).is_none());
+    assert_eq!(
+        fixture.svm.account(&fixture.cranker).unwrap().lamports,
+        cranker_lamports + progress_lamports
+    );
+    let closed = fixture.svm.events::<ProgressClosed>();
+    assert_eq!((closed[0].day_id, closed[0].payer), (first_day, fixture.cranker));
+    assert!(closed[0].folded_into_history);
+    let history: ProgressHistory = fixture.svm.anchor_account(&fixture.progress_history());
+    assert_eq!(history.days_closed, 1);
+    assert_eq!(history.total_claimed, DAILY_FEES);
+    assert_eq!(history.last_closed_day_id, first_day);
+}
+
+#[test]
+fn closes_progress_of_a_config_without_a_history() {
+    let mut fixture = Fixture::new();
+    let investors = [fixture.add_locked_investor(1_000_000)];
+    let pool = fixture.add_config_without_history();
+    fixture.use_config(pool);
+    let day_id = fixture.svm.day_id();
+    fixture.accrue_fees(DAILY_FEES, 0);
+    fixture.crank(&investors).unwrap();
+    fixture.svm.warp_days(1);
+    fixture.finalize(day_id).unwrap();
+
+    fixture.svm.warp_days(30);
+    let instruction = fixture.close_progress_ix(day_id, fixture.cranker, None);
+    fixture.svm.process(instruction, &[]).unwrap();
+    assert!(fixture.svm.account(&progress_pda(&fixture.config, day_id)).is_none());
+    let closed = fixture.svm.events::<ProgressClosed>();
+    assert!(!closed[0].folded_into_history);
+}
+
+#[test]
+fn close_progress_rejects_another_configs_progress() {
+    let mut fixture = Fixture::new();
+    let investors = [fixture.add_locked_investor(1_000_000)];
+    let second_pool = fixture.add_config();
+    let day_id = fixture.svm.day_id();
+    fixture.accrue_fees(DAILY_FEES, 0);
+    fixture.crank(&investors).unwrap();
+    fixture.svm.warp_days(1);
+    fixture.finalize(day_id).unwrap();
+    let progress = progress_pda(&fixture.config, day_id);
+
+    // The second config has no retention period left, yet it cannot close the first config's day
+    fixture.use_config(second_pool);
+    fixture
+        .update_config(ConfigUpdateParams {
+            progress_retention_days: Some(0),
+            ..Default::default()
+        })
+        .unwrap();
+    let instruction = Instruction {
+        program_id: DISTRIBUTOR,
+        accounts: damm_v2_fee_distributor::accounts::CloseProgress {
+            config: fixture.config,
+            protocol_config: fixture.protocol_config,
+            progress,
+            payer: fixture.cranker,
+            progress_history: Some(fixture.progress_history()),
+        }
+        .to_account_metas(None),
+        data: damm_v2_fee_distributor::instruction::CloseProgress { day_id }.data(),
+    };
+    assert_error(fixture.svm.process(instruction, &[]), anchor_lang::error::ErrorCode::ConstraintSeeds);
+    assert!(fixture.svm.account(&progress).is_some());
+}
+
+#[test]
+fn emergency_withdraws_only_the_configs_own_treasury() {
+    let mut fixture = Fixture::new();
+    let investors = [fixture.add_locked_investor(1_000_000)];
+    let second_pool = fixture.add_config();
+    fixture.fund_treasury(300_000);
+    let first_pool = fixture.use_config(second_pool);
+    fixture.fund_treasury(DAILY_FEES);
+    let second_treasury = fixture.program_quote_treasury;
+
+    // Both configs share the quote mint, but each has its own treasury
+    fixture.use_config(first_pool);
+    assert_ne!(fixture.program_quote_treasury, second_treasury);
+    let recovery_ata = fixture.svm.create_ata(&fixture.quote_mint, &Pubkey::new_unique());
+    fixture
+        .update_config(ConfigUpdateParams {
+            recovery_ata: Some(recovery_ata),
+            ..Default::default()
+        })
+        .unwrap();
+    fixture.set_config_paused(fixture.authority, PAUSE_ALL).unwrap();
+    fixture.svm.warp_days(3);
+    fixture.emergency_withdraw(recovery_ata).unwrap();
+
+    assert_eq!(fixture.quote_balance(&recovery_ata), 300_000);
+    assert_eq!(fixture.quote_balance(&fixture.program_quote_treasury), 0);
+    assert_eq!(fixture.quote_balance(&second_treasury), DAILY_FEES);
+
+    // The second config still pays its investors out of its own claim
+    fixture.use_config(second_pool);
+    fixture.accrue_fees(DAILY_FEES, 0);
+    fixture.crank(&investors).unwrap();
+    assert_eq!(fixture.quote_balance(&investors[0].quote_ata), 500_000);
+}
+
+#[test]
+fn keeps_each_configs_day_separate() {
+    let mut fixture = Fixture::new();
+    let investors = [fixture.add_locked_investor(1_000_000)];
+    let second_pool = fixture.add_config();
+    fixture.accrue_fees(DAILY_FEES, 0);
+    let day_id = fixture.svm.day_id();
+
+    fixture.crank(&investors).unwrap();
+    let first_pool = fixture.mock_pool;
+
+    // The second config claims and closes its own day, untouched by the first config's
+    fixture.use_config(second_pool);
+    fixture.accrue_fees(DAILY_FEES, 0);
+    fixture.crank(&investors).unwrap();
+    assert_eq!(fixture.svm.events::<QuoteFeesClaimed>().len(), 1);
+    fixture.svm.warp_days(1);
+    fixture.finalize(day_id).unwrap();
+    let second_config = fixture.config;
+    fixture.use_config(first_pool);
+    fixture.finalize(day_id).unwrap();
+
+    for config in [fixture.config, second_config] {
+        let summary: DaySummary = fixture.svm.anchor_account(&day_summary_pda(&config, day_id));
+        assert_eq!(summary.claimed_quote, DAILY_FEES);
+        assert_eq!(summary.distributed_to_investors, 500_000);
+    }
+    assert_eq!(fixture.quote_balance(&investors[0].quote_ata), 1_000_000);
+}
+
+#[test]
+fn claims_and_swaps_fees_from_mock_cp_amm() {
+    let mut fixture = Fixture::new();
+    let accounts = fixture.enable_swap_to_quote();
+    let investors = [fixture.add_locked_investor(1_000_000)];
+    fixture.accrue_fees(300_000, 100_000);
+    // Quote liquidity for the swap, on top of the fees the claim drains
+    let quote_vault = fixture.mock_pool.quote_vault;
+    fixture.svm.mint_to(&quote_vault, 100_000);
+
+    let instruction =
+        fixture.crank_ix(accounts, &investors, CrankArgs::default(), Vec::new());
+    fixture.svm.process(instruction, &[fixture.cranker]).unwrap();
+
+    let claimed = fixture.svm.events::<QuoteFeesClaimed>();
+    assert_eq!(claimed[0].claimed_base, 100_000);
+    assert_eq!(claimed[0].swapped_quote, 100_000);
+    assert_eq!(claimed[0].claimed_quote, 400_000);
+    assert_eq!(claimed[0].position_claims[0].claimed_quote, 300_000);
+    assert_eq!(fixture.quote_balance(&investors[0].quote_ata), 200_000);
+    assert_eq!(fixture.quote_balance(&fixture.program_quote_treasury), 200_000);
+    assert_eq!(fixture.svm.token_balance(&fixture.program_base_treasury), 0);
+    assert_eq!(fixture.svm.token_balance(&fixture.mock_pool.quote_vault), 0);
+}
+
+#[test]
+fn swap_floor_follows_the_oracle_not_the_pool() {
+    // Pushing the pool's price down ahead of the crank can't drag the floor down with it
+    let mut fixture = Fixture::new();
+    let accounts = fixture.enable_swap_to_quote();
+    let quote_vault = fixture.mock_pool.quote_vault;
+    fixture.svm.mint_to(&quote_vault, 1_000_000);
+    fixture.accrue_fees(100_000, 100_000);
+    fixture.set_pool_sqrt_price(SQRT_PRICE_ONE / 2);
+    let instruction = fixture.crank_ix(accounts, &[], CrankArgs::default(), Vec::new());
+    assert_error(
+        fixture.svm.process(instruction, &[fixture.cranker]),
+        mock_cp_amm::MockCpAmmError::SlippageExceeded,
+    );
+}
+
+#[test]
+fn decommission_pays_out_the_final_claim_and_sweeps_only_its_own_treasury() {
+    let mut fixture = Fixture::new();
+    let quote_sweep_recipient = fixture.svm.create_ata(&fixture.quote_mint, &Pubkey::new_unique());
+    let base_sweep_recipient = fixture.svm.create_ata(&fixture.base_mint, &Pubkey::new_unique());
+    fixture
+        .update_config(ConfigUpdateParams {
+            quote_sweep_recipient: Some(quote_sweep_recipient),
+            base_sweep_recipient: Some(base_sweep_recipient),
+            ..Default::default()
+        })
+        .unwrap();
+    let investors = [fixture.add_locked_investor(1_000_000)];
+    let second_pool = fixture.add_config();
+    let first_pool = fixture.use_config(second_pool);
+    fixture.fund_treasury(DAILY_FEES);
+    let second_treasury = fixture.program_quote_treasury;
+    fixture.use_config(first_pool);
+
+    fixture.request_decommission().unwrap();
+    fixture.svm.warp_days(3);
+    fixture.add_liquidity(50_000, 20_000);
+    fixture.accrue_fees(200_000, 10_000);
+    let day_id = fixture.svm.day_id();
+    let accounts = damm_v2_fee_distributor::accounts::Decommission {
+        cp_amm_program: Some(mock_cp_amm::ID),
+        ..fixture.decommission_accounts(base_sweep_recipient)
+    };
+    let instruction = fixture.decommission_ix(accounts, Vec::new());
+    fixture.svm.process(instruction, &[fixture.authority]).unwrap();
+
+    // Fees are claimed as today's, liquidity removed and the base treasury swept
+    let decommissioned = &fixture.svm.events::<ConfigDecommissioned>()[0];
+    assert_eq!((decommissioned.claimed_quote, decommissioned.claimed_base), (200_000, 10_000));
+    assert_eq!((decommissioned.removed_quote, decommissioned.removed_base), (50_000, 20_000));
+    assert_eq!((decommissioned.positions_closed, decommissioned.base_swept), (1, 30_000));
+    assert!(fixture.svm.account(&fixture.mock_pool.position).is_none());
+    assert_eq!(fixture.svm.token_balance(&base_sweep_recipient), 30_000);
+    assert_eq!(fixture.quote_balance(&fixture.program_quote_treasury), 250_000);
+
+    // The final claim is paid out like any other day's, then the rest of the treasury is swept
+    fixture.crank(&investors).unwrap();
+    fixture.svm.warp_days(1);
+    let accounts = damm_v2_fee_distributor::accounts::FinalizeDay {
+        quote_sweep_recipient: Some(quote_sweep_recipient),
+        ..fixture.finalize_accounts(day_id)
+    };
+    let instruction = fixture.finalize_ix(accounts, day_id, Vec::new());
+    fixture.svm.process(instruction, &[fixture.cranker]).unwrap();
+
+    assert_eq!(fixture.quote_balance(&investors[0].quote_ata), 100_000);
+    assert_eq!(fixture.quote_balance(&fixture.creator_quote_ata), 100_000);
+    assert_eq!(fixture.quote_balance(&quote_sweep_recipient), 50_000);
+    assert_eq!(fixture.svm.events::<CreatorPayoutDayClosed>()[0].quote_swept, 50_000);
+    assert_eq!(fixture.quote_balance(&fixture.program_quote_treasury), 0);
+    assert_eq!(fixture.quote_balance(&second_treasury), DAILY_FEES);
+    assert_eq!(fixture.config_account().open_days, 0);
+}
+
+#[test]
+fn withholds_token_2022_transfer_fees_per_claimed_position() {
+    // 1% transfer fee, at most 500 per transfer
+    let mut fixture = Fixture::with_quote_mint(|svm, mint, authority| {
+        svm.create_mint_with_extensions(
+            mint,
+            authority,
+            6,
+            &spl_token_2022::ID,
+            &[ExtensionType::TransferFeeConfig],
+            vec![spl_token_2022::extension::transfer_fee::instruction::initialize_transfer_fee_config(
+                &spl_token_2022::ID,
+                &mint,
+                Some(authority),
+                Some(authority),
+                100,
+                500,
+            )
+            .unwrap()],
+        )
+    });
+    let second_position = fixture.create_position();
+    fixture.add_honorary_position(fixture.authority, second_position).unwrap();
+    fixture.accrue_fees(100_000, 0);
+    fixture.accrue_fees_to(second_position, 100_000, 0);
+    // Top the vault back up for the fee withheld on each deposit
+    let quote_vault = fixture.mock_pool.quote_vault;
+    fixture.svm.mint_to(&quote_vault, 1_000);
+
+    let extra_accounts = vec![AccountMeta::new(second_position, false)];
+    let instruction = fixture.crank_ix(fixture.crank_accounts(), &[], CrankArgs::default(), extra_accounts);
+    fixture.svm.process(instruction, &[fixture.cranker]).unwrap();
+
+    // Each claim pays the capped 500 fee, rather than one 500 fee on the summed claim
+    let claimed = fixture.svm.events::<QuoteFeesClaimed>();
+    assert_eq!(claimed[0].position_claims[0].claimed_quote, 99_500);
+    assert_eq!(claimed[0].position_claims[1].claimed_quote, 99_500);
+    assert_eq!(claimed[0].claimed_quote, 199_000);
+    assert_eq!(fixture.quote_balance(&fixture.program_quote_treasury), 199_000);
+}
+
+#[test]
+fn pays_out_through_a_transfer_hook() {
+    let mut fixture = Fixture::with_quote_mint(|svm, mint, authority| {
+        svm.create_mint_with_extensions(
+            mint,
+            authority,
+            6,
+            &spl_token_2022::ID,
+            &[ExtensionType::TransferHook],
+            vec![spl_token_2022::extension::transfer_hook::instruction::initialize(
+                &spl_token_2022::ID,
+                &mint,
+                Some(*authority),
+                None,
+            )
+            .unwrap()],
+        )
+    });
+    let investor = fixture.add_locked_investor(1_000_000);
+    fixture.accrue_fees(DAILY_FEES, 0);
+    fixture
+        .update_config(ConfigUpdateParams {
+            crank_tip_bps: Some(100),
+            crank_tip_max: Some(1_000),
+            ..Default::default()
+        })
+        .unwrap();
+    let crank_accounts = |fixture: &Fixture| damm_v2_fee_distributor::accounts::CrankDistributePage {
+        cranker_quote_ata: Some(fixture.cranker_quote_ata),
+        ..fixture.crank_accounts()
+    };
+
+    // cp-amm's transfers carry no hook accounts, so the hook is set only once the fees are claimed,
+    // on a page of its own; the tally lets the investor's later page pay against the day's total
+    fixture.tally(&[investor]).unwrap();
+    let instruction = fixture.crank_ix(crank_accounts(&fixture), &[], CrankArgs::default(), Vec::new());
+    fixture.svm.process(instruction, &[fixture.cranker]).unwrap();
+    let tip = fixture.quote_balance(&fixture.cranker_quote_ata);
+    let quote_mint = fixture.quote_mint;
+    let instruction = spl_token_2022::extension::transfer_hook::instruction::update(
+        &spl_token_2022::ID,
+        &quote_mint,
+        &fixture.authority,
+        &[],
+        Some(mock_transfer_hook::ID),
+    )
+    .unwrap();
+    fixture.svm.process(instruction, &[fixture.authority]).unwrap();
+    let hook_accounts = fixture.initialize_transfer_hook();
+    let missing_hook = Err(TransferHookError::IncorrectAccount.into());
+
+    // The investor's payout can't resolve the hook without its accounts
+    let instruction = fixture.crank_ix(crank_accounts(&fixture), &[investor], CrankArgs::default(), Vec::new());
+    assert_eq!(fixture.svm.process(instruction, &[fixture.cranker]), missing_hook);
+    let instruction = fixture.crank_ix(crank_accounts(&fixture), &[investor], CrankArgs::default(), hook_accounts.clone());
+    fixture.svm.process(instruction, &[fixture.cranker]).unwrap();
+
+    // Nor can the creator's payout at finalize
+    let day_id = fixture.svm.day_id();
+    fixture.svm.warp_to_day(day_id + 1, 0);
+    let instruction = fixture.finalize_ix(fixture.finalize_accounts(day_id), day_id, Vec::new());
+    assert_eq!(fixture.svm.process(instruction, &[fixture.cranker]), missing_hook);
+    let instruction = fixture.finalize_ix(fixture.finalize_accounts(day_id), day_id, hook_accounts);
+    fixture.svm.process(instruction, &[fixture.cranker]).unwrap();
+
+    // The hook saw the investor's payout and the creator's payout
+    let investor_payout = fixture.quote_balance(&investor.quote_ata);
+    let creator_payout = fixture.quote_balance(&fixture.creator_quote_ata);
+    assert!(tip > 0 && investor_payout > 0 && creator_payout > 0);
+    let counter: mock_transfer_hook::Counter =
+        fixture.svm.anchor_account(&pda(&[mock_transfer_hook::COUNTER_SEED], &mock_transfer_hook::ID));
+    assert_eq!(counter.transfers, 2);
+    assert_eq!(counter.amount, investor_payout + creator_payout);
+    assert_eq!(counter.amount + tip, DAILY_FEES);
+}
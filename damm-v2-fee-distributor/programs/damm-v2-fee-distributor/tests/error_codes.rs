@@ -0,0 +1,848 @@
+//! One test per error code the distributor can raise
+//!
+//! `InvalidPoolForQuoteOnly`, `AlreadyProcessedPage` and
+//! `InvalidStreamflowProgram` are declared but never raised. `DayGateNotPassed`
+//! cannot fire either: the progress PDA is derived from the day and cannot be
+//! closed on it, so a fresh progress account is always a day not yet claimed.
+
+// `solana_program`'s system program and loader ids are deprecated in newer Solana crates
+#![allow(deprecated)]
+
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_2022::spl_token_2022::extension::ExtensionType;
+use common::*;
+use damm_v2_fee_distributor::cap_window::MAX_CAP_WINDOW_DAYS;
+use damm_v2_fee_distributor::cp_amm::BaseFeePolicy;
+use damm_v2_fee_distributor::{
+    ConfigUpdateParams, ErrorCode, FeeSharePoint, FeeShareSchedule, ProtocolConfigUpdateParams, RemainderRecipient,
+    ProtocolConfig, RemainderSplit, RollingCap, RollingCaps, MAX_HONORARY_POSITIONS, MAX_PROTOCOL_FEE_BPS, PAUSE_ALL,
+    PAUSE_CLAIM, PAUSE_CREATOR_PAYOUTS, PROTOCOL_CONFIG_SEED,
+};
+
+/// `instruction` without `key` among its accounts
+fn without(mut instruction: Instruction, key: &Pubkey) -> Instruction {
+    instruction.accounts.retain(|meta| meta.pubkey != *key);
+    instruction
+}
+
+/// A fixture with one fully locked investor and a day's fees accrued to the position
+fn funded_fixture() -> (Fixture, Investor) {
+    let mut fixture = Fixture::new();
+    let investor = fixture.add_locked_investor(1_000_000);
+    fixture.accrue_fees(DAILY_FEES, 0);
+    (fixture, investor)
+}
+
+/// `funded_fixture` with a per-investor cap, so payouts need a tally
+fn capped_fixture() -> (Fixture, Investor) {
+    let (mut fixture, investor) = funded_fixture();
+    fixture
+        .update_config(ConfigUpdateParams {
+            max_investor_share_bps: Some(5_000),
+            ..Default::default()
+        })
+        .unwrap();
+    (fixture, investor)
+}
+
+/// A fixture with a USD daily cap priced by a mock-oracle feed
+fn priced_fixture(price: i64, conf: u64, expo: i32, publish_age: i64) -> (Fixture, Investor, Pubkey) {
+    let (mut fixture, investor) = funded_fixture();
+    let publish_time = fixture.svm.now() - publish_age;
+    let price_feed = fixture.create_price_feed(price, conf, expo, publish_time);
+    fixture
+        .update_config(ConfigUpdateParams {
+            usd_daily_cap: Some(1_000_000),
+            price_feed: Some(price_feed),
+            oracle_program: Some(mock_oracle::ID),
+            ..Default::default()
+        })
+        .unwrap();
+    (fixture, investor, price_feed)
+}
+
+fn crank_with_price_feed(fixture: &mut Fixture, investor: Investor, price_feed: Pubkey) -> ProgramResult {
+    let accounts = damm_v2_fee_distributor::accounts::CrankDistributePage {
+        price_feed: Some(price_feed),
+        ..fixture.crank_accounts()
+    };
+    let instruction = fixture.crank_ix(accounts, &[investor], CrankArgs::default(), Vec::new());
+    fixture.svm.process(instruction, &[fixture.cranker])
+}
+
+/// Crank through mock cp-amm after the position accrued `quote` and `base` fees
+fn cp_amm_crank(fixture: &mut Fixture, quote: u64, base: u64) -> ProgramResult {
+    fixture.accrue_fees(quote, base);
+    let instruction = fixture.crank_ix(fixture.crank_accounts(), &[], CrankArgs::default(), Vec::new());
+    fixture.svm.process(instruction, &[fixture.cranker])
+}
+
+/// Register a recovery account and fully pause the config
+fn paused_fixture() -> (Fixture, Pubkey) {
+    let mut fixture = Fixture::new();
+    let recovery_ata = fixture.svm.create_ata(&fixture.quote_mint, &Pubkey::new_unique());
+    fixture
+        .update_config(ConfigUpdateParams {
+            recovery_ata: Some(recovery_ata),
+            ..Default::default()
+        })
+        .unwrap();
+    fixture.set_config_paused(fixture.authority, PAUSE_ALL).unwrap();
+    (fixture, recovery_ata)
+}
+
+/// Register sweep recipients, let the decommission notice run out and return decommission
+/// accounts sweeping to them
+fn decommission_fixture() -> (Fixture, damm_v2_fee_distributor::accounts::Decommission) {
+    let mut fixture = Fixture::new();
+    let quote_sweep_recipient = fixture.svm.create_ata(&fixture.quote_mint, &fixture.creator);
+    let base_sweep_recipient = fixture.svm.create_ata(&fixture.base_mint, &fixture.creator);
+    fixture
+        .update_config(ConfigUpdateParams {
+            quote_sweep_recipient: Some(quote_sweep_recipient),
+            base_sweep_recipient: Some(base_sweep_recipient),
+            ..Default::default()
+        })
+        .unwrap();
+    fixture.request_decommission().unwrap();
+    fixture.svm.warp_days(3);
+    let accounts = fixture.decommission_accounts(base_sweep_recipient);
+    (fixture, accounts)
+}
+
+#[test]
+fn invalid_tick_range() {
+    let mut fixture = Fixture::new();
+    let instruction =
+        fixture.initialize_config_ix(Pubkey::new_unique(), Pubkey::new_unique(), fixture.quote_mint, 10, 10);
+    assert_error(fixture.svm.process(instruction, &[fixture.authority]), ErrorCode::InvalidTickRange);
+}
+
+#[test]
+fn base_fee_detected() {
+    let mut fixture = Fixture::new();
+    assert_error(cp_amm_crank(&mut fixture, 100_000, 10), ErrorCode::BaseFeeDetected);
+}
+
+#[test]
+fn missing_investor_ata() {
+    let (mut fixture, investor) = funded_fixture();
+    let instruction = fixture.crank_ix(fixture.crank_accounts(), &[investor], CrankArgs::default(), Vec::new());
+    let result = fixture.svm.process(without(instruction, &investor.quote_ata), &[fixture.cranker]);
+    assert_error(result, ErrorCode::MissingInvestorATA);
+}
+
+#[test]
+fn insufficient_claimed_quote() {
+    let mut fixture = Fixture::new();
+    assert_error(cp_amm_crank(&mut fixture, 0, 0), ErrorCode::InsufficientClaimedQuote);
+}
+
+#[test]
+fn stream_account_not_found() {
+    let (mut fixture, investor) = funded_fixture();
+    let instruction = fixture.crank_ix(fixture.crank_accounts(), &[investor], CrankArgs::default(), Vec::new());
+    let result = fixture.svm.process(without(instruction, &investor.stream), &[fixture.cranker]);
+    assert_error(result, ErrorCode::StreamAccountNotFound);
+}
+
+#[test]
+fn math_overflow() {
+    // Converting the USD cap at an exponent of -40 overflows 10^40 in u128
+    let (mut fixture, investor, price_feed) = priced_fixture(1, 0, -40, 0);
+    assert_error(crank_with_price_feed(&mut fixture, investor, price_feed), ErrorCode::MathOverflow);
+}
+
+#[test]
+fn invalid_progress_account() {
+    let (mut fixture, investor) = funded_fixture();
+    let day_id = fixture.svm.day_id();
+    assert_error(fixture.finalize(day_id), ErrorCode::InvalidProgressAccount);
+
+    let accounts = damm_v2_fee_distributor::accounts::CrankDistributePage {
+        progress: progress_pda(&fixture.config, day_id + 1),
+        ..fixture.crank_accounts()
+    };
+    let instruction = fixture.crank_ix(accounts, &[investor], CrankArgs::default(), Vec::new());
+    assert_error(fixture.svm.process(instruction, &[fixture.cranker]), ErrorCode::InvalidProgressAccount);
+}
+
+#[test]
+fn day_already_finalized() {
+    // A fully paid tally lets the day close while it is still today
+    let (mut fixture, investor) = funded_fixture();
+    fixture.tally(&[investor]).unwrap();
+    fixture.crank(&[investor]).unwrap();
+    fixture.finalize(fixture.svm.day_id()).unwrap();
+    assert_error(fixture.crank(&[investor]), ErrorCode::DayAlreadyFinalized);
+}
+
+#[test]
+fn day_not_finalized() {
+    let (mut fixture, investor) = funded_fixture();
+    fixture.crank(&[investor]).unwrap();
+    let day_id = fixture.svm.day_id();
+    fixture.svm.warp_days(31);
+    assert_error(fixture.close_progress(day_id, fixture.cranker), ErrorCode::DayNotFinalized);
+}
+
+#[test]
+fn progress_retention_not_elapsed() {
+    let (mut fixture, investor) = funded_fixture();
+    fixture.crank(&[investor]).unwrap();
+    let day_id = fixture.svm.day_id();
+    fixture.svm.warp_days(1);
+    fixture.finalize(day_id).unwrap();
+    fixture.svm.warp_days(29);
+    assert_error(fixture.close_progress(day_id, fixture.cranker), ErrorCode::ProgressRetentionNotElapsed);
+}
+
+#[test]
+fn missing_progress_history() {
+    let (mut fixture, investor) = funded_fixture();
+    fixture.crank(&[investor]).unwrap();
+    let day_id = fixture.svm.day_id();
+    fixture.svm.warp_days(1);
+    fixture.finalize(day_id).unwrap();
+    fixture.svm.warp_days(30);
+    let instruction = fixture.close_progress_ix(day_id, fixture.cranker, None);
+    assert_error(fixture.svm.process(instruction, &[]), ErrorCode::MissingProgressHistory);
+}
+
+#[test]
+fn invalid_rent_recipient() {
+    let (mut fixture, investor) = funded_fixture();
+    fixture.crank(&[investor]).unwrap();
+    let day_id = fixture.svm.day_id();
+    fixture.svm.warp_days(1);
+    fixture.finalize(day_id).unwrap();
+    fixture.svm.warp_days(30);
+    assert_error(fixture.close_progress(day_id, fixture.authority), ErrorCode::InvalidRentRecipient);
+}
+
+#[test]
+fn unauthorized() {
+    let mut fixture = Fixture::new();
+    let intruder = Pubkey::new_unique();
+    let instruction = fixture.update_config_ix(intruder, ConfigUpdateParams::default());
+    assert_error(fixture.svm.process(instruction, &[intruder]), ErrorCode::Unauthorized);
+}
+
+#[test]
+fn invalid_basis_points() {
+    let mut fixture = Fixture::new();
+    let result = fixture.update_config(ConfigUpdateParams {
+        crank_tip_bps: Some(10_001),
+        ..Default::default()
+    });
+    assert_error(result, ErrorCode::InvalidBasisPoints);
+}
+
+#[test]
+fn missing_cranker_quote_ata() {
+    let (mut fixture, investor) = funded_fixture();
+    fixture
+        .update_config(ConfigUpdateParams {
+            crank_tip_bps: Some(100),
+            crank_tip_max: Some(1_000),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_error(fixture.crank(&[investor]), ErrorCode::MissingCrankerQuoteAta);
+}
+
+#[test]
+fn invalid_lock_program() {
+    let (mut fixture, investor) = funded_fixture();
+    let mut stream = fixture.svm.account(&investor.stream).unwrap().clone();
+    stream.owner = mock_oracle::ID;
+    fixture.svm.set_account(investor.stream, stream);
+    assert_error(fixture.crank(&[investor]), ErrorCode::InvalidLockProgram);
+}
+
+#[test]
+fn lock_program_not_set() {
+    let (mut fixture, investor) = funded_fixture();
+    fixture
+        .update_config(ConfigUpdateParams {
+            lock_program: Some(Pubkey::default()),
+            ..Default::default()
+        })
+        .unwrap();
+    let accounts = damm_v2_fee_distributor::accounts::CrankDistributePage {
+        lock_program: Pubkey::default(),
+        ..fixture.crank_accounts()
+    };
+    let instruction = fixture.crank_ix(accounts, &[investor], CrankArgs::default(), Vec::new());
+    assert_error(fixture.svm.process(instruction, &[fixture.cranker]), ErrorCode::LockProgramNotSet);
+}
+
+#[test]
+fn unsupported_mint_extension() {
+    let mut fixture = Fixture::new();
+    let mint = Pubkey::new_unique();
+    fixture.svm.create_mint_with_extensions(
+        mint,
+        &fixture.authority,
+        6,
+        &spl_token_2022::ID,
+        &[ExtensionType::NonTransferable],
+        vec![spl_token_2022::instruction::initialize_non_transferable_mint(&spl_token_2022::ID, &mint).unwrap()],
+    );
+    let instruction = fixture.initialize_config_ix(Pubkey::new_unique(), Pubkey::new_unique(), mint, -10, 10);
+    assert_error(
+        fixture.svm.process(instruction, &[fixture.authority]),
+        ErrorCode::UnsupportedMintExtension,
+    );
+}
+
+#[test]
+fn duplicate_honorary_position() {
+    let mut fixture = Fixture::new();
+    let position = fixture.mock_pool.position;
+    assert_error(
+        fixture.add_honorary_position(fixture.authority, position),
+        ErrorCode::DuplicateHonoraryPosition,
+    );
+}
+
+#[test]
+fn too_many_honorary_positions() {
+    let mut fixture = Fixture::new();
+    for _ in 1..MAX_HONORARY_POSITIONS {
+        let position = fixture.create_position();
+        fixture.add_honorary_position(fixture.authority, position).unwrap();
+    }
+    let position = fixture.create_position();
+    assert_error(
+        fixture.add_honorary_position(fixture.authority, position),
+        ErrorCode::TooManyHonoraryPositions,
+    );
+}
+
+#[test]
+fn missing_honorary_position() {
+    let (mut fixture, investor) = funded_fixture();
+    let position = fixture.create_position();
+    fixture.add_honorary_position(fixture.authority, position).unwrap();
+    assert_error(fixture.crank(&[investor]), ErrorCode::MissingHonoraryPosition);
+}
+
+#[test]
+fn invalid_remainder_split() {
+    let mut fixture = Fixture::new();
+    let result = fixture.update_config(ConfigUpdateParams {
+        remainder_split: Some(RemainderSplit {
+            recipients: vec![RemainderRecipient {
+                recipient: fixture.creator_quote_ata,
+                bps: 5_000,
+            }],
+        }),
+        ..Default::default()
+    });
+    assert_error(result, ErrorCode::InvalidRemainderSplit);
+}
+
+#[test]
+fn missing_remainder_recipient() {
+    let (mut fixture, investor) = funded_fixture();
+    let treasury_share = fixture.svm.create_ata(&fixture.quote_mint, &Pubkey::new_unique());
+    fixture
+        .update_config(ConfigUpdateParams {
+            remainder_split: Some(RemainderSplit {
+                recipients: vec![
+                    RemainderRecipient {
+                        recipient: fixture.creator_quote_ata,
+                        bps: 5_000,
+                    },
+                    RemainderRecipient {
+                        recipient: treasury_share,
+                        bps: 5_000,
+                    },
+                ],
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+    fixture.crank(&[investor]).unwrap();
+    let day_id = fixture.svm.day_id();
+    fixture.svm.warp_days(1);
+    assert_error(fixture.finalize(day_id), ErrorCode::MissingRemainderRecipient);
+}
+
+#[test]
+fn invalid_creator() {
+    let (mut fixture, investor) = funded_fixture();
+    fixture.crank(&[investor]).unwrap();
+    let day_id = fixture.svm.day_id();
+    fixture.svm.warp_days(1);
+    let intruder = Pubkey::new_unique();
+    let accounts = damm_v2_fee_distributor::accounts::FinalizeDay {
+        creator: intruder,
+        creator_quote_ata: fixture.svm.create_ata(&fixture.quote_mint, &intruder),
+        ..fixture.finalize_accounts(day_id)
+    };
+    let instruction = fixture.finalize_ix(accounts, day_id, Vec::new());
+    assert_error(fixture.svm.process(instruction, &[fixture.cranker]), ErrorCode::InvalidCreator);
+}
+
+#[test]
+fn day_not_over() {
+    // Without a tally more pages may still come, so the day stays open until it is over
+    let (mut fixture, investor) = funded_fixture();
+    fixture.crank(&[investor]).unwrap();
+    let day_id = fixture.svm.day_id();
+    assert_error(fixture.finalize(day_id), ErrorCode::DayNotOver);
+
+    fixture.svm.warp_to_day(day_id + 1, 0);
+    fixture.finalize(day_id).unwrap();
+}
+
+#[test]
+fn invalid_honorary_position() {
+    let mut fixture = Fixture::new();
+    fixture.accrue_fees(100_000, 0);
+    // Hand the position to another owner; it sits after the discriminator and the pool
+    let mut position = fixture.svm.account(&fixture.mock_pool.position).unwrap().clone();
+    position.data[40..72].copy_from_slice(Pubkey::new_unique().as_ref());
+    fixture.svm.set_account(fixture.mock_pool.position, position);
+    let instruction = fixture.crank_ix(fixture.crank_accounts(), &[], CrankArgs::default(), Vec::new());
+    assert_error(fixture.svm.process(instruction, &[fixture.cranker]), ErrorCode::InvalidHonoraryPosition);
+
+    // Nor can another config's position, in another pool, be registered
+    let other_pool = fixture.add_config();
+    assert_error(
+        fixture.add_honorary_position(fixture.authority, other_pool.position),
+        ErrorCode::InvalidHonoraryPosition,
+    );
+}
+
+#[test]
+fn protocol_fee_too_high() {
+    let mut fixture = Fixture::new();
+    let result = fixture.update_protocol_config(
+        fixture.authority,
+        ProtocolConfigUpdateParams {
+            protocol_fee_bps: Some(MAX_PROTOCOL_FEE_BPS + 1),
+            ..Default::default()
+        },
+    );
+    assert_error(result, ErrorCode::ProtocolFeeTooHigh);
+}
+
+#[test]
+fn not_protocol_admin() {
+    let mut fixture = Fixture::new();
+    let result = fixture.update_protocol_config(Pubkey::new_unique(), ProtocolConfigUpdateParams::default());
+    assert_error(result, ErrorCode::NotProtocolAdmin);
+}
+
+#[test]
+fn invalid_fee_share_schedule() {
+    let mut fixture = Fixture::new();
+    let result = fixture.update_config(ConfigUpdateParams {
+        fee_share_schedule: Some(FeeShareSchedule {
+            points: vec![FeeSharePoint {
+                offset_seconds: 0,
+                share_bps: 10_001,
+            }],
+        }),
+        ..Default::default()
+    });
+    assert_error(result, ErrorCode::InvalidFeeShareSchedule);
+}
+
+#[test]
+fn invalid_investor_cap() {
+    let mut fixture = Fixture::new();
+    let result = fixture.update_config(ConfigUpdateParams {
+        max_investor_share_bps: Some(100),
+        ..Default::default()
+    });
+    assert_error(result, ErrorCode::InvalidInvestorCap);
+}
+
+#[test]
+fn investor_tally_required() {
+    let (mut fixture, investor) = capped_fixture();
+    assert_error(fixture.crank(&[investor]), ErrorCode::InvestorTallyRequired);
+}
+
+#[test]
+fn investor_tally_closed() {
+    let (mut fixture, investor) = capped_fixture();
+    fixture.tally(&[investor]).unwrap();
+    fixture.crank(&[investor]).unwrap();
+    assert_error(fixture.tally(&[investor]), ErrorCode::InvestorTallyClosed);
+}
+
+#[test]
+fn missing_price_feed() {
+    let (mut fixture, investor, _) = priced_fixture(1_000_000, 0, -6, 0);
+    assert_error(fixture.crank(&[investor]), ErrorCode::MissingPriceFeed);
+}
+
+#[test]
+fn invalid_price_feed() {
+    let (mut fixture, investor, _) = priced_fixture(1_000_000, 0, -6, 0);
+    let impostor = fixture.create_price_feed(1_000_000, 0, -6, fixture.svm.now());
+    assert_error(crank_with_price_feed(&mut fixture, investor, impostor), ErrorCode::InvalidPriceFeed);
+}
+
+#[test]
+fn invalid_price() {
+    let (mut fixture, investor, price_feed) = priced_fixture(0, 0, -6, 0);
+    assert_error(crank_with_price_feed(&mut fixture, investor, price_feed), ErrorCode::InvalidPrice);
+}
+
+#[test]
+fn stale_price() {
+    let (mut fixture, investor, price_feed) = priced_fixture(1_000_000, 0, -6, 61);
+    assert_error(crank_with_price_feed(&mut fixture, investor, price_feed), ErrorCode::StalePrice);
+}
+
+#[test]
+fn price_confidence_too_wide() {
+    // 3% confidence against the default 2% limit
+    let (mut fixture, investor, price_feed) = priced_fixture(1_000_000, 30_000, -6, 0);
+    assert_error(
+        crank_with_price_feed(&mut fixture, investor, price_feed),
+        ErrorCode::PriceConfidenceTooWide,
+    );
+}
+
+#[test]
+fn invalid_rolling_caps() {
+    let mut fixture = Fixture::new();
+    let result = fixture.update_config(ConfigUpdateParams {
+        rolling_caps: Some(RollingCaps {
+            caps: vec![RollingCap {
+                window_days: MAX_CAP_WINDOW_DAYS as u16 + 1,
+                cap: 1,
+            }],
+        }),
+        ..Default::default()
+    });
+    assert_error(result, ErrorCode::InvalidRollingCaps);
+}
+
+#[test]
+fn missing_cap_window() {
+    let (mut fixture, investor) = funded_fixture();
+    fixture
+        .update_config(ConfigUpdateParams {
+            rolling_caps: Some(RollingCaps {
+                caps: vec![RollingCap { window_days: 7, cap: 100_000 }],
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_error(fixture.crank(&[investor]), ErrorCode::MissingCapWindow);
+
+    // Later pages must record into the window too
+    let cap_window = fixture.initialize_cap_window();
+    let other = fixture.add_locked_investor(1_000_000);
+    let accounts = damm_v2_fee_distributor::accounts::CrankDistributePage {
+        cap_window: Some(cap_window),
+        ..fixture.crank_accounts()
+    };
+    let instruction = fixture.crank_ix(accounts, &[investor], CrankArgs::default(), Vec::new());
+    fixture.svm.process(instruction, &[fixture.cranker]).unwrap();
+    assert_error(fixture.crank(&[other]), ErrorCode::MissingCapWindow);
+}
+
+#[test]
+fn paused() {
+    let (mut fixture, investor) = funded_fixture();
+    fixture.set_protocol_paused(fixture.authority, PAUSE_CLAIM).unwrap();
+    assert_error(fixture.crank(&[investor]), ErrorCode::Paused);
+}
+
+#[test]
+fn paused_blocks_withdrawals_decommission_and_progress_closing() {
+    let (mut fixture, accounts) = decommission_fixture();
+    let investor = fixture.add_locked_investor(1_000_000);
+    fixture.accrue_fees(DAILY_FEES, 0);
+    fixture.crank(&[investor]).unwrap();
+    let day_id = fixture.svm.day_id();
+    fixture.svm.warp_days(1);
+    fixture.finalize(day_id).unwrap();
+    fixture.svm.mint_to(&fixture.protocol_fee_vault.clone(), 1_000);
+
+    // Any protocol pause bit stops protocol fee withdrawals
+    fixture.set_protocol_paused(fixture.authority, PAUSE_CREATOR_PAYOUTS).unwrap();
+    assert_error(fixture.withdraw_protocol_fees(fixture.creator_quote_ata, 1_000), ErrorCode::Paused);
+    fixture.set_protocol_paused(fixture.authority, 0).unwrap();
+
+    // Any config pause bit stops decommissioning and closing old progress
+    fixture.set_config_paused(fixture.authority, PAUSE_CLAIM).unwrap();
+    let instruction =
+        fixture.decommission_ix(fixture.decommission_accounts(accounts.base_sweep_recipient), Vec::new());
+    assert_error(fixture.svm.process(instruction, &[fixture.authority]), ErrorCode::Paused);
+    fixture.svm.warp_to_day(day_id + 31, 0);
+    assert_error(fixture.close_progress(day_id, fixture.cranker), ErrorCode::Paused);
+
+    fixture.set_config_paused(fixture.authority, 0).unwrap();
+    fixture.close_progress(day_id, fixture.cranker).unwrap();
+    fixture.withdraw_protocol_fees(fixture.creator_quote_ata, 1_000).unwrap();
+}
+
+#[test]
+fn not_guardian() {
+    let mut fixture = Fixture::new();
+    assert_error(fixture.set_config_paused(Pubkey::new_unique(), PAUSE_ALL), ErrorCode::NotGuardian);
+}
+
+#[test]
+fn invalid_pause_flags() {
+    let mut fixture = Fixture::new();
+    assert_error(fixture.set_config_paused(fixture.authority, PAUSE_ALL + 1), ErrorCode::InvalidPauseFlags);
+}
+
+#[test]
+fn not_paused() {
+    let (mut fixture, recovery_ata) = paused_fixture();
+    fixture.set_config_paused(fixture.authority, PAUSE_CLAIM).unwrap();
+    assert_error(fixture.emergency_withdraw(recovery_ata), ErrorCode::NotPaused);
+}
+
+#[test]
+fn emergency_timelock_not_elapsed() {
+    let (mut fixture, recovery_ata) = paused_fixture();
+    fixture.svm.warp_days(2);
+    assert_error(fixture.emergency_withdraw(recovery_ata), ErrorCode::EmergencyTimelockNotElapsed);
+}
+
+#[test]
+fn invalid_recovery_account() {
+    let (mut fixture, _) = paused_fixture();
+    fixture.svm.warp_days(3);
+    let creator_quote_ata = fixture.creator_quote_ata;
+    assert_error(fixture.emergency_withdraw(creator_quote_ata), ErrorCode::InvalidRecoveryAccount);
+}
+
+#[test]
+fn config_closed() {
+    let (mut fixture, accounts) = decommission_fixture();
+    fixture.accrue_fees(DAILY_FEES, 0);
+    let instruction = fixture.decommission_ix(accounts, Vec::new());
+    fixture.svm.process(instruction, &[fixture.authority]).unwrap();
+    assert!(fixture.config_account().closed);
+    assert_error(fixture.request_decommission(), ErrorCode::ConfigClosed);
+
+    // The final claim's day is still paid out, but no later day is claimed
+    let investor = fixture.add_locked_investor(1_000_000);
+    fixture.crank(&[investor]).unwrap();
+    fixture.svm.warp_days(1);
+    assert_error(fixture.crank(&[investor]), ErrorCode::ConfigClosed);
+}
+
+#[test]
+fn decommission_notice_not_elapsed() {
+    let mut fixture = Fixture::new();
+    let base_sweep_recipient = fixture.svm.create_ata(&fixture.base_mint, &fixture.creator);
+    fixture
+        .update_config(ConfigUpdateParams {
+            base_sweep_recipient: Some(base_sweep_recipient),
+            ..Default::default()
+        })
+        .unwrap();
+    let accounts = fixture.decommission_accounts(base_sweep_recipient);
+    let instruction = fixture.decommission_ix(accounts, Vec::new());
+    assert_error(
+        fixture.svm.process(instruction, &[fixture.authority]),
+        ErrorCode::DecommissionNoticeNotElapsed,
+    );
+
+    fixture.request_decommission().unwrap();
+    fixture.svm.warp_days(2);
+    let accounts = fixture.decommission_accounts(base_sweep_recipient);
+    let instruction = fixture.decommission_ix(accounts, Vec::new());
+    assert_error(
+        fixture.svm.process(instruction, &[fixture.authority]),
+        ErrorCode::DecommissionNoticeNotElapsed,
+    );
+}
+
+#[test]
+fn decommission_with_an_unfinalized_day() {
+    let (mut fixture, accounts) = decommission_fixture();
+    let investor = fixture.add_locked_investor(1_000_000);
+    fixture.accrue_fees(DAILY_FEES, 0);
+    fixture.crank(&[investor]).unwrap();
+
+    // An earlier claimed day still open blocks decommissioning
+    fixture.svm.warp_days(1);
+    let instruction =
+        fixture.decommission_ix(fixture.decommission_accounts(accounts.base_sweep_recipient), Vec::new());
+    assert_error(fixture.svm.process(instruction, &[fixture.authority]), ErrorCode::DayNotFinalized);
+}
+
+#[test]
+fn day_already_claimed() {
+    let (mut fixture, accounts) = decommission_fixture();
+    let investor = fixture.add_locked_investor(1_000_000);
+    fixture.accrue_fees(DAILY_FEES, 0);
+    fixture.tally(&[investor]).unwrap();
+    fixture.crank(&[investor]).unwrap();
+    fixture.finalize(fixture.svm.day_id()).unwrap();
+
+    let instruction = fixture.decommission_ix(accounts, Vec::new());
+    assert_error(fixture.svm.process(instruction, &[fixture.authority]), ErrorCode::DayAlreadyClaimed);
+}
+
+#[test]
+fn invalid_sweep_recipient() {
+    let (mut fixture, accounts) = decommission_fixture();
+    let accounts = damm_v2_fee_distributor::accounts::Decommission {
+        base_sweep_recipient: fixture.svm.create_ata(&fixture.base_mint, &fixture.cranker),
+        ..accounts
+    };
+    let instruction = fixture.decommission_ix(accounts, Vec::new());
+    assert_error(
+        fixture.svm.process(instruction, &[fixture.authority]),
+        ErrorCode::InvalidSweepRecipient,
+    );
+}
+
+#[test]
+fn missing_base_fee_accounts() {
+    let mut fixture = Fixture::new();
+    fixture
+        .update_config(ConfigUpdateParams {
+            base_fee_policy: Some(BaseFeePolicy::RouteToCreator),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_error(cp_amm_crank(&mut fixture, 100_000, 10), ErrorCode::MissingBaseFeeAccounts);
+}
+
+#[test]
+fn slippage_exceeded() {
+    // A 5% transfer fee on quote means the swap lands less in the treasury than the pool quoted
+    let mut fixture = Fixture::with_quote_mint(|svm, mint, authority| {
+        svm.create_mint_with_extensions(
+            mint,
+            authority,
+            6,
+            &spl_token_2022::ID,
+            &[ExtensionType::TransferFeeConfig],
+            vec![spl_token_2022::extension::transfer_fee::instruction::initialize_transfer_fee_config(
+                &spl_token_2022::ID,
+                &mint,
+                Some(authority),
+                Some(authority),
+                500,
+                u64::MAX,
+            )
+            .unwrap()],
+        )
+    });
+    let accounts = fixture.enable_swap_to_quote();
+    // Enough quote in the pool to pay the claim and the swap after the fee withheld on deposit
+    let quote_vault = fixture.mock_pool.quote_vault;
+    fixture.svm.mint_to(&quote_vault, 1_000_000);
+    fixture.accrue_fees(100_000, 100_000);
+    let instruction = fixture.crank_ix(accounts, &[], CrankArgs::default(), Vec::new());
+    assert_error(fixture.svm.process(instruction, &[fixture.cranker]), ErrorCode::SlippageExceeded);
+}
+
+#[test]
+fn missing_price_feed_for_swap() {
+    let mut fixture = Fixture::new();
+    let accounts = damm_v2_fee_distributor::accounts::CrankDistributePage {
+        base_price_feed: None,
+        ..fixture.enable_swap_to_quote()
+    };
+    fixture.accrue_fees(100_000, 100_000);
+    let instruction = fixture.crank_ix(accounts, &[], CrankArgs::default(), Vec::new());
+    assert_error(fixture.svm.process(instruction, &[fixture.cranker]), ErrorCode::MissingPriceFeed);
+}
+
+#[test]
+fn missing_cp_amm_accounts() {
+    let (mut fixture, investor) = funded_fixture();
+    let instruction = fixture.crank_ix(fixture.crank_accounts(), &[investor], CrankArgs::default(), Vec::new());
+    let result = fixture.svm.process(without(instruction, &fixture.mock_pool.pool_authority), &[fixture.cranker]);
+    assert_error(result, ErrorCode::MissingCpAmmAccounts);
+
+    // Nothing is claimed until the authority sets a cp-amm program
+    fixture
+        .update_config(ConfigUpdateParams {
+            cp_amm_program: Some(Pubkey::default()),
+            ..Default::default()
+        })
+        .unwrap();
+    let accounts = damm_v2_fee_distributor::accounts::CrankDistributePage {
+        cp_amm_program: None,
+        ..fixture.crank_accounts()
+    };
+    let instruction = fixture.crank_ix(accounts, &[investor], CrankArgs::default(), Vec::new());
+    assert_error(fixture.svm.process(instruction, &[fixture.cranker]), ErrorCode::MissingCpAmmAccounts);
+}
+
+#[test]
+fn invalid_cp_amm_account() {
+    let mut fixture = Fixture::new();
+    let accounts = damm_v2_fee_distributor::accounts::CrankDistributePage {
+        cp_amm_program: Some(mock_oracle::ID),
+        ..fixture.crank_accounts()
+    };
+    let instruction = fixture.crank_ix(accounts, &[], CrankArgs::default(), Vec::new());
+    assert_error(fixture.svm.process(instruction, &[fixture.cranker]), ErrorCode::InvalidCpAmmAccount);
+}
+
+#[test]
+fn not_upgrade_authority() {
+    let mut svm = Svm::new();
+    svm.add_program(DISTRIBUTOR, damm_v2_fee_distributor::entry);
+    let upgrade_authority = Pubkey::new_unique();
+    let intruder = Pubkey::new_unique();
+    for wallet in [&upgrade_authority, &intruder] {
+        svm.airdrop(wallet, 1_000_000_000);
+    }
+    let program_data = svm.set_upgrade_authority(DISTRIBUTOR, Some(upgrade_authority));
+
+    let instruction = initialize_protocol_config_ix(intruder, program_data, 0);
+    assert_error(svm.process(instruction, &[intruder]), ErrorCode::NotUpgradeAuthority);
+
+    let instruction = initialize_protocol_config_ix(upgrade_authority, program_data, 0);
+    svm.process(instruction, &[upgrade_authority]).unwrap();
+    let protocol_config: ProtocolConfig = svm.anchor_account(&pda(&[PROTOCOL_CONFIG_SEED], &DISTRIBUTOR));
+    assert_eq!(protocol_config.admin, upgrade_authority);
+}
+
+#[test]
+fn investor_out_of_order() {
+    let (mut fixture, investor) = capped_fixture();
+    fixture.tally(&[investor]).unwrap();
+    // A second tally of the same stream would count its weight twice
+    assert_error(fixture.tally(&[investor]), ErrorCode::InvestorOutOfOrder);
+}
+
+#[test]
+fn tally_mismatch() {
+    let (mut fixture, investor) = capped_fixture();
+    let other = fixture.add_locked_investor(1_000_000);
+    fixture.tally(&[investor, other]).unwrap();
+    fixture.crank(&[investor]).unwrap();
+    let day_id = fixture.svm.day_id();
+    assert_error(fixture.finalize(day_id), ErrorCode::TallyMismatch);
+}
+
+#[test]
+fn cap_exceeded() {
+    let (mut fixture, investor) = funded_fixture();
+    let other = fixture.add_locked_investor(1_000_000);
+    let capped = |daily_cap| CrankArgs {
+        daily_cap: Some(daily_cap),
+        ..Default::default()
+    };
+    fixture.crank_with(&[investor], capped(300_000)).unwrap();
+    assert_error(fixture.crank_with(&[other], capped(100_000)), ErrorCode::CapExceeded);
+}
@@ -0,0 +1,378 @@
+//! Property tests for the payout split in `payout.rs`
+//!
+//! A day is tallied and paid page by page through `pay_page`, as
+//! `crank_distribute_page` does, then closed with `release_held_back` and
+//! `creator_remainder`, as `finalize_day` does.
+
+use anchor_lang::prelude::Pubkey;
+use damm_v2_fee_distributor::payout::{page_payouts, pay_page, PagePayouts, PageTerms, PayoutRate};
+use damm_v2_fee_distributor::payout_cap::{self, InvestorCapPolicy, MIN_INVESTOR_CAP_BPS};
+use damm_v2_fee_distributor::{ErrorCode, ProgressAccount, BPS_DENOMINATOR};
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+
+/// Per-investor cap applied on top of the pro-rata split
+#[derive(Clone, Copy, Debug)]
+struct Cap {
+    bps: u16,
+    policy: InvestorCapPolicy,
+}
+
+/// An investor's locked amount and payout weight
+type Investor = (u64, u128);
+
+#[derive(Clone, Debug)]
+struct Day {
+    claimed: u64,
+    protocol_fee: u64,
+    crank_tip: u64,
+    y0: u64,
+    investor_share_bps: u16,
+    daily_cap: Option<u64>,
+    window_room: u64,
+    /// Overflow held back on earlier days
+    held_back: u64,
+    cap: Option<Cap>,
+    min_payout: u64,
+    /// Always set on capped days, which the crank refuses to pay untallied
+    tallied: bool,
+    pages: Vec<Vec<Investor>>,
+}
+
+impl Day {
+    /// Most the day's investors can be owed, before eligibility and caps cut it down
+    fn max_pool(&self) -> u64 {
+        bps_of(self.claimed - self.protocol_fee - self.crank_tip, self.investor_share_bps)
+    }
+
+    /// Total weight the day's rate is fixed against: the tally's, or else the first page's
+    fn rate_weight_total(&self) -> u128 {
+        if self.tallied {
+            weight_total(&page_weights(&self.pages.concat()))
+        } else {
+            weight_total(&page_weights(&self.pages[0]))
+        }
+    }
+}
+
+struct ClosedDay {
+    progress: ProgressAccount,
+    pages: Vec<PagePayouts>,
+    /// The part of the held-back reserve returned to the cap window
+    held_back_returned: u64,
+}
+
+fn bps_of(amount: u64, bps: u16) -> u64 {
+    (amount as u128 * bps as u128 / BPS_DENOMINATOR as u128) as u64
+}
+
+fn weight_total(weights: &[u128]) -> u128 {
+    weights.iter().fold(0u128, |total, weight| total.saturating_add(*weight))
+}
+
+fn page_weights(investors: &[Investor]) -> Vec<u128> {
+    investors.iter().map(|(_, weight)| *weight).collect()
+}
+
+fn top_weights(weights: &[u128]) -> Vec<u128> {
+    let mut top = Vec::new();
+    for weight in weights {
+        payout_cap::track_weight(&mut top, *weight);
+    }
+    top
+}
+
+/// Whether `payout` is within `weight`'s pro-rata share of `pool`
+///
+/// Only asked of payouts the crank made, which it fails rather than make when this overflows.
+fn within_pro_rata(payout: u64, weight: u128, pool: u64, weight_total: u128) -> bool {
+    let amount = weight.checked_mul(pool as u128).expect("the crank fails on this overflow");
+    payout as u128 <= amount.checked_div(weight_total).unwrap_or(0)
+}
+
+/// Tally and pay every page of `day` and return the progress as `finalize_day` sees it
+fn run_day(day: &Day) -> anchor_lang::Result<ClosedDay> {
+    let mut progress = ProgressAccount::new(Pubkey::default(), 0, Pubkey::default(), 0);
+    progress.claimed_quote_for_day = day.claimed;
+    progress.protocol_fee = day.protocol_fee;
+    progress.crank_tip = day.crank_tip;
+    if day.tallied {
+        for &(locked_amount, weight) in day.pages.iter().flatten() {
+            progress.tally(locked_amount, weight);
+        }
+    }
+
+    let pages = day
+        .pages
+        .iter()
+        .map(|investors| {
+            let weights = page_weights(investors);
+            let terms = PageTerms {
+                weights: &weights,
+                locked_total: investors.iter().fold(0u64, |total, (locked, _)| total.saturating_add(*locked)),
+                y0: day.y0,
+                investor_fee_share_bps: day.investor_share_bps,
+                daily_cap: day.daily_cap,
+                max_investor_share_bps: day.cap.map_or(0, |cap| cap.bps),
+                investor_cap_policy: day.cap.map_or(InvestorCapPolicy::Redistribute, |cap| cap.policy),
+                held_back: day.held_back,
+                window_room: day.window_room,
+                min_payout: day.min_payout,
+            };
+            pay_page(&mut progress, &terms)
+        })
+        .collect::<anchor_lang::Result<_>>()?;
+    let held_back_returned = progress.release_held_back();
+
+    Ok(ClosedDay { progress, pages, held_back_returned })
+}
+
+/// `run_day`, or `None` when a weight is too large to pay and the crank fails with `MathOverflow`
+fn closed_day(day: &Day) -> Result<Option<ClosedDay>, TestCaseError> {
+    match run_day(day) {
+        Ok(closed) => Ok(Some(closed)),
+        Err(error) => {
+            prop_assert_eq!(error, ErrorCode::MathOverflow.into());
+            Ok(None)
+        }
+    }
+}
+
+/// A 1,000,000 claim with a 50% investor share, paid over `pages`
+fn half_share_day(tallied: bool, daily_cap: Option<u64>, pages: Vec<Vec<Investor>>) -> Day {
+    Day {
+        claimed: 1_000_000,
+        protocol_fee: 0,
+        crank_tip: 0,
+        y0: 1,
+        investor_share_bps: 5_000,
+        daily_cap,
+        window_room: u64::MAX,
+        held_back: 0,
+        cap: None,
+        min_payout: 0,
+        tallied,
+        pages,
+    }
+}
+
+/// Two equal locks on two tallied pages are each paid half the pool
+#[test]
+fn tallied_pages_share_the_day_wide_total() {
+    let closed = run_day(&half_share_day(true, None, vec![vec![(1_000, 1_000)], vec![(1_000, 1_000)]])).unwrap();
+    assert_eq!(closed.pages[0].payouts, vec![250_000]);
+    assert_eq!(closed.pages[1].payouts, vec![250_000]);
+    assert_eq!(closed.progress.creator_remainder(), 500_000);
+}
+
+/// Cranking the same investor page after page never pays out more than the day's pool
+#[test]
+fn repeated_untallied_pages_stop_at_the_pool() {
+    let closed = run_day(&half_share_day(false, None, vec![vec![(1_000, 1_000)]; 4])).unwrap();
+    let paid: Vec<u64> = closed.pages.iter().map(|page| page.distributed).collect();
+    assert_eq!(paid, vec![500_000, 0, 0, 0]);
+    assert_eq!(closed.progress.creator_remainder(), 500_000);
+}
+
+/// A daily cap fixed on the first page pays equal tallied investors equally
+#[test]
+fn tallied_pages_share_a_daily_cap_equally() {
+    let pages = vec![vec![(1_000, 1_000)], vec![(1_000, 1_000)]];
+    let closed = run_day(&half_share_day(true, Some(100_000), pages)).unwrap();
+    assert_eq!(closed.pages[0].payouts, vec![50_000]);
+    assert_eq!(closed.pages[1].payouts, vec![50_000]);
+}
+
+/// A ve-style weight too large to multiply by the pool fails the page instead of paying 0
+#[test]
+fn overflowing_weights_fail_the_page() {
+    let weight = u64::MAX as u128 * 4 * 365 * 86_400;
+    let day = Day {
+        claimed: u64::MAX / 2,
+        ..half_share_day(false, None, vec![vec![(1_000, weight)]])
+    };
+    assert_eq!(run_day(&day).err(), Some(ErrorCode::MathOverflow.into()));
+}
+
+fn cap() -> impl Strategy<Value = Option<Cap>> {
+    let policy = prop_oneof![Just(InvestorCapPolicy::Redistribute), Just(InvestorCapPolicy::Creator)];
+    let cap = (MIN_INVESTOR_CAP_BPS..=BPS_DENOMINATOR as u16, policy);
+    proptest::option::of(cap.prop_map(|(bps, policy)| Cap { bps, policy }))
+}
+
+fn weights() -> impl Strategy<Value = Vec<u128>> {
+    let weight = prop_oneof![Just(0u128), 1..=1_000u128, 1..=u64::MAX as u128];
+    proptest::collection::vec(weight, 0..30)
+}
+
+fn investors() -> impl Strategy<Value = Vec<Investor>> {
+    let locked = prop_oneof![Just(0u64), 1..=1_000u64, any::<u64>()];
+    // Up to ve-style weights: a large lock times years of seconds left
+    let weight = prop_oneof![
+        3 => Just(0u128),
+        3 => 1..=1_000u128,
+        3 => 1..=u64::MAX as u128,
+        1 => 1..=u64::MAX as u128 * 4 * 365 * 86_400,
+    ];
+    proptest::collection::vec((locked, weight), 0..30)
+}
+
+fn day() -> impl Strategy<Value = Day> {
+    (
+        (0..=u64::MAX / 2, 0..=BPS_DENOMINATOR as u16, 0..=BPS_DENOMINATOR as u16),
+        (prop_oneof![Just(0u64), 1..=1_000_000u64, any::<u64>()], 0..=BPS_DENOMINATOR as u16),
+        (
+            proptest::option::of(any::<u64>()),
+            proptest::option::of(any::<u64>()),
+            prop_oneof![Just(0u64), 0..=u64::MAX / 4],
+            cap(),
+        ),
+        prop_oneof![Just(0u64), 1..=1_000u64, any::<u64>()],
+        any::<bool>(),
+        proptest::collection::vec(investors(), 1..5),
+    )
+        .prop_map(
+            |(
+                (claimed, protocol_fee_bps, tip_bps),
+                (y0, investor_share_bps),
+                (daily_cap, window_room, held_back, cap),
+                min_payout,
+                tallied,
+                pages,
+            )| Day {
+                claimed,
+                protocol_fee: bps_of(claimed, protocol_fee_bps),
+                crank_tip: bps_of(claimed - bps_of(claimed, protocol_fee_bps), tip_bps),
+                y0,
+                investor_share_bps,
+                daily_cap,
+                window_room: window_room.unwrap_or(u64::MAX),
+                held_back,
+                cap,
+                min_payout,
+                tallied: tallied || cap.is_some(),
+                pages,
+            },
+        )
+}
+
+proptest! {
+    #[test]
+    fn claimed_quote_is_conserved(day in day()) {
+        let Some(closed) = closed_day(&day)? else { return Ok(()) };
+        let progress = &closed.progress;
+        prop_assert!(progress.held_back_reserved <= day.held_back);
+        prop_assert!(
+            progress.cumulative_distributed_today + progress.carry_over
+                <= day.max_pool() + progress.held_back_reserved
+        );
+        // The reserve comes in on top of the claim, and what investors did not draw on goes back
+        prop_assert_eq!(
+            progress.protocol_fee
+                + progress.crank_tip
+                + progress.cumulative_distributed_today
+                + progress.carry_over
+                + progress.creator_remainder()
+                + closed.held_back_returned,
+            day.claimed + progress.held_back_reserved
+        );
+    }
+
+    #[test]
+    fn pages_account_for_every_investor(day in day()) {
+        let Some(closed) = closed_day(&day)? else { return Ok(()) };
+        for (investors, page) in day.pages.iter().zip(&closed.pages) {
+            prop_assert_eq!(page.payouts.len(), investors.len());
+            prop_assert_eq!(page.paid + page.skipped, investors.len() as u64);
+            prop_assert_eq!(page.payouts.iter().sum::<u64>(), page.distributed);
+        }
+        prop_assert_eq!(closed.progress.pagination_cursor, day.pages.len() as u64);
+    }
+
+    #[test]
+    fn nobody_receives_more_than_their_share(day in day()) {
+        let Some(closed) = closed_day(&day)? else { return Ok(()) };
+        let pool = closed.progress.investor_pool;
+        prop_assert!(pool <= day.max_pool() + closed.progress.held_back_reserved);
+        let day_total = day.rate_weight_total();
+        for (investors, page) in day.pages.iter().zip(&closed.pages) {
+            for (&(_, weight), &payout) in investors.iter().zip(&page.payouts) {
+                match day.cap {
+                    None => prop_assert!(within_pro_rata(payout, weight, pool, day_total)),
+                    Some(cap) => {
+                        prop_assert!(payout <= closed.progress.investor_cap);
+                        prop_assert_eq!(closed.progress.investor_cap, bps_of(pool, cap.bps));
+                        if cap.policy == InvestorCapPolicy::Creator {
+                            prop_assert!(within_pro_rata(payout, weight, pool, day_total));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn payouts_below_the_minimum_are_carried(day in day()) {
+        let Some(closed) = closed_day(&day)? else { return Ok(()) };
+        for (investors, page) in day.pages.iter().zip(&closed.pages) {
+            let mut carried = 0u128;
+            for (&(_, weight), &payout) in investors.iter().zip(&page.payouts) {
+                if payout > 0 {
+                    prop_assert!(payout >= day.min_payout);
+                } else if weight > 0 {
+                    carried += 1;
+                }
+            }
+            // Each carried payout is below the minimum
+            prop_assert!(page.dust as u128 <= carried * day.min_payout.saturating_sub(1) as u128);
+        }
+    }
+
+    #[test]
+    fn a_zero_minimum_pays_everyone_with_weight(weights in weights(), pool in any::<u64>()) {
+        let rate = PayoutRate::ProRata { pool, weight_total: weight_total(&weights) };
+        let page = page_payouts(&weights, rate, pool, 0).unwrap();
+        prop_assert_eq!(page.dust, 0);
+        prop_assert_eq!(page.paid, weights.iter().filter(|weight| **weight > 0).count() as u64);
+    }
+
+    #[test]
+    fn a_minimum_above_the_pool_pays_nobody(weights in weights(), pool in 0..u64::MAX) {
+        let rate = PayoutRate::ProRata { pool, weight_total: weight_total(&weights) };
+        let page = page_payouts(&weights, rate, pool, pool + 1).unwrap();
+        prop_assert_eq!(page.distributed, 0);
+        prop_assert_eq!(page.skipped, weights.len() as u64);
+        prop_assert!(page.dust <= pool);
+    }
+
+    #[test]
+    fn a_full_cap_pays_plain_pro_rata(weights in weights(), pool in any::<u64>()) {
+        let total = weight_total(&weights);
+        let (numerator, denominator) = payout_cap::water_level(pool, pool, total, &top_weights(&weights));
+        let capped = PayoutRate::Capped { numerator, denominator, cap: pool };
+        let pro_rata = PayoutRate::ProRata { pool, weight_total: total };
+        prop_assert_eq!(
+            page_payouts(&weights, capped, pool, 0).unwrap(),
+            page_payouts(&weights, pro_rata, pool, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn redistribution_never_lowers_a_payout(
+        weights in weights(),
+        pool in any::<u64>(),
+        bps in MIN_INVESTOR_CAP_BPS..=BPS_DENOMINATOR as u16,
+    ) {
+        let total = weight_total(&weights);
+        let cap = bps_of(pool, bps);
+        let (numerator, denominator) = payout_cap::water_level(pool, cap, total, &top_weights(&weights));
+        let redistributed = PayoutRate::Capped { numerator, denominator, cap };
+        let creator = PayoutRate::Capped { numerator: pool as u128, denominator: total, cap };
+        let redistributed = page_payouts(&weights, redistributed, pool, 0).unwrap();
+        let creator = page_payouts(&weights, creator, pool, 0).unwrap();
+        prop_assert!(redistributed.distributed <= pool);
+        for (high, low) in redistributed.payouts.iter().zip(&creator.payouts) {
+            prop_assert!(high >= low);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! The runtime's account rules as `Svm` enforces them
+//!
+//! A small native program takes an opcode in its first data byte and does one
+//! thing to its first account, or calls itself with the rest of its data.
+
+// `solana_program`'s system program id is deprecated in newer Solana crates
+#![allow(deprecated)]
+
+mod common;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program::invoke;
+use anchor_lang::solana_program::system_program;
+use common::svm::{Account, Svm};
+
+const PROGRAM: Pubkey = Pubkey::new_from_array([7; 32]);
+const OTHER_PROGRAM: Pubkey = Pubkey::new_from_array([8; 32]);
+
+const WRITE: u8 = 0;
+const PAY: u8 = 1;
+const ASSIGN: u8 = 2;
+const RESIZE: u8 = 3;
+const INVOKE_WRITABLE: u8 = 4;
+const INVOKE_RESIZE: u8 = 5;
+
+fn processor<'a>(program_id: &Pubkey, accounts: &'a [AccountInfo<'a>], data: &[u8]) -> ProgramResult {
+    let account = &accounts[0];
+    match data[0] {
+        WRITE => {
+            account.try_borrow_mut_data()?[0] = data[1];
+            Ok(())
+        }
+        PAY => {
+            **account.try_borrow_mut_lamports()? -= 1;
+            **accounts[1].try_borrow_mut_lamports()? += 1;
+            Ok(())
+        }
+        ASSIGN => {
+            account.assign(&system_program::ID);
+            Ok(())
+        }
+        RESIZE => account.resize(data[1] as usize),
+        // Call back in, asking for the account as writable
+        INVOKE_WRITABLE => invoke(
+            &Instruction {
+                program_id: *program_id,
+                accounts: vec![AccountMeta::new(*account.key, false)],
+                data: vec![WRITE, 1],
+            },
+            &accounts[..1],
+        ),
+        // Have a callee resize the account, then check the caller sees the new length
+        INVOKE_RESIZE => {
+            invoke(
+                &Instruction {
+                    program_id: *program_id,
+                    accounts: vec![AccountMeta::new(*account.key, false)],
+                    data: vec![RESIZE, data[1]],
+                },
+                &accounts[..1],
+            )?;
+            if account.data_len() == data[1] as usize {
+                Ok(())
+            } else {
+                Err(ProgramError::InvalidRealloc)
+            }
+        }
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+fn svm() -> Svm {
+    let mut svm = Svm::new();
+    svm.add_program(PROGRAM, processor);
+    svm
+}
+
+/// Put an account with 1 SOL and `data` owned by `owner` at a fresh address
+fn create(svm: &mut Svm, owner: Pubkey, data: Vec<u8>) -> Pubkey {
+    let address = Pubkey::new_unique();
+    svm.set_account(address, Account { lamports: 1_000_000_000, data, owner, executable: false });
+    address
+}
+
+fn run(svm: &mut Svm, accounts: Vec<AccountMeta>, data: Vec<u8>, signers: &[Pubkey]) -> ProgramResult {
+    svm.process(Instruction { program_id: PROGRAM, accounts, data }, signers)
+}
+
+#[test]
+fn only_the_owner_writes_data() {
+    let mut svm = svm();
+    let owned = create(&mut svm, PROGRAM, vec![0; 8]);
+    let foreign = create(&mut svm, OTHER_PROGRAM, vec![0; 8]);
+
+    assert_eq!(
+        run(&mut svm, vec![AccountMeta::new(foreign, false)], vec![WRITE, 1], &[]),
+        Err(ProgramError::IllegalOwner)
+    );
+    run(&mut svm, vec![AccountMeta::new(owned, false)], vec![WRITE, 1], &[]).unwrap();
+    assert_eq!(svm.account(&owned).unwrap().data[0], 1);
+}
+
+#[test]
+fn read_only_accounts_are_immutable() {
+    let mut svm = svm();
+    let owned = create(&mut svm, PROGRAM, vec![0; 8]);
+    let receiver = create(&mut svm, OTHER_PROGRAM, Vec::new());
+
+    assert_eq!(
+        run(&mut svm, vec![AccountMeta::new_readonly(owned, false)], vec![WRITE, 1], &[]),
+        Err(ProgramError::Immutable)
+    );
+    assert_eq!(
+        run(
+            &mut svm,
+            vec![AccountMeta::new(owned, false), AccountMeta::new_readonly(receiver, false)],
+            vec![PAY],
+            &[]
+        ),
+        Err(ProgramError::Immutable)
+    );
+}
+
+#[test]
+fn only_the_owner_debits_lamports() {
+    let mut svm = svm();
+    let owned = create(&mut svm, PROGRAM, Vec::new());
+    let foreign = create(&mut svm, OTHER_PROGRAM, Vec::new());
+
+    assert_eq!(
+        run(
+            &mut svm,
+            vec![AccountMeta::new(foreign, false), AccountMeta::new(owned, false)],
+            vec![PAY],
+            &[]
+        ),
+        Err(ProgramError::IllegalOwner)
+    );
+    // Anyone may credit an account
+    run(
+        &mut svm,
+        vec![AccountMeta::new(owned, false), AccountMeta::new(foreign, false)],
+        vec![PAY],
+        &[],
+    )
+    .unwrap();
+    assert_eq!(svm.account(&foreign).unwrap().lamports, 1_000_000_001);
+}
+
+#[test]
+fn only_zeroed_accounts_change_owner() {
+    let mut svm = svm();
+    let dirty = create(&mut svm, PROGRAM, vec![1; 8]);
+    let zeroed = create(&mut svm, PROGRAM, vec![0; 8]);
+    let foreign = create(&mut svm, OTHER_PROGRAM, vec![0; 8]);
+
+    assert_eq!(
+        run(&mut svm, vec![AccountMeta::new(dirty, false)], vec![ASSIGN], &[]),
+        Err(ProgramError::IllegalOwner)
+    );
+    assert_eq!(
+        run(&mut svm, vec![AccountMeta::new(foreign, false)], vec![ASSIGN], &[]),
+        Err(ProgramError::IllegalOwner)
+    );
+    run(&mut svm, vec![AccountMeta::new(zeroed, false)], vec![ASSIGN], &[]).unwrap();
+    assert_eq!(svm.account(&zeroed).unwrap().owner, system_program::ID);
+}
+
+#[test]
+fn signers_must_sign() {
+    let mut svm = svm();
+    let owned = create(&mut svm, PROGRAM, vec![0; 8]);
+
+    assert_eq!(
+        run(&mut svm, vec![AccountMeta::new(owned, true)], vec![WRITE, 1], &[]),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    run(&mut svm, vec![AccountMeta::new(owned, true)], vec![WRITE, 1], &[owned]).unwrap();
+}
+
+#[test]
+fn invocations_cannot_escalate_privileges() {
+    let mut svm = svm();
+    let owned = create(&mut svm, PROGRAM, vec![0; 8]);
+
+    assert_eq!(
+        run(&mut svm, vec![AccountMeta::new_readonly(owned, false)], vec![INVOKE_WRITABLE], &[]),
+        Err(ProgramError::InvalidArgument)
+    );
+    run(&mut svm, vec![AccountMeta::new(owned, false)], vec![INVOKE_WRITABLE], &[]).unwrap();
+    assert_eq!(svm.account(&owned).unwrap().data[0], 1);
+}
+
+#[test]
+fn callers_see_data_a_callee_resized() {
+    let mut svm = svm();
+    let owned = create(&mut svm, PROGRAM, vec![0; 8]);
+
+    run(&mut svm, vec![AccountMeta::new(owned, false)], vec![INVOKE_RESIZE, 32], &[]).unwrap();
+    assert_eq!(svm.account(&owned).unwrap().data, vec![0; 32]);
+    run(&mut svm, vec![AccountMeta::new(owned, false)], vec![INVOKE_RESIZE, 0], &[]).unwrap();
+    assert!(svm.account(&owned).unwrap().data.is_empty());
+}
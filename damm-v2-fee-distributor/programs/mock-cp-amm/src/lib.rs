@@ -0,0 +1,542 @@
+// The `#[program]` macro expands to `AccountInfo::realloc`, deprecated in newer Solana crates
+#![allow(deprecated)]
+
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+declare_id!("11111111111111111111111111111115");
+
+pub const POOL_AUTHORITY_SEED: &[u8] = b"pool_authority";
+pub const QUOTE_VAULT_SEED: &[u8] = b"quote_vault";
+pub const BASE_VAULT_SEED: &[u8] = b"base_vault";
+
+const BPS_DENOMINATOR: u128 = 10_000;
+
+#[program]
+pub mod mock_cp_amm {
+    use super::*;
+
+    /// Create a pool with empty quote and base vaults at `sqrt_price_x64`
+    pub fn initialize_pool(
+        ctx: Context<InitializePool>,
+        sqrt_price_x64: u128,
+        swap_fee_bps: u16,
+    ) -> Result<()> {
+        require!(swap_fee_bps as u128 <= BPS_DENOMINATOR, MockCpAmmError::InvalidSwapFee);
+
+        let pool = &mut ctx.accounts.pool;
+        pool.authority = ctx.accounts.pool_authority.key();
+        pool.quote_mint = ctx.accounts.quote_mint.key();
+        pool.base_mint = ctx.accounts.base_mint.key();
+        pool.quote_vault = ctx.accounts.quote_vault.key();
+        pool.base_vault = ctx.accounts.base_vault.key();
+        pool.sqrt_price_x64 = sqrt_price_x64;
+        pool.swap_fee_bps = swap_fee_bps;
+        pool.authority_bump = ctx.bumps.pool_authority;
+
+        Ok(())
+    }
+
+    /// Move the pool's price, standing in for trading activity
+    pub fn set_sqrt_price(ctx: Context<SetSqrtPrice>, sqrt_price_x64: u128) -> Result<()> {
+        ctx.accounts.pool.sqrt_price_x64 = sqrt_price_x64;
+        Ok(())
+    }
+
+    /// Open a position in `pool` owned by `owner`, which may be any address including a PDA
+    pub fn create_position(ctx: Context<CreatePosition>, owner: Pubkey) -> Result<()> {
+        let position = &mut ctx.accounts.position;
+        position.pool = ctx.accounts.pool.key();
+        position.owner = owner;
+        position.fee_quote_pending = 0;
+        position.fee_base_pending = 0;
+        position.liquidity_quote = 0;
+        position.liquidity_base = 0;
+
+        Ok(())
+    }
+
+    /// Deposit tokens into the pool's vaults as `position`'s liquidity
+    ///
+    /// Takes the same accounts as `accrue_fees`.
+    pub fn add_liquidity(ctx: Context<AccrueFees>, quote_amount: u64, base_amount: u64) -> Result<()> {
+        let accounts = &ctx.accounts;
+        if quote_amount > 0 {
+            transfer(
+                &accounts.quote_token_program,
+                &accounts.funder_quote,
+                &accounts.quote_mint,
+                &accounts.quote_vault,
+                &accounts.funder,
+                &[],
+                quote_amount,
+            )?;
+        }
+        if base_amount > 0 {
+            transfer(
+                &accounts.base_token_program,
+                &accounts.funder_base,
+                &accounts.base_mint,
+                &accounts.base_vault,
+                &accounts.funder,
+                &[],
+                base_amount,
+            )?;
+        }
+
+        let position = &mut ctx.accounts.position;
+        position.liquidity_quote = position
+            .liquidity_quote
+            .checked_add(quote_amount)
+            .ok_or(MockCpAmmError::MathOverflow)?;
+        position.liquidity_base = position
+            .liquidity_base
+            .checked_add(base_amount)
+            .ok_or(MockCpAmmError::MathOverflow)?;
+
+        Ok(())
+    }
+
+    /// Deposit fees into the pool's vaults and credit them to `position`, standing in for swaps
+    pub fn accrue_fees(ctx: Context<AccrueFees>, quote_amount: u64, base_amount: u64) -> Result<()> {
+        let accounts = &ctx.accounts;
+        if quote_amount > 0 {
+            transfer(
+                &accounts.quote_token_program,
+                &accounts.funder_quote,
+                &accounts.quote_mint,
+                &accounts.quote_vault,
+                &accounts.funder,
+                &[],
+                quote_amount,
+            )?;
+        }
+        if base_amount > 0 {
+            transfer(
+                &accounts.base_token_program,
+                &accounts.funder_base,
+                &accounts.base_mint,
+                &accounts.base_vault,
+                &accounts.funder,
+                &[],
+                base_amount,
+            )?;
+        }
+
+        let position = &mut ctx.accounts.position;
+        position.fee_quote_pending = position
+            .fee_quote_pending
+            .checked_add(quote_amount)
+            .ok_or(MockCpAmmError::MathOverflow)?;
+        position.fee_base_pending = position
+            .fee_base_pending
+            .checked_add(base_amount)
+            .ok_or(MockCpAmmError::MathOverflow)?;
+
+        Ok(())
+    }
+
+    /// Pay a position's pending fees out to the owner's chosen token accounts
+    pub fn claim_fees(ctx: Context<ClaimFees>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let pool_key = accounts.pool.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            POOL_AUTHORITY_SEED,
+            pool_key.as_ref(),
+            &[accounts.pool.authority_bump],
+        ]];
+
+        let quote_amount = accounts.position.fee_quote_pending;
+        let base_amount = accounts.position.fee_base_pending;
+        if quote_amount > 0 {
+            transfer(
+                &accounts.quote_token_program,
+                &accounts.quote_vault,
+                &accounts.quote_mint,
+                &accounts.quote_destination,
+                &accounts.pool_authority,
+                signer_seeds,
+                quote_amount,
+            )?;
+        }
+        if base_amount > 0 {
+            transfer(
+                &accounts.base_token_program,
+                &accounts.base_vault,
+                &accounts.base_mint,
+                &accounts.base_destination,
+                &accounts.pool_authority,
+                signer_seeds,
+                base_amount,
+            )?;
+        }
+
+        let position = &mut ctx.accounts.position;
+        position.fee_quote_pending = 0;
+        position.fee_base_pending = 0;
+
+        Ok(())
+    }
+
+    /// Withdraw all of a position's liquidity to the owner's chosen token accounts
+    ///
+    /// Takes the same accounts as `claim_fees`.
+    pub fn remove_all_liquidity(ctx: Context<ClaimFees>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let pool_key = accounts.pool.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            POOL_AUTHORITY_SEED,
+            pool_key.as_ref(),
+            &[accounts.pool.authority_bump],
+        ]];
+
+        let quote_amount = accounts.position.liquidity_quote;
+        let base_amount = accounts.position.liquidity_base;
+        if quote_amount > 0 {
+            transfer(
+                &accounts.quote_token_program,
+                &accounts.quote_vault,
+                &accounts.quote_mint,
+                &accounts.quote_destination,
+                &accounts.pool_authority,
+                signer_seeds,
+                quote_amount,
+            )?;
+        }
+        if base_amount > 0 {
+            transfer(
+                &accounts.base_token_program,
+                &accounts.base_vault,
+                &accounts.base_mint,
+                &accounts.base_destination,
+                &accounts.pool_authority,
+                signer_seeds,
+                base_amount,
+            )?;
+        }
+
+        let position = &mut ctx.accounts.position;
+        position.liquidity_quote = 0;
+        position.liquidity_base = 0;
+
+        Ok(())
+    }
+
+    /// Sell `amount_in` base for quote at the pool's spot price, less the pool's swap fee
+    pub fn swap_base_for_quote(
+        ctx: Context<SwapBaseForQuote>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let pool = &accounts.pool;
+        let spot = quote_at_spot(pool.sqrt_price_x64, amount_in) as u128;
+        let amount_out =
+            (spot * (BPS_DENOMINATOR - pool.swap_fee_bps as u128) / BPS_DENOMINATOR) as u64;
+        require!(amount_out >= minimum_amount_out, MockCpAmmError::SlippageExceeded);
+
+        transfer(
+            &accounts.base_token_program,
+            &accounts.user_base,
+            &accounts.base_mint,
+            &accounts.base_vault,
+            &accounts.user,
+            &[],
+            amount_in,
+        )?;
+
+        let pool_key = pool.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            POOL_AUTHORITY_SEED,
+            pool_key.as_ref(),
+            &[pool.authority_bump],
+        ]];
+        transfer(
+            &accounts.quote_token_program,
+            &accounts.quote_vault,
+            &accounts.quote_mint,
+            &accounts.user_quote,
+            &accounts.pool_authority,
+            signer_seeds,
+            amount_out,
+        )?;
+
+        Ok(())
+    }
+
+    /// Close a position with no fees pending and no liquidity, returning its rent
+    pub fn close_position(_ctx: Context<ClosePosition>) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn transfer<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    authority: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+) -> Result<()> {
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: from.to_account_info(),
+                mint: mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: authority.clone(),
+            },
+            signer_seeds,
+        ),
+        amount,
+        mint.decimals,
+    )
+}
+
+/// Quote worth `base_amount` at `sqrt_price_x64`, the Q64.64 square root of the quote-per-base price
+pub fn quote_at_spot(sqrt_price_x64: u128, base_amount: u64) -> u64 {
+    let scaled = (sqrt_price_x64 >> 32).saturating_mul(sqrt_price_x64 >> 32);
+    let quote = (scaled >> 64).saturating_mul(base_amount as u128)
+        + ((scaled & u64::MAX as u128).saturating_mul(base_amount as u128) >> 64);
+    std::cmp::min(quote, u64::MAX as u128) as u64
+}
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(init, payer = payer, space = 8 + Pool::LEN)]
+    pub pool: Account<'info, Pool>,
+
+    /// CHECK: PDA owning the pool's vaults, validated by seeds
+    #[account(seeds = [POOL_AUTHORITY_SEED, pool.key().as_ref()], bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    #[account(mint::token_program = quote_token_program)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mint::token_program = base_token_program)]
+    pub base_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [QUOTE_VAULT_SEED, pool.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = pool_authority,
+        token::token_program = quote_token_program
+    )]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [BASE_VAULT_SEED, pool.key().as_ref()],
+        bump,
+        token::mint = base_mint,
+        token::authority = pool_authority,
+        token::token_program = base_token_program
+    )]
+    pub base_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub quote_token_program: Interface<'info, TokenInterface>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetSqrtPrice<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+}
+
+#[derive(Accounts)]
+pub struct CreatePosition<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub pool: Account<'info, Pool>,
+
+    #[account(init, payer = payer, space = 8 + Position::LEN)]
+    pub position: Account<'info, Position>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AccrueFees<'info> {
+    pub funder: Signer<'info>,
+
+    #[account(
+        has_one = quote_mint,
+        has_one = base_mint,
+        has_one = quote_vault,
+        has_one = base_vault
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut, has_one = pool)]
+    pub position: Account<'info, Position>,
+
+    #[account(mut)]
+    pub funder_quote: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub funder_base: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub base_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    pub base_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_program: Interface<'info, TokenInterface>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimFees<'info> {
+    #[account(
+        has_one = quote_mint,
+        has_one = base_mint,
+        has_one = quote_vault,
+        has_one = base_vault
+    )]
+    pub pool: Account<'info, Pool>,
+
+    /// CHECK: PDA owning the pool's vaults, validated by seeds
+    #[account(seeds = [POOL_AUTHORITY_SEED, pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    #[account(mut, has_one = pool, has_one = owner @ MockCpAmmError::NotPositionOwner)]
+    pub position: Account<'info, Position>,
+
+    pub owner: Signer<'info>,
+
+    #[account(mut)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub base_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Receives the position's quote fees
+    #[account(mut)]
+    pub quote_destination: InterfaceAccount<'info, TokenAccount>,
+
+    /// Receives the position's base fees
+    #[account(mut)]
+    pub base_destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    pub base_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_program: Interface<'info, TokenInterface>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SwapBaseForQuote<'info> {
+    #[account(
+        has_one = quote_mint,
+        has_one = base_mint,
+        has_one = quote_vault,
+        has_one = base_vault
+    )]
+    pub pool: Account<'info, Pool>,
+
+    /// CHECK: PDA owning the pool's vaults, validated by seeds
+    #[account(seeds = [POOL_AUTHORITY_SEED, pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub user_base: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user_quote: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub base_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    pub base_mint: InterfaceAccount<'info, Mint>,
+    pub quote_token_program: Interface<'info, TokenInterface>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePosition<'info> {
+    #[account(
+        mut,
+        close = rent_receiver,
+        has_one = owner @ MockCpAmmError::NotPositionOwner,
+        constraint = position.fee_quote_pending == 0 && position.fee_base_pending == 0
+            @ MockCpAmmError::FeesPending,
+        constraint = position.liquidity_quote == 0 && position.liquidity_base == 0
+            @ MockCpAmmError::LiquidityRemaining
+    )]
+    pub position: Account<'info, Position>,
+
+    pub owner: Signer<'info>,
+
+    /// CHECK: Any account may receive the position's rent
+    #[account(mut)]
+    pub rent_receiver: AccountInfo<'info>,
+}
+
+/// A constant-price pool: swaps fill at `sqrt_price_x64` less `swap_fee_bps`
+#[account]
+pub struct Pool {
+    pub authority: Pubkey,
+    pub quote_mint: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_vault: Pubkey,
+    pub base_vault: Pubkey,
+    pub sqrt_price_x64: u128,
+    pub swap_fee_bps: u16,
+    pub authority_bump: u8,
+}
+
+impl Pool {
+    pub const LEN: usize = 32 + 32 + 32 + 32 + 32 + 16 + 2 + 1;
+}
+
+/// A position collecting the fees credited to it, with liquidity held in the pool's vaults
+#[account]
+pub struct Position {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub fee_quote_pending: u64,
+    pub fee_base_pending: u64,
+    pub liquidity_quote: u64,
+    pub liquidity_base: u64,
+}
+
+impl Position {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8;
+}
+
+#[error_code]
+pub enum MockCpAmmError {
+    #[msg("Swap fee must not exceed 10000 bps")]
+    InvalidSwapFee,
+    #[msg("Signer does not own the position")]
+    NotPositionOwner,
+    #[msg("Position has unclaimed fees")]
+    FeesPending,
+    #[msg("Position still holds liquidity")]
+    LiquidityRemaining,
+    #[msg("Swap output below the minimum")]
+    SlippageExceeded,
+    #[msg("Math overflow")]
+    MathOverflow,
+}
@@ -0,0 +1,61 @@
+// The `#[program]` macro expands to `AccountInfo::realloc`, deprecated in newer Solana crates
+#![allow(deprecated)]
+
+use anchor_lang::prelude::*;
+
+declare_id!("11111111111111111111111111111114");
+
+/// Marks an account as a Pyth-style price account
+pub const PRICE_ACCOUNT_MAGIC: u32 = 0xa1b2c3d4;
+pub const PRICE_ACCOUNT_VERSION: u32 = 2;
+
+#[program]
+pub mod mock_oracle {
+    use super::*;
+
+    /// Write a price into `price_feed`, which must already be allocated to this program
+    pub fn set_price(
+        ctx: Context<SetPrice>,
+        price: i64,
+        conf: u64,
+        expo: i32,
+        publish_time: i64,
+    ) -> Result<()> {
+        let price_data = PriceFeedData {
+            magic: PRICE_ACCOUNT_MAGIC,
+            version: PRICE_ACCOUNT_VERSION,
+            expo,
+            price,
+            conf,
+            publish_time,
+        };
+
+        let mut price_feed = ctx.accounts.price_feed.try_borrow_mut_data()?;
+        price_data.serialize(&mut &mut price_feed[..])?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SetPrice<'info> {
+    /// The price account to write
+    /// CHECK: Owned by this program, laid out as `PriceFeedData`
+    #[account(mut, owner = crate::ID)]
+    pub price_feed: AccountInfo<'info>,
+}
+
+/// Pyth-style price account: `price * 10^expo` with a confidence interval `conf`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PriceFeedData {
+    pub magic: u32,
+    pub version: u32,
+    pub expo: i32,
+    pub price: i64,
+    pub conf: u64,
+    pub publish_time: i64,
+}
+
+impl PriceFeedData {
+    pub const LEN: usize = 4 + 4 + 4 + 8 + 8 + 8;
+}
@@ -1,4 +1,8 @@
+// The `#[program]` macro expands to `AccountInfo::realloc`, deprecated in newer Solana crates
+#![allow(deprecated)]
+
 use anchor_lang::prelude::*;
+pub use stream_vesting::StreamData;
 
 declare_id!("11111111111111111111111111111113");
 
@@ -8,30 +12,15 @@ pub mod mock_streamflow {
 
     pub fn get_locked_amount(
         ctx: Context<GetLockedAmount>,
-        stream_pubkey: Pubkey,
+        _stream_pubkey: Pubkey,
         timestamp: i64,
     ) -> Result<u64> {
-        // Mock implementation - return a fixed amount for testing
-        // In real implementation, this would read the stream account
-        // and calculate the locked amount based on vesting schedule
-        
-        let stream = &ctx.accounts.stream;
-        let stream_data = StreamData::try_from_slice(&stream.data.borrow())?;
-        
-        if timestamp >= stream_data.end_ts {
-            Ok(0) // Stream has ended
-        } else if timestamp <= stream_data.start_ts {
-            Ok(stream_data.initial_allocation) // Stream hasn't started
-        } else {
-            // Calculate linear unlock
-            let total_duration = stream_data.end_ts - stream_data.start_ts;
-            let elapsed = timestamp - stream_data.start_ts;
-            let unlocked = (stream_data.initial_allocation as i64 * elapsed) / total_duration;
-            let locked = stream_data.initial_allocation.saturating_sub(unlocked as u64);
-            Ok(locked)
-        }
+        // Uses the same stream layout and vesting calculation as the distributor's Streamflow client
+        let stream_data = StreamData::deserialize(&mut &ctx.accounts.stream.data.borrow()[..])?;
+        Ok(stream_data.locked_amount(timestamp))
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn create_stream(
         ctx: Context<CreateStream>,
         initial_allocation: u64,
@@ -39,6 +28,10 @@ pub mod mock_streamflow {
         end_ts: i64,
         mint: Pubkey,
         recipient: Pubkey,
+        cliff: i64,
+        cliff_amount: u64,
+        period: u64,
+        amount_per_period: u64,
     ) -> Result<()> {
         let stream_data = StreamData {
             initial_allocation,
@@ -46,6 +39,12 @@ pub mod mock_streamflow {
             end_ts,
             mint,
             recipient,
+            canceled_at: 0,
+            closed: false,
+            cliff,
+            cliff_amount,
+            period,
+            amount_per_period,
         };
 
         let mut stream_account = ctx.accounts.stream.try_borrow_mut_data()?;
@@ -53,12 +52,38 @@ pub mod mock_streamflow {
 
         Ok(())
     }
+
+    pub fn cancel_stream(ctx: Context<UpdateStream>, canceled_at: i64) -> Result<()> {
+        update_stream(&ctx.accounts.stream, |stream_data| {
+            stream_data.canceled_at = canceled_at;
+        })
+    }
+
+    pub fn close_stream(ctx: Context<UpdateStream>) -> Result<()> {
+        update_stream(&ctx.accounts.stream, |stream_data| {
+            stream_data.closed = true;
+        })
+    }
+
+    pub fn transfer_stream(ctx: Context<UpdateStream>, new_recipient: Pubkey) -> Result<()> {
+        update_stream(&ctx.accounts.stream, |stream_data| {
+            stream_data.recipient = new_recipient;
+        })
+    }
+}
+
+fn update_stream(stream: &AccountInfo, update: impl FnOnce(&mut StreamData)) -> Result<()> {
+    let mut stream_data = StreamData::deserialize(&mut &stream.data.borrow()[..])?;
+    update(&mut stream_data);
+    let mut stream_account = stream.try_borrow_mut_data()?;
+    stream_data.serialize(&mut &mut stream_account[..])?;
+    Ok(())
 }
 
 #[derive(Accounts)]
 pub struct GetLockedAmount<'info> {
     /// The stream account
-    #[account(mut)]
+    /// CHECK: Read-only, deserialized as `StreamData`
     pub stream: AccountInfo<'info>,
 }
 
@@ -75,11 +100,10 @@ pub struct CreateStream<'info> {
     pub system_program: Program<'info, System>,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-pub struct StreamData {
-    pub initial_allocation: u64,
-    pub start_ts: i64,
-    pub end_ts: i64,
-    pub mint: Pubkey,
-    pub recipient: Pubkey,
-}
\ No newline at end of file
+#[derive(Accounts)]
+pub struct UpdateStream<'info> {
+    /// The stream account to update
+    /// CHECK: Owned by this program, written by `create_stream`
+    #[account(mut, owner = crate::ID)]
+    pub stream: AccountInfo<'info>,
+}
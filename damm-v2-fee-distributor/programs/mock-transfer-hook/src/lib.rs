@@ -0,0 +1,103 @@
+// The `#[program]` macro expands to `AccountInfo::realloc`, deprecated in newer Solana crates
+#![allow(deprecated)]
+
+use anchor_lang::prelude::*;
+use spl_discriminator::SplDiscriminate;
+use spl_tlv_account_resolution::account::ExtraAccountMeta;
+use spl_tlv_account_resolution::seeds::Seed;
+use spl_tlv_account_resolution::state::ExtraAccountMetaList;
+use spl_transfer_hook_interface::instruction::ExecuteInstruction;
+
+declare_id!("11111111111111111111111111111116");
+
+pub const EXTRA_ACCOUNT_METAS_SEED: &[u8] = b"extra-account-metas";
+pub const COUNTER_SEED: &[u8] = b"counter";
+
+#[program]
+pub mod mock_transfer_hook {
+    use super::*;
+
+    /// Write `mint`'s ExtraAccountMetaList, asking every transfer for the
+    /// writable counter PDA, and create the counter
+    pub fn initialize_extra_account_meta_list(ctx: Context<InitializeExtraAccountMetaList>) -> Result<()> {
+        let extra_account_metas = [ExtraAccountMeta::new_with_seeds(
+            &[Seed::Literal { bytes: COUNTER_SEED.to_vec() }],
+            false,
+            true,
+        )?];
+        let mut data = ctx.accounts.extra_account_meta_list.try_borrow_mut_data()?;
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, &extra_account_metas)?;
+
+        Ok(())
+    }
+
+    /// The transfer-hook `Execute` instruction: count the transfer
+    #[instruction(discriminator = ExecuteInstruction::SPL_DISCRIMINATOR_SLICE)]
+    pub fn execute(ctx: Context<Execute>, amount: u64) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+        counter.transfers += 1;
+        counter.amount += amount;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeExtraAccountMetaList<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Laid out as an ExtraAccountMetaList by the instruction
+    #[account(
+        init,
+        payer = payer,
+        space = ExtraAccountMetaList::size_of(1)?,
+        seeds = [EXTRA_ACCOUNT_METAS_SEED, mint.key().as_ref()],
+        bump
+    )]
+    pub extra_account_meta_list: AccountInfo<'info>,
+
+    /// CHECK: The Token-2022 mint whose transfers call this hook
+    pub mint: AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Counter::INIT_SPACE,
+        seeds = [COUNTER_SEED],
+        bump
+    )]
+    pub counter: Account<'info, Counter>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Execute<'info> {
+    /// CHECK: Source token account, checked by Token-2022
+    pub source: AccountInfo<'info>,
+
+    /// CHECK: The mint being transferred, checked by Token-2022
+    pub mint: AccountInfo<'info>,
+
+    /// CHECK: Destination token account, checked by Token-2022
+    pub destination: AccountInfo<'info>,
+
+    /// CHECK: Source owner or delegate, checked by Token-2022
+    pub authority: AccountInfo<'info>,
+
+    /// CHECK: The mint's ExtraAccountMetaList
+    #[account(seeds = [EXTRA_ACCOUNT_METAS_SEED, mint.key().as_ref()], bump)]
+    pub extra_account_meta_list: AccountInfo<'info>,
+
+    #[account(mut, seeds = [COUNTER_SEED], bump)]
+    pub counter: Account<'info, Counter>,
+}
+
+/// Transfers seen by the hook
+#[account]
+#[derive(InitSpace)]
+pub struct Counter {
+    pub transfers: u64,
+    pub amount: u64,
+}
@@ -1,7 +1,9 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { DammV2FeeDistributor } from "../target/types/damm_v2_fee_distributor";
-import { PublicKey, Keypair, SystemProgram, SYSVAR_RENT_PUBKEY } from "@solana/web3.js";
+import { MockStreamflow } from "../target/types/mock_streamflow";
+import { MockCpAmm } from "../target/types/mock_cp_amm";
+import { PublicKey, Keypair, SystemProgram, SYSVAR_RENT_PUBKEY, Transaction } from "@solana/web3.js";
 import { 
   TOKEN_PROGRAM_ID, 
   ASSOCIATED_TOKEN_PROGRAM_ID,
@@ -10,6 +12,7 @@ import {
   mintTo,
   getAccount,
   getAssociatedTokenAddress,
+  getOrCreateAssociatedTokenAccount,
 } from "@solana/spl-token";
 
 async function main() {
@@ -18,6 +21,8 @@ async function main() {
   // Configure the client to use the local cluster
   anchor.setProvider(anchor.AnchorProvider.env());
   const program = anchor.workspace.DammV2FeeDistributor as Program<DammV2FeeDistributor>;
+  const mockStreamflow = anchor.workspace.MockStreamflow as Program<MockStreamflow>;
+  const mockCpAmm = anchor.workspace.MockCpAmm as Program<MockCpAmm>;
   const provider = anchor.getProvider();
 
   // Generate test accounts
@@ -63,9 +68,11 @@ async function main() {
   console.log("  Quote Mint:", quoteMint.toString());
   console.log("  Base Mint:", baseMint.toString());
 
-  // Generate pool and position IDs
-  const poolId = Keypair.generate().publicKey;
-  const positionId = Keypair.generate().publicKey;
+  // A mock cp-amm pool stands in for the DAMM v2 pool
+  const pool = Keypair.generate();
+  const position = Keypair.generate();
+  const poolId = pool.publicKey;
+  const positionId = position.publicKey;
 
   console.log("🏊 Pool ID:", poolId.toString());
   console.log("📍 Position ID:", positionId.toString());
@@ -77,20 +84,55 @@ async function main() {
   );
 
   const [investorFeePositionOwnerPda] = PublicKey.findProgramAddressSync(
-    [Buffer.from("vault"), Buffer.from("vault"), Buffer.from("investor_fee_pos_owner")],
+    [Buffer.from("vault"), configPda.toBuffer(), Buffer.from("investor_fee_pos_owner")],
     program.programId
   );
 
   const [programAuthorityPda] = PublicKey.findProgramAddressSync(
-    [Buffer.from("program_authority")],
+    [Buffer.from("program_authority"), configPda.toBuffer()],
     program.programId
   );
 
+  const [poolAuthority] = PublicKey.findProgramAddressSync(
+    [Buffer.from("pool_authority"), poolId.toBuffer()],
+    mockCpAmm.programId
+  );
+  const [quoteVault] = PublicKey.findProgramAddressSync([Buffer.from("quote_vault"), poolId.toBuffer()], mockCpAmm.programId);
+  const [baseVault] = PublicKey.findProgramAddressSync([Buffer.from("base_vault"), poolId.toBuffer()], mockCpAmm.programId);
+
   console.log("🔑 Calculated PDAs:");
   console.log("  Config PDA:", configPda.toString());
   console.log("  Position Owner PDA:", investorFeePositionOwnerPda.toString());
   console.log("  Program Authority PDA:", programAuthorityPda.toString());
 
+  // The honorary position is owned by the config's position owner PDA
+  await mockCpAmm.methods
+    .initializePool(new anchor.BN(1).shln(64), 30)
+    .accounts({
+      payer: payer.publicKey,
+      pool: poolId,
+      poolAuthority,
+      quoteMint,
+      baseMint,
+      quoteVault,
+      baseVault,
+      quoteTokenProgram: TOKEN_PROGRAM_ID,
+      baseTokenProgram: TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    })
+    .signers([payer, pool])
+    .rpc();
+  await mockCpAmm.methods
+    .createPosition(investorFeePositionOwnerPda)
+    .accounts({
+      payer: payer.publicKey,
+      pool: poolId,
+      position: positionId,
+      systemProgram: SystemProgram.programId,
+    })
+    .signers([payer, position])
+    .rpc();
+
   // Step 1: Initialize honorary position
   console.log("\n📝 Step 1: Initializing honorary position...");
   const tickLower = -1000;
@@ -127,10 +169,81 @@ async function main() {
     return;
   }
 
+  // Read investor locks from the mock Streamflow program and pay the remainder to the creator
+  await program.methods
+    .updateConfig({
+      progressRetentionDays: null,
+      crankTipBps: null,
+      crankTipMax: null,
+      lockProvider: { mockStreamflow: {} },
+      lockProgram: mockStreamflow.programId,
+      remainderSplit: null,
+      feeShareSchedule: null,
+      weightingMode: null,
+      maxInvestorShareBps: null,
+      investorCapPolicy: null,
+      usdDailyCap: null,
+      priceFeed: null,
+      oracleProgram: null,
+      maxPriceAgeSecs: null,
+      maxPriceConfidenceBps: null,
+      rollingCaps: null,
+      capOverflowPolicy: null,
+      guardian: null,
+      recoveryAta: null,
+      quoteSweepRecipient: null,
+      baseSweepRecipient: null,
+      baseFeePolicy: null,
+      maxSwapSlippageBps: null,
+      basePriceFeed: null,
+      creatorBaseAta: null,
+      cpAmmProgram: mockCpAmm.programId,
+      creator: creator.publicKey,
+    })
+    .accounts({ authority: payer.publicKey, config: configPda })
+    .signers([payer])
+    .rpc();
+
   // Step 2: Create ATAs and mint tokens
   console.log("\n🪙 Step 2: Setting up token accounts...");
+
+  // Program-wide protocol config: 1% of each claim goes to the protocol fee vault
+  const [protocolConfigPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("protocol_config")],
+    program.programId
+  );
+  const [protocolFeeAuthorityPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("protocol_fee_vault")],
+    program.programId
+  );
+  // Only the program's upgrade authority, the provider wallet, may create it
+  if (!(await provider.connection.getAccountInfo(protocolConfigPda))) {
+    const upgradeAuthority = (provider as anchor.AnchorProvider).wallet.publicKey;
+    const [programData] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+    await program.methods
+      .initializeProtocolConfig(100)
+      .accounts({
+        payer: upgradeAuthority,
+        program: program.programId,
+        programData,
+        protocolConfig: protocolConfigPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+  }
+  const protocolFeeVault = (
+    await getOrCreateAssociatedTokenAccount(provider.connection, payer, quoteMint, protocolFeeAuthorityPda, true)
+  ).address;
   
-  const programQuoteTreasury = await getAssociatedTokenAddress(quoteMint, programAuthorityPda, true);
+  const programQuoteTreasury = (
+    await getOrCreateAssociatedTokenAccount(provider.connection, payer, quoteMint, programAuthorityPda, true)
+  ).address;
+  const programBaseTreasury = (
+    await getOrCreateAssociatedTokenAccount(provider.connection, payer, baseMint, programAuthorityPda, true)
+  ).address;
   const creatorQuoteAta = await getAssociatedTokenAddress(quoteMint, creator.publicKey);
   const investor1QuoteAta = await getAssociatedTokenAddress(quoteMint, investor1.publicKey);
   const investor2QuoteAta = await getAssociatedTokenAddress(quoteMint, investor2.publicKey);
@@ -142,30 +255,95 @@ async function main() {
   await createAccount(provider.connection, payer, quoteMint, investor2.publicKey);
   await createAccount(provider.connection, payer, quoteMint, investor3.publicKey);
 
-  // Mint tokens to program treasury for simulation
-  await mintTo(
-    provider.connection,
-    payer,
-    quoteMint,
-    programQuoteTreasury,
-    payer,
-    10000000 // 10M tokens
-  );
-
-  console.log("✅ Token accounts created and funded");
+  console.log("✅ Token accounts created");
 
   // Step 3: Simulate fee accrual
   console.log("\n💰 Step 3: Simulating fee accrual...");
-  console.log("  Program treasury balance: 10,000,000 tokens");
-  console.log("  (In production, this would come from cp-amm fee claims)");
+  const payerQuoteAta = (
+    await getOrCreateAssociatedTokenAccount(provider.connection, payer, quoteMint, payer.publicKey)
+  ).address;
+  const payerBaseAta = (
+    await getOrCreateAssociatedTokenAccount(provider.connection, payer, baseMint, payer.publicKey)
+  ).address;
+  // Stands in for swaps: deposits quote fees into the pool and credits them to the position
+  const accrueFees = async (quoteAmount: number) => {
+    await mintTo(provider.connection, payer, quoteMint, payerQuoteAta, payer, quoteAmount);
+    await mockCpAmm.methods
+      .accrueFees(new anchor.BN(quoteAmount), new anchor.BN(0))
+      .accounts({
+        funder: payer.publicKey,
+        pool: poolId,
+        position: positionId,
+        funderQuote: payerQuoteAta,
+        funderBase: payerBaseAta,
+        quoteVault,
+        baseVault,
+        quoteMint,
+        baseMint,
+        quoteTokenProgram: TOKEN_PROGRAM_ID,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([payer])
+      .rpc();
+  };
+  await accrueFees(10000000);
+  console.log("  Position fees pending: 10,000,000 tokens, claimed by the day's first crank");
+
+  // The pool accounts the crank's cp-amm claim looks up in remaining accounts
+  const poolAccountMetas = [
+    { pubkey: poolId, isWritable: true, isSigner: false },
+    { pubkey: poolAuthority, isWritable: false, isSigner: false },
+    { pubkey: quoteVault, isWritable: true, isSigner: false },
+    { pubkey: baseVault, isWritable: true, isSigner: false },
+  ];
 
   // Step 4: Run crank distribution
   console.log("\n⚙️ Step 4: Running crank distribution...");
 
   // Create mock stream accounts for investors
-  const stream1 = Keypair.generate().publicKey;
-  const stream2 = Keypair.generate().publicKey;
-  const stream3 = Keypair.generate().publicKey;
+  const streamDataLen = 8 + 8 + 8 + 32 + 32 + 8 + 1 + 8 + 8 + 8 + 8;
+  const createMockStream = async (recipient: PublicKey, initialAllocation: number): Promise<PublicKey> => {
+    const stream = Keypair.generate();
+    const now = Math.floor(Date.now() / 1000);
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.createAccount({
+          fromPubkey: payer.publicKey,
+          newAccountPubkey: stream.publicKey,
+          lamports: await provider.connection.getMinimumBalanceForRentExemption(streamDataLen),
+          space: streamDataLen,
+          programId: mockStreamflow.programId,
+        })
+      ),
+      [payer, stream]
+    );
+    await mockStreamflow.methods
+      .createStream(
+        new anchor.BN(initialAllocation),
+        new anchor.BN(now - 86400),
+        new anchor.BN(now + 86400 * 365),
+        quoteMint,
+        recipient,
+        new anchor.BN(0), // no cliff
+        new anchor.BN(0),
+        new anchor.BN(0), // linear unlock
+        new anchor.BN(0)
+      )
+      .accounts({ payer: payer.publicKey, stream: stream.publicKey, systemProgram: SystemProgram.programId })
+      .signers([payer])
+      .rpc();
+    return stream.publicKey;
+  };
+
+  // Streams are read-only, investor ATAs are written by payouts
+  const pageAccountMetas = (page: { streamPubkey: PublicKey; investorQuoteAta: PublicKey }[]) => [
+    ...page.map((investor) => ({ pubkey: investor.streamPubkey, isWritable: false, isSigner: false })),
+    ...page.map((investor) => ({ pubkey: investor.investorQuoteAta, isWritable: true, isSigner: false })),
+  ];
+
+  const stream1 = await createMockStream(investor1.publicKey, 400000);
+  const stream2 = await createMockStream(investor2.publicKey, 300000);
+  const stream3 = await createMockStream(investor3.publicKey, 300000);
 
   const pageInvestors = [
     {
@@ -180,7 +358,7 @@ async function main() {
       streamPubkey: stream3,
       investorQuoteAta: investor3QuoteAta,
     },
-  ];
+  ].sort((a, b) => a.streamPubkey.toBuffer().compare(b.streamPubkey.toBuffer()));
 
   const y0 = 1000000; // 1M total allocation
   const investorFeeShareBps = 5000; // 50%
@@ -190,34 +368,62 @@ async function main() {
   const currentTime = Math.floor(Date.now() / 1000);
   const dayId = Math.floor(currentTime / 86400);
   const [progressPda] = PublicKey.findProgramAddressSync(
-    [Buffer.from("investor_fee_progress"), Buffer.from(dayId.toString())],
+    [Buffer.from("investor_fee_progress"), configPda.toBuffer(), new anchor.BN(dayId).toArrayLike(Buffer, "le", 8)],
     program.programId
   );
 
   try {
+    // Tally the day's investors first so the day can be finalized right after the crank
+    await program.methods
+      .tallyInvestorPage(pageInvestors)
+      .accounts({
+        cranker: payer.publicKey,
+        config: configPda,
+        progress: progressPda,
+        protocolConfig: protocolConfigPda,
+        lockProgram: mockStreamflow.programId,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts(pageAccountMetas(pageInvestors))
+      .signers([payer])
+      .rpc();
+
     const crankTx = await program.methods
       .crankDistributePage(
         pageInvestors,
         new anchor.BN(y0),
         investorFeeShareBps,
         null, // No daily cap
-        new anchor.BN(minPayoutLamports),
-        true // Final page
+        new anchor.BN(minPayoutLamports)
       )
       .accounts({
+        cranker: payer.publicKey,
         config: configPda,
         progress: progressPda,
         position: positionId,
         investorFeePositionOwnerPda: investorFeePositionOwnerPda,
+        quoteMint: quoteMint,
         programQuoteTreasury: programQuoteTreasury,
         programAuthority: programAuthorityPda,
-        creatorQuoteAta: creatorQuoteAta,
-        creator: creator.publicKey,
-        streamflowProgram: program.programId, // Mock for testing
+        protocolConfig: protocolConfigPda,
+        protocolFeeAuthority: protocolFeeAuthorityPda,
+        protocolFeeVault: protocolFeeVault,
+        priceFeed: null,
+        basePriceFeed: null,
+        pool: poolId,
+        baseMint: baseMint,
+        programBaseTreasury: programBaseTreasury,
+        creatorBaseAta: null,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        capWindow: null,
+        cpAmmProgram: mockCpAmm.programId,
+        lockProgram: mockStreamflow.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
       })
+      .remainingAccounts([...pageAccountMetas(pageInvestors), ...poolAccountMetas])
+      .signers([payer])
       .rpc();
 
     console.log("✅ Crank distribution completed successfully!");
@@ -231,6 +437,37 @@ async function main() {
     console.log("  Carry Over:", progressAccount.carryOver.toString());
     console.log("  Pagination Cursor:", progressAccount.paginationCursor.toString());
 
+    // Close the day: creator remainder + immutable summary
+    const [daySummaryPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("investor_fee_day_summary"), configPda.toBuffer(), new anchor.BN(dayId).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const finalizeTx = await program.methods
+      .finalizeDay(new anchor.BN(dayId))
+      .accounts({
+        payer: payer.publicKey,
+        config: configPda,
+        progress: progressPda,
+        daySummary: daySummaryPda,
+        quoteMint: quoteMint,
+        programQuoteTreasury: programQuoteTreasury,
+        programAuthority: programAuthorityPda,
+        creatorQuoteAta: creatorQuoteAta,
+        creator: creator.publicKey,
+        protocolConfig: protocolConfigPda,
+        capWindow: null,
+        quoteSweepRecipient: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([payer])
+      .rpc();
+
+    const daySummary = await program.account.daySummary.fetch(daySummaryPda);
+    console.log("✅ Day finalized:", finalizeTx);
+    console.log("  Creator Amount:", daySummary.creatorAmount.toString());
+    console.log("  Investors Paid:", daySummary.investorsPaid.toString());
+
   } catch (error) {
     console.error("❌ Failed to run crank distribution:", error);
     return;
@@ -241,14 +478,14 @@ async function main() {
 
   const page1Investors = [
     {
-      streamPubkey: Keypair.generate().publicKey,
+      streamPubkey: await createMockStream(investor1.publicKey, 500000),
       investorQuoteAta: investor1QuoteAta,
     },
   ];
 
   const page2Investors = [
     {
-      streamPubkey: Keypair.generate().publicKey,
+      streamPubkey: await createMockStream(investor2.publicKey, 500000),
       investorQuoteAta: investor2QuoteAta,
     },
   ];
@@ -256,35 +493,49 @@ async function main() {
   // Calculate progress PDA for tomorrow
   const tomorrowDayId = Math.floor(currentTime / 86400) + 1;
   const [tomorrowProgressPda] = PublicKey.findProgramAddressSync(
-    [Buffer.from("investor_fee_progress"), Buffer.from(tomorrowDayId.toString())],
+    [Buffer.from("investor_fee_progress"), configPda.toBuffer(), new anchor.BN(tomorrowDayId).toArrayLike(Buffer, "le", 8)],
     program.programId
   );
 
   try {
-    // First page
+    // First page, claiming the day's fees
+    await accrueFees(10000000);
     const page1Tx = await program.methods
       .crankDistributePage(
         page1Investors,
         new anchor.BN(y0),
         investorFeeShareBps,
         null,
-        new anchor.BN(minPayoutLamports),
-        false // Not final page
+        new anchor.BN(minPayoutLamports)
       )
       .accounts({
+        cranker: payer.publicKey,
         config: configPda,
         progress: tomorrowProgressPda,
         position: positionId,
         investorFeePositionOwnerPda: investorFeePositionOwnerPda,
+        quoteMint: quoteMint,
         programQuoteTreasury: programQuoteTreasury,
         programAuthority: programAuthorityPda,
-        creatorQuoteAta: creatorQuoteAta,
-        creator: creator.publicKey,
-        streamflowProgram: program.programId,
+        protocolConfig: protocolConfigPda,
+        protocolFeeAuthority: protocolFeeAuthorityPda,
+        protocolFeeVault: protocolFeeVault,
+        priceFeed: null,
+        basePriceFeed: null,
+        pool: poolId,
+        baseMint: baseMint,
+        programBaseTreasury: programBaseTreasury,
+        creatorBaseAta: null,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        capWindow: null,
+        cpAmmProgram: mockCpAmm.programId,
+        lockProgram: mockStreamflow.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
       })
+      .remainingAccounts([...pageAccountMetas(page1Investors), ...poolAccountMetas])
+      .signers([payer])
       .rpc();
 
     console.log("✅ Page 1 processed:", page1Tx);
@@ -296,23 +547,36 @@ async function main() {
         new anchor.BN(y0),
         investorFeeShareBps,
         null,
-        new anchor.BN(minPayoutLamports),
-        true // Final page
+        new anchor.BN(minPayoutLamports)
       )
       .accounts({
+        cranker: payer.publicKey,
         config: configPda,
         progress: tomorrowProgressPda,
         position: positionId,
         investorFeePositionOwnerPda: investorFeePositionOwnerPda,
+        quoteMint: quoteMint,
         programQuoteTreasury: programQuoteTreasury,
         programAuthority: programAuthorityPda,
-        creatorQuoteAta: creatorQuoteAta,
-        creator: creator.publicKey,
-        streamflowProgram: program.programId,
+        protocolConfig: protocolConfigPda,
+        protocolFeeAuthority: protocolFeeAuthorityPda,
+        protocolFeeVault: protocolFeeVault,
+        priceFeed: null,
+        basePriceFeed: null,
+        pool: poolId,
+        baseMint: baseMint,
+        programBaseTreasury: programBaseTreasury,
+        creatorBaseAta: null,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        capWindow: null,
+        cpAmmProgram: mockCpAmm.programId,
+        lockProgram: mockStreamflow.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
       })
+      .remainingAccounts(pageAccountMetas(page2Investors))
+      .signers([payer])
       .rpc();
 
     console.log("✅ Page 2 processed:", page2Tx);
@@ -1,7 +1,10 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { DammV2FeeDistributor } from "../target/types/damm_v2_fee_distributor";
-import { PublicKey, Keypair, SystemProgram, SYSVAR_RENT_PUBKEY } from "@solana/web3.js";
+import { MockStreamflow } from "../target/types/mock_streamflow";
+import { MockOracle } from "../target/types/mock_oracle";
+import { MockCpAmm } from "../target/types/mock_cp_amm";
+import { PublicKey, Keypair, SystemProgram, SYSVAR_RENT_PUBKEY, Transaction } from "@solana/web3.js";
 import { 
   TOKEN_PROGRAM_ID, 
   ASSOCIATED_TOKEN_PROGRAM_ID,
@@ -10,16 +13,33 @@ import {
   mintTo,
   getAccount,
   getAssociatedTokenAddress,
+  getOrCreateAssociatedTokenAccount,
+  TOKEN_2022_PROGRAM_ID,
+  ExtensionType,
+  getMintLen,
+  createInitializeMintInstruction,
+  createInitializeNonTransferableMintInstruction,
 } from "@solana/spl-token";
 import { expect } from "chai";
 
+const BPF_LOADER_UPGRADEABLE_PROGRAM_ID = new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111");
+
 describe("damm-v2-fee-distributor", () => {
   // Configure the client to use the local cluster.
   anchor.setProvider(anchor.AnchorProvider.env());
 
   const program = anchor.workspace.DammV2FeeDistributor as Program<DammV2FeeDistributor>;
+  const mockStreamflow = anchor.workspace.MockStreamflow as Program<MockStreamflow>;
+  const mockOracle = anchor.workspace.MockOracle as Program<MockOracle>;
+  const mockCpAmm = anchor.workspace.MockCpAmm as Program<MockCpAmm>;
   const provider = anchor.getProvider();
 
+  // Size of the mock-streamflow `StreamData` account
+  const STREAM_DATA_LEN = 8 + 8 + 8 + 32 + 32 + 8 + 1 + 8 + 8 + 8 + 8;
+
+  // Size of the mock-oracle `PriceFeedData` account
+  const PRICE_FEED_DATA_LEN = 4 + 4 + 4 + 8 + 8 + 8;
+
   // Test accounts
   let payer: Keypair;
   let creator: Keypair;
@@ -31,19 +51,148 @@ describe("damm-v2-fee-distributor", () => {
   let quoteMint: PublicKey;
   let baseMint: PublicKey;
   
-  // Pool and position
+  // Mock cp-amm pool and the honorary position in it
   let poolId: PublicKey;
   let positionId: PublicKey;
+  let poolAuthority: PublicKey;
+  let quoteVault: PublicKey;
+  let baseVault: PublicKey;
   
   // PDAs
   let configPda: PublicKey;
   let investorFeePositionOwnerPda: PublicKey;
   let programAuthorityPda: PublicKey;
   let programQuoteTreasury: PublicKey;
+  let programBaseTreasury: PublicKey;
   let creatorQuoteAta: PublicKey;
   let investor1QuoteAta: PublicKey;
   let investor2QuoteAta: PublicKey;
   let investor3QuoteAta: PublicKey;
+  let protocolConfigPda: PublicKey;
+  let protocolFeeAuthorityPda: PublicKey;
+  let protocolFeeVault: PublicKey;
+
+  // Day ids are seeded as 8-byte little-endian integers
+  const dayIdSeed = (dayId: number) => new anchor.BN(dayId).toArrayLike(Buffer, "le", 8);
+
+  // `update_config` params with every field left unchanged unless overridden
+  const configUpdate = (overrides: Record<string, unknown>) => ({
+    progressRetentionDays: null,
+    crankTipBps: null,
+    crankTipMax: null,
+    lockProvider: null,
+    lockProgram: null,
+    remainderSplit: null,
+    feeShareSchedule: null,
+    weightingMode: null,
+    maxInvestorShareBps: null,
+    investorCapPolicy: null,
+    usdDailyCap: null,
+    priceFeed: null,
+    oracleProgram: null,
+    maxPriceAgeSecs: null,
+    maxPriceConfidenceBps: null,
+    rollingCaps: null,
+    capOverflowPolicy: null,
+    guardian: null,
+    recoveryAta: null,
+    quoteSweepRecipient: null,
+    baseSweepRecipient: null,
+    baseFeePolicy: null,
+    maxSwapSlippageBps: null,
+    basePriceFeed: null,
+    creatorBaseAta: null,
+    cpAmmProgram: null,
+    creator: null,
+    ...overrides,
+  });
+
+  // Streams are read-only, investor ATAs are written by payouts
+  const pageAccountMetas = (page: { streamPubkey: PublicKey; investorQuoteAta: PublicKey }[]) => [
+    ...page.map((investor) => ({ pubkey: investor.streamPubkey, isWritable: false, isSigner: false })),
+    ...page.map((investor) => ({ pubkey: investor.investorQuoteAta, isWritable: true, isSigner: false })),
+  ];
+
+  // The pool accounts cp-amm CPIs look up in remaining accounts
+  const poolAccountMetas = () => [
+    { pubkey: poolId, isWritable: true, isSigner: false },
+    { pubkey: poolAuthority, isWritable: false, isSigner: false },
+    { pubkey: quoteVault, isWritable: true, isSigner: false },
+    { pubkey: baseVault, isWritable: true, isSigner: false },
+  ];
+
+  // Credit the honorary position with quote fees, deposited into the pool's vaults by the payer
+  const accrueFees = async (quoteAmount: number) => {
+    const payerQuoteAta = (
+      await getOrCreateAssociatedTokenAccount(provider.connection, payer, quoteMint, payer.publicKey)
+    ).address;
+    const payerBaseAta = (
+      await getOrCreateAssociatedTokenAccount(provider.connection, payer, baseMint, payer.publicKey)
+    ).address;
+    await mintTo(provider.connection, payer, quoteMint, payerQuoteAta, payer, quoteAmount);
+    await mockCpAmm.methods
+      .accrueFees(new anchor.BN(quoteAmount), new anchor.BN(0))
+      .accounts({
+        funder: payer.publicKey,
+        pool: poolId,
+        position: positionId,
+        funderQuote: payerQuoteAta,
+        funderBase: payerBaseAta,
+        quoteVault,
+        baseVault,
+        quoteMint,
+        baseMint,
+        quoteTokenProgram: TOKEN_PROGRAM_ID,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([payer])
+      .rpc();
+  };
+
+  const createMockStream = async (
+    recipient: PublicKey,
+    initialAllocation: number,
+    startTs: number,
+    endTs: number,
+    schedule = { cliff: 0, cliffAmount: 0, period: 0, amountPerPeriod: 0 }
+  ): Promise<PublicKey> => {
+    const stream = Keypair.generate();
+    const lamports = await provider.connection.getMinimumBalanceForRentExemption(STREAM_DATA_LEN);
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.createAccount({
+          fromPubkey: payer.publicKey,
+          newAccountPubkey: stream.publicKey,
+          lamports,
+          space: STREAM_DATA_LEN,
+          programId: mockStreamflow.programId,
+        })
+      ),
+      [payer, stream]
+    );
+
+    await mockStreamflow.methods
+      .createStream(
+        new anchor.BN(initialAllocation),
+        new anchor.BN(startTs),
+        new anchor.BN(endTs),
+        quoteMint,
+        recipient,
+        new anchor.BN(schedule.cliff),
+        new anchor.BN(schedule.cliffAmount),
+        new anchor.BN(schedule.period),
+        new anchor.BN(schedule.amountPerPeriod)
+      )
+      .accounts({
+        payer: payer.publicKey,
+        stream: stream.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([payer])
+      .rpc();
+
+    return stream.publicKey;
+  };
 
   before(async () => {
     // Generate keypairs
@@ -77,9 +226,11 @@ describe("damm-v2-fee-distributor", () => {
       6
     );
 
-    // Generate pool and position IDs
-    poolId = Keypair.generate().publicKey;
-    positionId = Keypair.generate().publicKey;
+    // A mock cp-amm pool and a position owned by its config's position owner PDA
+    const pool = Keypair.generate();
+    const position = Keypair.generate();
+    poolId = pool.publicKey;
+    positionId = position.publicKey;
 
     // Calculate PDAs
     [configPda] = PublicKey.findProgramAddressSync(
@@ -88,17 +239,63 @@ describe("damm-v2-fee-distributor", () => {
     );
 
     [investorFeePositionOwnerPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("vault"), Buffer.from("vault"), Buffer.from("investor_fee_pos_owner")],
+      [Buffer.from("vault"), configPda.toBuffer(), Buffer.from("investor_fee_pos_owner")],
       program.programId
     );
 
+    [poolAuthority] = PublicKey.findProgramAddressSync(
+      [Buffer.from("pool_authority"), poolId.toBuffer()],
+      mockCpAmm.programId
+    );
+    [quoteVault] = PublicKey.findProgramAddressSync([Buffer.from("quote_vault"), poolId.toBuffer()], mockCpAmm.programId);
+    [baseVault] = PublicKey.findProgramAddressSync([Buffer.from("base_vault"), poolId.toBuffer()], mockCpAmm.programId);
+
+    await mockCpAmm.methods
+      .initializePool(new anchor.BN(1).shln(64), 30)
+      .accounts({
+        payer: payer.publicKey,
+        pool: poolId,
+        poolAuthority,
+        quoteMint,
+        baseMint,
+        quoteVault,
+        baseVault,
+        quoteTokenProgram: TOKEN_PROGRAM_ID,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([payer, pool])
+      .rpc();
+    await mockCpAmm.methods
+      .createPosition(investorFeePositionOwnerPda)
+      .accounts({
+        payer: payer.publicKey,
+        pool: poolId,
+        position: positionId,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([payer, position])
+      .rpc();
+
     [programAuthorityPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("program_authority")],
+      [Buffer.from("program_authority"), configPda.toBuffer()],
+      program.programId
+    );
+
+    [protocolConfigPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("protocol_config")],
+      program.programId
+    );
+
+    [protocolFeeAuthorityPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("protocol_fee_vault")],
       program.programId
     );
 
     // Calculate ATAs
+    protocolFeeVault = await getAssociatedTokenAddress(quoteMint, protocolFeeAuthorityPda, true);
     programQuoteTreasury = await getAssociatedTokenAddress(quoteMint, programAuthorityPda, true);
+    programBaseTreasury = await getAssociatedTokenAddress(baseMint, programAuthorityPda, true);
     creatorQuoteAta = await getAssociatedTokenAddress(quoteMint, creator.publicKey);
     investor1QuoteAta = await getAssociatedTokenAddress(quoteMint, investor1.publicKey);
     investor2QuoteAta = await getAssociatedTokenAddress(quoteMint, investor2.publicKey);
@@ -135,6 +332,14 @@ describe("damm-v2-fee-distributor", () => {
     expect(configAccount.baseMint.toString()).to.equal(baseMint.toString());
     expect(configAccount.tickLower).to.equal(tickLower);
     expect(configAccount.tickUpper).to.equal(tickUpper);
+
+    // Nothing is claimed until the config points at the cp-amm program its position lives in
+    expect(configAccount.cpAmmProgram.toString()).to.equal(PublicKey.default.toString());
+    await program.methods
+      .updateConfig(configUpdate({ cpAmmProgram: mockCpAmm.programId }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
   });
 
   it("Fails to initialize with invalid tick range", async () => {
@@ -143,6 +348,10 @@ describe("damm-v2-fee-distributor", () => {
       [Buffer.from("investor_fee_config"), invalidPoolId.toBuffer()],
       program.programId
     );
+    const [invalidPositionOwnerPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault"), invalidConfigPda.toBuffer(), Buffer.from("investor_fee_pos_owner")],
+      program.programId
+    );
 
     try {
       await program.methods
@@ -154,7 +363,7 @@ describe("damm-v2-fee-distributor", () => {
           quoteMint: quoteMint,
           baseMint: baseMint,
           position: Keypair.generate().publicKey,
-          investorFeePositionOwnerPda: investorFeePositionOwnerPda,
+          investorFeePositionOwnerPda: invalidPositionOwnerPda,
           systemProgram: SystemProgram.programId,
           tokenProgram: TOKEN_PROGRAM_ID,
           rent: SYSVAR_RENT_PUBKEY,
@@ -168,27 +377,241 @@ describe("damm-v2-fee-distributor", () => {
     }
   });
 
+  it("Rejects Token-2022 quote mints with unsupported extensions", async () => {
+    // A non-transferable mint could never be paid out of the treasury
+    const mint = Keypair.generate();
+    const mintLen = getMintLen([ExtensionType.NonTransferable]);
+    const lamports = await provider.connection.getMinimumBalanceForRentExemption(mintLen);
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.createAccount({
+          fromPubkey: payer.publicKey,
+          newAccountPubkey: mint.publicKey,
+          space: mintLen,
+          lamports,
+          programId: TOKEN_2022_PROGRAM_ID,
+        }),
+        createInitializeNonTransferableMintInstruction(mint.publicKey, TOKEN_2022_PROGRAM_ID),
+        createInitializeMintInstruction(mint.publicKey, 6, payer.publicKey, null, TOKEN_2022_PROGRAM_ID)
+      ),
+      [payer, mint]
+    );
+
+    const otherPoolId = Keypair.generate().publicKey;
+    const [otherConfigPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("investor_fee_config"), otherPoolId.toBuffer()],
+      program.programId
+    );
+    const [otherPositionOwnerPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault"), otherConfigPda.toBuffer(), Buffer.from("investor_fee_pos_owner")],
+      program.programId
+    );
+
+    try {
+      await program.methods
+        .initializeHonoraryPosition(otherPoolId, -1000, 1000)
+        .accounts({
+          payer: payer.publicKey,
+          config: otherConfigPda,
+          pool: otherPoolId,
+          quoteMint: mint.publicKey,
+          baseMint: baseMint,
+          position: Keypair.generate().publicKey,
+          investorFeePositionOwnerPda: otherPositionOwnerPda,
+          systemProgram: SystemProgram.programId,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          rent: SYSVAR_RENT_PUBKEY,
+        })
+        .signers([payer])
+        .rpc();
+
+      expect.fail("Should have rejected the non-transferable mint");
+    } catch (error) {
+      expect(error.message).to.include("extension the distributor does not support");
+    }
+  });
+
+  it("Reads investor locks from the configured lock program", async () => {
+    const configBefore = await program.account.configAccount.fetch(configPda);
+    expect(configBefore.lockProvider).to.deep.equal({ mockStreamflow: {} });
+
+    // A new config has no lock program; the real Streamflow layout is not supported, so point it at the mock
+    await program.methods
+      .updateConfig(configUpdate({ lockProvider: { mockStreamflow: {} }, lockProgram: mockStreamflow.programId }))
+      .accounts({
+        authority: payer.publicKey,
+        config: configPda,
+      })
+      .signers([payer])
+      .rpc();
+
+    const configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.lockProgram.toString()).to.equal(mockStreamflow.programId.toString());
+  });
+
+  it("Configures the crank tip", async () => {
+    try {
+      await program.methods
+        .updateConfig(configUpdate({ crankTipBps: 10001 }))
+        .accounts({
+          authority: payer.publicKey,
+          config: configPda,
+        })
+        .signers([payer])
+        .rpc();
+
+      expect.fail("Should have rejected more than 10000 bps");
+    } catch (error) {
+      expect(error.message).to.include("Basis points must not exceed 10000");
+    }
+
+    await program.methods
+      .updateConfig(configUpdate({ crankTipBps: 100, crankTipMax: new anchor.BN(5000) }))
+      .accounts({
+        authority: payer.publicKey,
+        config: configPda,
+      })
+      .signers([payer])
+      .rpc();
+
+    const configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.crankTipBps).to.equal(100);
+    expect(configAccount.crankTipMax.toString()).to.equal("5000");
+  });
+
+  it("Initializes the protocol config", async () => {
+    // Only the upgrade authority the program was deployed with may create the protocol config
+    const upgradeAuthority = (provider as anchor.AnchorProvider).wallet.publicKey;
+    const [programData] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+    );
+
+    try {
+      await program.methods
+        .initializeProtocolConfig(100)
+        .accounts({
+          payer: payer.publicKey,
+          program: program.programId,
+          programData,
+          protocolConfig: protocolConfigPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([payer])
+        .rpc();
+
+      expect.fail("Should have rejected a signer other than the upgrade authority");
+    } catch (error) {
+      expect(error.message).to.include("Signer is not the program's upgrade authority");
+    }
+
+    try {
+      await program.methods
+        .initializeProtocolConfig(1001)
+        .accounts({
+          payer: upgradeAuthority,
+          program: program.programId,
+          programData,
+          protocolConfig: protocolConfigPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      expect.fail("Should have rejected a protocol fee above the cap");
+    } catch (error) {
+      expect(error.message).to.include("Protocol fee exceeds the maximum");
+    }
+
+    // 1% of every claim goes to the protocol fee vault
+    await program.methods
+      .initializeProtocolConfig(100)
+      .accounts({
+        payer: upgradeAuthority,
+        program: program.programId,
+        programData,
+        protocolConfig: protocolConfigPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    // Hand the protocol over to the test payer
+    await program.methods
+      .updateProtocolConfig({ admin: payer.publicKey, protocolFeeBps: null, guardian: payer.publicKey })
+      .accounts({ admin: upgradeAuthority, protocolConfig: protocolConfigPda })
+      .rpc();
+    await getOrCreateAssociatedTokenAccount(provider.connection, payer, quoteMint, protocolFeeAuthorityPda, true);
+
+    const protocolConfig = await program.account.protocolConfig.fetch(protocolConfigPda);
+    expect(protocolConfig.admin.toString()).to.equal(payer.publicKey.toString());
+    expect(protocolConfig.protocolFeeBps).to.equal(100);
+
+    try {
+      await program.methods
+        .updateProtocolConfig({ admin: null, protocolFeeBps: 0, guardian: null })
+        .accounts({ admin: creator.publicKey, protocolConfig: protocolConfigPda })
+        .signers([creator])
+        .rpc();
+
+      expect.fail("Should have failed for a non-admin signer");
+    } catch (error) {
+      expect(error.message).to.include("Signer is not the protocol admin");
+    }
+  });
+
+  it("Unlocks cliff and step streams the same way as the distributor", async () => {
+    const start = 1_700_000_000;
+    const cliff = start + 30 * 86400;
+    const period = 7 * 86400;
+    const stream = await createMockStream(investor1.publicKey, 1_000_000, start, start + 365 * 86400, {
+      cliff,
+      cliffAmount: 250_000,
+      period,
+      amountPerPeriod: 50_000,
+    });
+
+    const lockedAt = async (timestamp: number) =>
+      (
+        await mockStreamflow.methods
+          .getLockedAmount(stream, new anchor.BN(timestamp))
+          .accounts({ stream })
+          .view()
+      ).toString();
+
+    // Fully locked until the cliff, cliff amount unlocks at once, then one step per period
+    expect(await lockedAt(cliff - 1)).to.equal("1000000");
+    expect(await lockedAt(cliff)).to.equal("750000");
+    expect(await lockedAt(cliff + period - 1)).to.equal("750000");
+    expect(await lockedAt(cliff + 2 * period)).to.equal("650000");
+    expect(await lockedAt(start + 365 * 86400)).to.equal("0");
+  });
+
   it("Runs crank distribution successfully", async () => {
     // Create ATAs first
     await createAccount(provider.connection, payer, quoteMint, creator.publicKey);
     await createAccount(provider.connection, payer, quoteMint, investor1.publicKey);
     await createAccount(provider.connection, payer, quoteMint, investor2.publicKey);
     await createAccount(provider.connection, payer, quoteMint, investor3.publicKey);
+    await createAccount(provider.connection, payer, quoteMint, payer.publicKey);
+    const crankerQuoteAta = await getAssociatedTokenAddress(quoteMint, payer.publicKey);
 
-    // Mint some tokens to the program treasury for testing
-    await mintTo(
-      provider.connection,
-      payer,
-      quoteMint,
-      programQuoteTreasury,
-      payer,
-      1000000 // 1M tokens
-    );
+    // The crank claims the position's fees into the program treasuries
+    await getOrCreateAssociatedTokenAccount(provider.connection, payer, quoteMint, programAuthorityPda, true);
+    await getOrCreateAssociatedTokenAccount(provider.connection, payer, baseMint, programAuthorityPda, true);
+    await accrueFees(1000000);
 
     // Create mock stream accounts for investors
-    const stream1 = Keypair.generate().publicKey;
-    const stream2 = Keypair.generate().publicKey;
-    const stream3 = Keypair.generate().publicKey;
+    const now = Math.floor(Date.now() / 1000);
+    const stream1 = await createMockStream(investor1.publicKey, 400000, now - 86400, now + 86400 * 365);
+    const stream2 = await createMockStream(investor2.publicKey, 400000, now - 86400, now + 86400 * 365);
+    const stream3 = await createMockStream(investor3.publicKey, 200000, now - 86400, now + 86400 * 365);
+
+    // Investor 3's stream was cancelled before today's snapshot, so their allocation
+    // no longer counts as locked
+    const dayStart = Math.floor(now / 86400) * 86400;
+    await mockStreamflow.methods
+      .cancelStream(new anchor.BN(dayStart - 60))
+      .accounts({ stream: stream3 })
+      .rpc();
 
     const pageInvestors = [
       {
@@ -203,7 +626,7 @@ describe("damm-v2-fee-distributor", () => {
         streamPubkey: stream3,
         investorQuoteAta: investor3QuoteAta,
       },
-    ];
+    ].sort((a, b) => a.streamPubkey.toBuffer().compare(b.streamPubkey.toBuffer()));
 
     const y0 = 1000000; // 1M total allocation
     const investorFeeShareBps = 5000; // 50%
@@ -214,33 +637,62 @@ describe("damm-v2-fee-distributor", () => {
     const currentTime = Math.floor(Date.now() / 1000);
     const dayId = Math.floor(currentTime / 86400);
     const [progressPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("investor_fee_progress"), Buffer.from(dayId.toString())],
+      [Buffer.from("investor_fee_progress"), configPda.toBuffer(), dayIdSeed(dayId)],
       program.programId
     );
 
+    // Tally the day's investors, in stream key order, so the day can be finalized before it is over
+    await program.methods
+      .tallyInvestorPage(pageInvestors)
+      .accounts({
+        cranker: payer.publicKey,
+        config: configPda,
+        progress: progressPda,
+        protocolConfig: protocolConfigPda,
+        lockProgram: mockStreamflow.programId,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts(pageAccountMetas(pageInvestors))
+      .signers([payer])
+      .rpc();
+
     const tx = await program.methods
       .crankDistributePage(
         pageInvestors,
         new anchor.BN(y0),
         investorFeeShareBps,
         null, // No daily cap
-        new anchor.BN(minPayoutLamports),
-        true // Final page
+        new anchor.BN(minPayoutLamports)
       )
       .accounts({
+        cranker: payer.publicKey,
         config: configPda,
         progress: progressPda,
         position: positionId,
         investorFeePositionOwnerPda: investorFeePositionOwnerPda,
+        quoteMint: quoteMint,
         programQuoteTreasury: programQuoteTreasury,
         programAuthority: programAuthorityPda,
-        creatorQuoteAta: creatorQuoteAta,
-        creator: creator.publicKey,
-        streamflowProgram: program.programId, // Mock for testing
+        crankerQuoteAta: crankerQuoteAta,
+        protocolConfig: protocolConfigPda,
+        protocolFeeAuthority: protocolFeeAuthorityPda,
+        protocolFeeVault: protocolFeeVault,
+        priceFeed: null,
+        basePriceFeed: null,
+        pool: poolId,
+        baseMint: baseMint,
+        programBaseTreasury: programBaseTreasury,
+        creatorBaseAta: null,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        capWindow: null,
+        cpAmmProgram: mockCpAmm.programId,
+        lockProgram: mockStreamflow.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
       })
+      .remainingAccounts([...pageAccountMetas(pageInvestors), ...poolAccountMetas()])
+      .signers([payer])
       .rpc();
 
     console.log("Crank distribution transaction signature:", tx);
@@ -249,20 +701,38 @@ describe("damm-v2-fee-distributor", () => {
     const progressAccount = await program.account.progressAccount.fetch(progressPda);
     expect(progressAccount.dayId.toString()).to.equal(dayId.toString());
     expect(progressAccount.claimedQuoteForDay.toString()).to.equal("1000000");
+
+    // Locks are evaluated at the start of the day, not when each page runs
+    expect(progressAccount.snapshotTs.toString()).to.equal((dayId * 86400).toString());
+
+    // 1% of 1M is 10K, capped at 5K
+    expect(progressAccount.crankTip.toString()).to.equal("5000");
+
+    // The protocol fee comes off the top of the claim
+    expect(progressAccount.protocolFee.toString()).to.equal("10000");
+    const protocolFeeVaultAccount = await getAccount(provider.connection, protocolFeeVault);
+    expect(protocolFeeVaultAccount.amount.toString()).to.equal("10000");
+    const crankerAccount = await getAccount(provider.connection, crankerQuoteAta);
+    expect(crankerAccount.amount.toString()).to.equal("5000");
+
+    // Nothing is paid against a cancelled stream
+    const investor3Account = await getAccount(provider.connection, investor3QuoteAta);
+    expect(investor3Account.amount.toString()).to.equal("0");
   });
 
   it("Handles pagination correctly", async () => {
     // Test with multiple pages
+    const now = Math.floor(Date.now() / 1000);
     const page1Investors = [
       {
-        streamPubkey: Keypair.generate().publicKey,
+        streamPubkey: await createMockStream(investor1.publicKey, 500000, now - 86400, now + 86400 * 365),
         investorQuoteAta: investor1QuoteAta,
       },
     ];
 
     const page2Investors = [
       {
-        streamPubkey: Keypair.generate().publicKey,
+        streamPubkey: await createMockStream(investor2.publicKey, 500000, now - 86400, now + 86400 * 365),
         investorQuoteAta: investor2QuoteAta,
       },
     ];
@@ -275,34 +745,48 @@ describe("damm-v2-fee-distributor", () => {
     const currentTime = Math.floor(Date.now() / 1000);
     const tomorrowDayId = Math.floor(currentTime / 86400) + 1;
     const [progressPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("investor_fee_progress"), Buffer.from(tomorrowDayId.toString())],
+      [Buffer.from("investor_fee_progress"), configPda.toBuffer(), dayIdSeed(tomorrowDayId)],
       program.programId
     );
 
-    // First page
+    // First page, claiming the day's fees
+    await accrueFees(1000000);
     await program.methods
       .crankDistributePage(
         page1Investors,
         new anchor.BN(y0),
         investorFeeShareBps,
         null,
-        new anchor.BN(minPayoutLamports),
-        false // Not final page
+        new anchor.BN(minPayoutLamports)
       )
       .accounts({
+        cranker: payer.publicKey,
         config: configPda,
         progress: progressPda,
         position: positionId,
         investorFeePositionOwnerPda: investorFeePositionOwnerPda,
+        quoteMint: quoteMint,
         programQuoteTreasury: programQuoteTreasury,
         programAuthority: programAuthorityPda,
-        creatorQuoteAta: creatorQuoteAta,
-        creator: creator.publicKey,
-        streamflowProgram: program.programId,
+        protocolConfig: protocolConfigPda,
+        protocolFeeAuthority: protocolFeeAuthorityPda,
+        protocolFeeVault: protocolFeeVault,
+        priceFeed: null,
+        basePriceFeed: null,
+        pool: poolId,
+        baseMint: baseMint,
+        programBaseTreasury: programBaseTreasury,
+        creatorBaseAta: null,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        capWindow: null,
+        cpAmmProgram: mockCpAmm.programId,
+        lockProgram: mockStreamflow.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
       })
+      .remainingAccounts([...pageAccountMetas(page1Investors), ...poolAccountMetas()])
+      .signers([payer])
       .rpc();
 
     // Second page (final)
@@ -312,23 +796,36 @@ describe("damm-v2-fee-distributor", () => {
         new anchor.BN(y0),
         investorFeeShareBps,
         null,
-        new anchor.BN(minPayoutLamports),
-        true // Final page
+        new anchor.BN(minPayoutLamports)
       )
       .accounts({
+        cranker: payer.publicKey,
         config: configPda,
         progress: progressPda,
         position: positionId,
         investorFeePositionOwnerPda: investorFeePositionOwnerPda,
+        quoteMint: quoteMint,
         programQuoteTreasury: programQuoteTreasury,
         programAuthority: programAuthorityPda,
-        creatorQuoteAta: creatorQuoteAta,
-        creator: creator.publicKey,
-        streamflowProgram: program.programId,
+        protocolConfig: protocolConfigPda,
+        protocolFeeAuthority: protocolFeeAuthorityPda,
+        protocolFeeVault: protocolFeeVault,
+        priceFeed: null,
+        basePriceFeed: null,
+        pool: poolId,
+        baseMint: baseMint,
+        programBaseTreasury: programBaseTreasury,
+        creatorBaseAta: null,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        capWindow: null,
+        cpAmmProgram: mockCpAmm.programId,
+        lockProgram: mockStreamflow.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
       })
+      .remainingAccounts(pageAccountMetas(page2Investors))
+      .signers([payer])
       .rpc();
 
     // Verify progress account shows both pages processed
@@ -336,12 +833,104 @@ describe("damm-v2-fee-distributor", () => {
     expect(progressAccount.paginationCursor.toString()).to.equal("2");
   });
 
+  it("Finalizes the day and records a summary", async () => {
+    const currentTime = Math.floor(Date.now() / 1000);
+    const dayId = Math.floor(currentTime / 86400);
+    const [progressPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("investor_fee_progress"), configPda.toBuffer(), dayIdSeed(dayId)],
+      program.programId
+    );
+    const [daySummaryPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("investor_fee_day_summary"), configPda.toBuffer(), dayIdSeed(dayId)],
+      program.programId
+    );
+
+    // Split the creator remainder 70/30 with a marketing wallet
+    const marketing = Keypair.generate();
+    const marketingQuoteAta = await createAccount(provider.connection, payer, quoteMint, marketing.publicKey);
+
+    try {
+      await program.methods
+        .updateConfig(configUpdate({
+          remainderSplit: { recipients: [{ recipient: creatorQuoteAta, bps: 7000 }, { recipient: marketingQuoteAta, bps: 2000 }] },
+        }))
+        .accounts({ authority: payer.publicKey, config: configPda })
+        .signers([payer])
+        .rpc();
+
+      expect.fail("Should have rejected a split that does not sum to 10000");
+    } catch (error) {
+      expect(error.message).to.include("Remainder split needs at most 8 recipients");
+    }
+
+    await program.methods
+      .updateConfig(configUpdate({
+        remainderSplit: { recipients: [{ recipient: creatorQuoteAta, bps: 7000 }, { recipient: marketingQuoteAta, bps: 3000 }] },
+      }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+
+    // The remainder only goes to the creator recorded on the config
+    await program.methods
+      .updateConfig(configUpdate({ creator: creator.publicKey }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+
+    const creatorBefore = await getAccount(provider.connection, creatorQuoteAta);
+
+    await program.methods
+      .finalizeDay(new anchor.BN(dayId))
+      .accounts({
+        payer: payer.publicKey,
+        config: configPda,
+        progress: progressPda,
+        daySummary: daySummaryPda,
+        quoteMint: quoteMint,
+        programQuoteTreasury: programQuoteTreasury,
+        programAuthority: programAuthorityPda,
+        creatorQuoteAta: creatorQuoteAta,
+        creator: creator.publicKey,
+        protocolConfig: protocolConfigPda,
+        capWindow: null,
+        quoteSweepRecipient: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts([{ pubkey: marketingQuoteAta, isWritable: true, isSigner: false }])
+      .signers([payer])
+      .rpc();
+
+    const summary = await program.account.daySummary.fetch(daySummaryPda);
+    const creatorAfter = await getAccount(provider.connection, creatorQuoteAta);
+    const creatorReceived = creatorAfter.amount - creatorBefore.amount;
+    const marketingReceived = (await getAccount(provider.connection, marketingQuoteAta)).amount;
+
+    expect(summary.dayId.toString()).to.equal(dayId.toString());
+    expect(summary.creatorAmount.toString()).to.equal((creatorReceived + marketingReceived).toString());
+    // The creator's share rounds down; the last recipient absorbs the dust
+    const expectedCreatorShare = summary.creatorAmount.muln(7000).divn(10000);
+    expect(creatorReceived.toString()).to.equal(expectedCreatorShare.toString());
+    expect(
+      summary.distributedToInvestors
+        .add(summary.creatorAmount)
+        .add(summary.dustCarried)
+        .add(summary.crankTip)
+        .add(summary.protocolFee)
+        .toString()
+    ).to.equal(summary.claimedQuote.toString());
+
+    const progressAccount = await program.account.progressAccount.fetch(progressPda);
+    expect(progressAccount.finalized).to.equal(true);
+  });
+
   it("Enforces 24-hour gate", async () => {
     // Try to run crank again immediately (should fail)
     const currentTime = Math.floor(Date.now() / 1000);
     const dayId = Math.floor(currentTime / 86400);
     const [progressPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("investor_fee_progress"), Buffer.from(dayId.toString())],
+      [Buffer.from("investor_fee_progress"), configPda.toBuffer(), dayIdSeed(dayId)],
       program.programId
     );
 
@@ -352,28 +941,882 @@ describe("damm-v2-fee-distributor", () => {
           new anchor.BN(1000000),
           5000,
           null,
-          new anchor.BN(1000),
-          true
+          new anchor.BN(1000)
         )
         .accounts({
+          cranker: payer.publicKey,
           config: configPda,
           progress: progressPda,
           position: positionId,
           investorFeePositionOwnerPda: investorFeePositionOwnerPda,
+          quoteMint: quoteMint,
           programQuoteTreasury: programQuoteTreasury,
           programAuthority: programAuthorityPda,
-          creatorQuoteAta: creatorQuoteAta,
-          creator: creator.publicKey,
-          streamflowProgram: program.programId,
+          protocolConfig: protocolConfigPda,
+          protocolFeeAuthority: protocolFeeAuthorityPda,
+          protocolFeeVault: protocolFeeVault,
+          priceFeed: null,
+          basePriceFeed: null,
+          pool: poolId,
+          baseMint: baseMint,
+          programBaseTreasury: programBaseTreasury,
+          creatorBaseAta: null,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          capWindow: null,
+          cpAmmProgram: mockCpAmm.programId,
+          lockProgram: mockStreamflow.programId,
           tokenProgram: TOKEN_PROGRAM_ID,
           associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
           systemProgram: SystemProgram.programId,
         })
+        .signers([payer])
         .rpc();
 
       expect.fail("Should have failed due to day gate");
     } catch (error) {
-      expect(error.message).to.include("Day gate not passed");
+      expect(error.message).to.include("Day already finalized");
     }
   });
-});
\ No newline at end of file
+
+  it("Records the cranker as the progress account payer", async () => {
+    const dayId = Math.floor(Math.floor(Date.now() / 1000) / 86400);
+    const [progressPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("investor_fee_progress"), configPda.toBuffer(), dayIdSeed(dayId)],
+      program.programId
+    );
+
+    const progressAccount = await program.account.progressAccount.fetch(progressPda);
+    expect(progressAccount.payer.toString()).to.equal(payer.publicKey.toString());
+  });
+
+  it("Refuses to close progress inside the retention period", async () => {
+    const dayId = Math.floor(Math.floor(Date.now() / 1000) / 86400);
+    const [progressPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("investor_fee_progress"), configPda.toBuffer(), dayIdSeed(dayId)],
+      program.programId
+    );
+    const [progressHistoryPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("investor_fee_progress_history"), configPda.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .initializeProgressHistory()
+      .accounts({
+        payer: payer.publicKey,
+        config: configPda,
+        progressHistory: progressHistoryPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([payer])
+      .rpc();
+
+    try {
+      await program.methods
+        .closeProgress(new anchor.BN(dayId))
+        .accounts({
+          config: configPda,
+          protocolConfig: protocolConfigPda,
+          progress: progressPda,
+          payer: payer.publicKey,
+          progressHistory: progressHistoryPda,
+        })
+        .rpc();
+
+      expect.fail("Should have failed inside the retention period");
+    } catch (error) {
+      expect(error.message).to.include("Progress retention period has not elapsed");
+    }
+  });
+
+  it("Only the config authority can change the retention period", async () => {
+    try {
+      await program.methods
+        .updateConfig(configUpdate({ progressRetentionDays: 0 }))
+        .accounts({
+          authority: creator.publicKey,
+          config: configPda,
+        })
+        .signers([creator])
+        .rpc();
+
+      expect.fail("Should have failed for a non-authority signer");
+    } catch (error) {
+      expect(error.message).to.include("Signer is not the config authority");
+    }
+
+    await program.methods
+      .updateConfig(configUpdate({ progressRetentionDays: 7 }))
+      .accounts({
+        authority: payer.publicKey,
+        config: configPda,
+      })
+      .signers([payer])
+      .rpc();
+
+    const configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.progressRetentionDays).to.equal(7);
+  });
+
+  it("Registers additional honorary positions", async () => {
+    const secondPositionKp = Keypair.generate();
+    const secondPosition = secondPositionKp.publicKey;
+    await mockCpAmm.methods
+      .createPosition(investorFeePositionOwnerPda)
+      .accounts({
+        payer: payer.publicKey,
+        pool: poolId,
+        position: secondPosition,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([payer, secondPositionKp])
+      .rpc();
+
+    await program.methods
+      .addHonoraryPosition(poolId, -2000, -1000)
+      .accounts({
+        authority: payer.publicKey,
+        config: configPda,
+        position: secondPosition,
+        investorFeePositionOwnerPda: investorFeePositionOwnerPda,
+        cpAmmProgram: mockCpAmm.programId,
+      })
+      .signers([payer])
+      .rpc();
+
+    const configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.additionalPositions.length).to.equal(1);
+    expect(configAccount.additionalPositions[0].position.toString()).to.equal(secondPosition.toString());
+
+    // The same position cannot be claimed twice
+    try {
+      await program.methods
+        .addHonoraryPosition(poolId, -2000, -1000)
+        .accounts({
+          authority: payer.publicKey,
+          config: configPda,
+          position: secondPosition,
+          investorFeePositionOwnerPda: investorFeePositionOwnerPda,
+          cpAmmProgram: mockCpAmm.programId,
+        })
+        .signers([payer])
+        .rpc();
+
+      expect.fail("Should have rejected a duplicate position");
+    } catch (error) {
+      expect(error.message).to.include("Honorary position is already registered");
+    }
+  });
+
+  it("Configures a decaying investor fee share schedule", async () => {
+    const month = 30 * 86400;
+
+    try {
+      await program.methods
+        .updateConfig(configUpdate({
+          feeShareSchedule: {
+            points: [
+              { offsetSeconds: new anchor.BN(11 * month), shareBps: 2000 },
+              { offsetSeconds: new anchor.BN(0), shareBps: 8000 },
+            ],
+          },
+        }))
+        .accounts({ authority: payer.publicKey, config: configPda })
+        .signers([payer])
+        .rpc();
+
+      expect.fail("Should have rejected out-of-order schedule points");
+    } catch (error) {
+      expect(error.message).to.include("Fee share schedule needs at most 12 points");
+    }
+
+    // 80% in month 1, decaying linearly to 20% by month 12
+    await program.methods
+      .updateConfig(configUpdate({
+        feeShareSchedule: {
+          points: [
+            { offsetSeconds: new anchor.BN(month), shareBps: 8000 },
+            { offsetSeconds: new anchor.BN(11 * month), shareBps: 2000 },
+          ],
+        },
+      }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+
+    const configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.feeShareSchedule.points.length).to.equal(2);
+    expect(configAccount.feeShareSchedule.points[1].shareBps).to.equal(2000);
+  });
+
+  it("Selects the payout weighting mode", async () => {
+    const before = await program.account.configAccount.fetch(configPda);
+    expect(before.weightingMode).to.deep.equal({ lockedAmount: {} });
+
+    await program.methods
+      .updateConfig(configUpdate({ weightingMode: { sqrtLocked: {} } }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+
+    const after = await program.account.configAccount.fetch(configPda);
+    expect(after.weightingMode).to.deep.equal({ sqrtLocked: {} });
+  });
+
+  it("Caps any one investor's share of the day's pool", async () => {
+    try {
+      await program.methods
+        .updateConfig(configUpdate({ maxInvestorShareBps: 100 }))
+        .accounts({ authority: payer.publicKey, config: configPda })
+        .signers([payer])
+        .rpc();
+
+      expect.fail("Should have rejected a cap below the minimum");
+    } catch (error) {
+      expect(error.message).to.include("Per-investor cap must be 0 or between 500 and 10000 bps");
+    }
+
+    // No investor may take more than 10% of the day's investor pool
+    await program.methods
+      .updateConfig(configUpdate({ maxInvestorShareBps: 1000, investorCapPolicy: { redistribute: {} } }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+
+    const configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.maxInvestorShareBps).to.equal(1000);
+
+    // Today's payouts have already started, so the tally is closed
+    const dayId = Math.floor(Math.floor(Date.now() / 1000) / 86400);
+    const [progressPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("investor_fee_progress"), configPda.toBuffer(), dayIdSeed(dayId)],
+      program.programId
+    );
+    try {
+      await program.methods
+        .tallyInvestorPage([])
+        .accounts({
+          cranker: payer.publicKey,
+          config: configPda,
+          progress: progressPda,
+          protocolConfig: protocolConfigPda,
+          lockProgram: mockStreamflow.programId,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([payer])
+        .rpc();
+
+      expect.fail("Should have rejected a tally after payouts started");
+    } catch (error) {
+      expect(error.message).to.include("Investor tally is closed");
+    }
+  });
+
+  it("Configures a USD daily cap priced through the oracle", async () => {
+    const priceFeed = Keypair.generate();
+    const lamports = await provider.connection.getMinimumBalanceForRentExemption(PRICE_FEED_DATA_LEN);
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.createAccount({
+          fromPubkey: payer.publicKey,
+          newAccountPubkey: priceFeed.publicKey,
+          lamports,
+          space: PRICE_FEED_DATA_LEN,
+          programId: mockOracle.programId,
+        })
+      ),
+      [payer, priceFeed]
+    );
+
+    // $1.00 per quote token, +/- $0.001, published now
+    const now = Math.floor(Date.now() / 1000);
+    await mockOracle.methods
+      .setPrice(new anchor.BN(100_000_000), new anchor.BN(100_000), -8, new anchor.BN(now))
+      .accounts({ priceFeed: priceFeed.publicKey })
+      .rpc();
+
+    const feedData = (await provider.connection.getAccountInfo(priceFeed.publicKey)).data;
+    expect(feedData.readUInt32LE(0)).to.equal(0xa1b2c3d4);
+    expect(feedData.readInt32LE(8)).to.equal(-8);
+
+    try {
+      await program.methods
+        .updateConfig(configUpdate({ maxPriceConfidenceBps: 10001 }))
+        .accounts({ authority: payer.publicKey, config: configPda })
+        .signers([payer])
+        .rpc();
+
+      expect.fail("Should have rejected a confidence bound above 10000 bps");
+    } catch (error) {
+      expect(error.message).to.include("Basis points must not exceed 10000");
+    }
+
+    // Investors may receive at most $1,000 a day
+    await program.methods
+      .updateConfig(configUpdate({
+        usdDailyCap: new anchor.BN(1_000_000_000),
+        priceFeed: priceFeed.publicKey,
+        oracleProgram: mockOracle.programId,
+        maxPriceAgeSecs: 120,
+      }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+
+    const configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.usdDailyCap.toString()).to.equal("1000000000");
+    expect(configAccount.priceFeed.toString()).to.equal(priceFeed.publicKey.toString());
+    expect(configAccount.oracleProgram.toString()).to.equal(mockOracle.programId.toString());
+    expect(configAccount.maxPriceAgeSecs).to.equal(120);
+    expect(configAccount.maxPriceConfidenceBps).to.equal(200);
+
+    // Switch the USD cap back off so later cranks need no price feed
+    await program.methods
+      .updateConfig(configUpdate({ usdDailyCap: new anchor.BN(0) }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+  });
+
+  it("Enforces rolling 7- and 30-day caps through the cap window", async () => {
+    const [capWindowPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("cap_window"), configPda.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .initializeCapWindow()
+      .accounts({
+        payer: payer.publicKey,
+        config: configPda,
+        capWindow: capWindowPda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([payer])
+      .rpc();
+
+    const capWindow = await program.account.capWindow.fetch(capWindowPda);
+    expect(capWindow.config.toString()).to.equal(configPda.toString());
+    expect(capWindow.slots.length).to.equal(30);
+    expect(capWindow.heldBack.toNumber()).to.equal(0);
+
+    try {
+      await program.methods
+        .updateConfig(configUpdate({ rollingCaps: { caps: [{ windowDays: 31, cap: new anchor.BN(1) }] } }))
+        .accounts({ authority: payer.publicKey, config: configPda })
+        .signers([payer])
+        .rpc();
+
+      expect.fail("Should have rejected a window longer than 30 days");
+    } catch (error) {
+      expect(error.message).to.include("Rolling caps need at most 4 entries");
+    }
+
+    await program.methods
+      .updateConfig(configUpdate({
+        rollingCaps: {
+          caps: [
+            { windowDays: 7, cap: new anchor.BN(5_000_000) },
+            { windowDays: 30, cap: new anchor.BN(15_000_000) },
+          ],
+        },
+        capOverflowPolicy: { carryForward: {} },
+      }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+
+    const configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.rollingCaps.caps.map((cap) => cap.windowDays)).to.deep.equal([7, 30]);
+    expect(configAccount.capOverflowPolicy).to.deep.equal({ carryForward: {} });
+
+    // Clear the caps so later cranks run without the cap window
+    await program.methods
+      .updateConfig(configUpdate({ rollingCaps: { caps: [] }, capOverflowPolicy: { creator: {} } }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+  });
+
+  it("Holds back daily-cap overflow for later days", async () => {
+    const [capWindowPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("cap_window"), configPda.toBuffer()],
+      program.programId
+    );
+
+    // With no rolling caps the policy governs the excess over the daily cap alone
+    await program.methods
+      .updateConfig(configUpdate({ capOverflowPolicy: { carryForward: {} } }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+
+    const configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.rollingCaps.caps.length).to.equal(0);
+    expect(configAccount.capOverflowPolicy).to.deep.equal({ carryForward: {} });
+
+    // Nothing has been held back yet, so there is nothing to release
+    const capWindow = await program.account.capWindow.fetch(capWindowPda);
+    expect(capWindow.heldBack.toNumber()).to.equal(0);
+    expect(capWindow.retained.toNumber()).to.equal(0);
+
+    await program.methods
+      .updateConfig(configUpdate({ capOverflowPolicy: { creator: {} } }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+  });
+
+  it("Lets only the guardian pause claims and payouts", async () => {
+    const PAUSE_CLAIM = 1 << 0;
+    const PAUSE_INVESTOR_PAYOUTS = 1 << 1;
+
+    try {
+      await program.methods
+        .setConfigPaused(PAUSE_CLAIM)
+        .accounts({ guardian: creator.publicKey, config: configPda })
+        .signers([creator])
+        .rpc();
+
+      expect.fail("Should have failed for a non-guardian signer");
+    } catch (error) {
+      expect(error.message).to.include("Signer is not the guardian");
+    }
+
+    try {
+      await program.methods
+        .setProtocolPaused(1 << 3)
+        .accounts({ guardian: payer.publicKey, protocolConfig: protocolConfigPda })
+        .signers([payer])
+        .rpc();
+
+      expect.fail("Should have rejected an unknown pause bit");
+    } catch (error) {
+      expect(error.message).to.include("Pause flags may only set");
+    }
+
+    // The initializer is the config's guardian by default
+    await program.methods
+      .setConfigPaused(PAUSE_CLAIM | PAUSE_INVESTOR_PAYOUTS)
+      .accounts({ guardian: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+
+    let configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.guardian.toString()).to.equal(payer.publicKey.toString());
+    expect(configAccount.paused).to.equal(PAUSE_CLAIM | PAUSE_INVESTOR_PAYOUTS);
+
+    // Hand the guardian role to the creator, who lifts the pause
+    await program.methods
+      .updateConfig(configUpdate({ guardian: creator.publicKey }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+    await program.methods
+      .setConfigPaused(0)
+      .accounts({ guardian: creator.publicKey, config: configPda })
+      .signers([creator])
+      .rpc();
+
+    configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.paused).to.equal(0);
+
+    const protocolConfig = await program.account.protocolConfig.fetch(protocolConfigPda);
+    expect(protocolConfig.guardian.toString()).to.equal(payer.publicKey.toString());
+    expect(protocolConfig.paused).to.equal(0);
+  });
+
+  it("Allows an emergency withdrawal only after a full pause and the timelock", async () => {
+    const PAUSE_ALL = 0b111;
+    await getOrCreateAssociatedTokenAccount(provider.connection, payer, quoteMint, programAuthorityPda, true);
+
+    // Register the creator's quote ATA as the recovery account while the config runs
+    await program.methods
+      .updateConfig(configUpdate({ recoveryAta: creatorQuoteAta }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+
+    const emergencyWithdraw = () =>
+      program.methods
+        .emergencyWithdraw(1)
+        .accounts({
+          authority: payer.publicKey,
+          config: configPda,
+          quoteMint: quoteMint,
+          programQuoteTreasury: programQuoteTreasury,
+          programAuthority: programAuthorityPda,
+          recoveryAta: creatorQuoteAta,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([payer])
+        .rpc();
+
+    try {
+      await emergencyWithdraw();
+      expect.fail("Should have required the config to be paused");
+    } catch (error) {
+      expect(error.message).to.include("requires every stage of the config to be paused");
+    }
+
+    // The creator became the guardian in the pause test
+    await program.methods
+      .setConfigPaused(PAUSE_ALL)
+      .accounts({ guardian: creator.publicKey, config: configPda })
+      .signers([creator])
+      .rpc();
+
+    const configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.recoveryAta.toString()).to.equal(creatorQuoteAta.toString());
+    expect(configAccount.fullyPausedAt.toNumber()).to.be.greaterThan(0);
+
+    try {
+      await emergencyWithdraw();
+      expect.fail("Should have enforced the timelock");
+    } catch (error) {
+      expect(error.message).to.include("Emergency withdrawal timelock has not elapsed");
+    }
+
+    try {
+      await program.methods
+        .updateConfig(configUpdate({ recoveryAta: investor1QuoteAta }))
+        .accounts({ authority: payer.publicKey, config: configPda })
+        .signers([payer])
+        .rpc();
+
+      expect.fail("Should have frozen the recovery account while paused");
+    } catch (error) {
+      expect(error.message).to.include("Instruction is paused");
+    }
+
+    await program.methods
+      .setConfigPaused(0)
+      .accounts({ guardian: creator.publicKey, config: configPda })
+      .signers([creator])
+      .rpc();
+  });
+
+  it("Registers decommission sweep recipients and guards decommissioning", async () => {
+    const creatorBaseAta = await getAssociatedTokenAddress(baseMint, creator.publicKey);
+
+    await program.methods
+      .updateConfig(configUpdate({ quoteSweepRecipient: creatorQuoteAta, baseSweepRecipient: creatorBaseAta }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+
+    const configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.quoteSweepRecipient.toString()).to.equal(creatorQuoteAta.toString());
+    expect(configAccount.baseSweepRecipient.toString()).to.equal(creatorBaseAta.toString());
+    expect(configAccount.closed).to.equal(false);
+
+    const dayId = Math.floor(Math.floor(Date.now() / 1000) / 86400);
+    const [progressPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("investor_fee_progress"), configPda.toBuffer(), dayIdSeed(dayId)],
+      program.programId
+    );
+    try {
+      await program.methods
+        .decommission(new anchor.BN(dayId))
+        .accounts({
+          authority: creator.publicKey,
+          config: configPda,
+          protocolConfig: protocolConfigPda,
+          progress: progressPda,
+          position: positionId,
+          investorFeePositionOwnerPda: investorFeePositionOwnerPda,
+          quoteMint: quoteMint,
+          baseMint: baseMint,
+          programQuoteTreasury: programQuoteTreasury,
+          programBaseTreasury: programBaseTreasury,
+          programAuthority: programAuthorityPda,
+          protocolFeeAuthority: protocolFeeAuthorityPda,
+          protocolFeeVault: protocolFeeVault,
+          baseSweepRecipient: creatorBaseAta,
+          cpAmmProgram: mockCpAmm.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(poolAccountMetas())
+        .signers([creator])
+        .rpc();
+
+      expect.fail("Should have failed for a non-authority signer");
+    } catch (error) {
+      expect(error.message).to.include("Signer is not the config authority");
+    }
+  });
+
+  it("Selects how base-token fees are handled", async () => {
+    const before = await program.account.configAccount.fetch(configPda);
+    expect(before.baseFeePolicy).to.deep.equal({ reject: {} });
+    expect(before.maxSwapSlippageBps).to.equal(100);
+
+    try {
+      await program.methods
+        .updateConfig(configUpdate({ baseFeePolicy: { swapToQuote: {} }, maxSwapSlippageBps: 10001 }))
+        .accounts({ authority: payer.publicKey, config: configPda })
+        .signers([payer])
+        .rpc();
+
+      expect.fail("Should have rejected slippage above 10000 bps");
+    } catch (error) {
+      expect(error.message).to.include("Basis points must not exceed 10000");
+    }
+
+    // Route base fees to the creator's base ATA
+    const creatorBaseAta = await getAssociatedTokenAddress(baseMint, creator.publicKey);
+    await program.methods
+      .updateConfig(configUpdate({ baseFeePolicy: { routeToCreator: {} }, creatorBaseAta }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+
+    let configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.baseFeePolicy).to.deep.equal({ routeToCreator: {} });
+    expect(configAccount.creatorBaseAta.toString()).to.equal(creatorBaseAta.toString());
+
+    // Swap base fees through the pool, accepting at most 0.5% below the spot price
+    await program.methods
+      .updateConfig(configUpdate({ baseFeePolicy: { swapToQuote: {} }, maxSwapSlippageBps: 50 }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+
+    configAccount = await program.account.configAccount.fetch(configPda);
+    expect(configAccount.baseFeePolicy).to.deep.equal({ swapToQuote: {} });
+    expect(configAccount.maxSwapSlippageBps).to.equal(50);
+
+    await program.methods
+      .updateConfig(configUpdate({ baseFeePolicy: { reject: {} } }))
+      .accounts({ authority: payer.publicKey, config: configPda })
+      .signers([payer])
+      .rpc();
+  });
+
+  it("Claims both fee tokens from a mock cp-amm position through CPI and guards its decommissioning", async () => {
+    // A pool and a position owned by the position owner PDA of the pool's config
+    const cpAmmPool = Keypair.generate();
+    const cpAmmPosition = Keypair.generate();
+    const [cpAmmConfigPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("investor_fee_config"), cpAmmPool.publicKey.toBuffer()],
+      program.programId
+    );
+    const [cpAmmPositionOwnerPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault"), cpAmmConfigPda.toBuffer(), Buffer.from("investor_fee_pos_owner")],
+      program.programId
+    );
+    const [poolAuthority] = PublicKey.findProgramAddressSync(
+      [Buffer.from("pool_authority"), cpAmmPool.publicKey.toBuffer()],
+      mockCpAmm.programId
+    );
+    const [quoteVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("quote_vault"), cpAmmPool.publicKey.toBuffer()],
+      mockCpAmm.programId
+    );
+    const [baseVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("base_vault"), cpAmmPool.publicKey.toBuffer()],
+      mockCpAmm.programId
+    );
+
+    await mockCpAmm.methods
+      .initializePool(new anchor.BN(1).shln(64), 30)
+      .accounts({
+        payer: payer.publicKey,
+        pool: cpAmmPool.publicKey,
+        poolAuthority,
+        quoteMint,
+        baseMint,
+        quoteVault,
+        baseVault,
+        quoteTokenProgram: TOKEN_PROGRAM_ID,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([payer, cpAmmPool])
+      .rpc();
+    await mockCpAmm.methods
+      .createPosition(cpAmmPositionOwnerPda)
+      .accounts({
+        payer: payer.publicKey,
+        pool: cpAmmPool.publicKey,
+        position: cpAmmPosition.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([payer, cpAmmPosition])
+      .rpc();
+
+    // A config for the pool that claims through the mock cp-amm program
+    await program.methods
+      .initializeHonoraryPosition(cpAmmPool.publicKey, -1000, 1000)
+      .accounts({
+        payer: payer.publicKey,
+        config: cpAmmConfigPda,
+        pool: cpAmmPool.publicKey,
+        quoteMint: quoteMint,
+        baseMint: baseMint,
+        position: cpAmmPosition.publicKey,
+        investorFeePositionOwnerPda: cpAmmPositionOwnerPda,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        rent: SYSVAR_RENT_PUBKEY,
+      })
+      .signers([payer])
+      .rpc();
+
+    const creatorBaseAta = (
+      await getOrCreateAssociatedTokenAccount(provider.connection, payer, baseMint, creator.publicKey)
+    ).address;
+    // Each config claims into treasuries of its own program authority
+    const [cpAmmProgramAuthority] = PublicKey.findProgramAddressSync(
+      [Buffer.from("program_authority"), cpAmmConfigPda.toBuffer()],
+      program.programId
+    );
+    const cpAmmQuoteTreasury = (
+      await getOrCreateAssociatedTokenAccount(provider.connection, payer, quoteMint, cpAmmProgramAuthority, true)
+    ).address;
+    const programBaseTreasury = (
+      await getOrCreateAssociatedTokenAccount(provider.connection, payer, baseMint, cpAmmProgramAuthority, true)
+    ).address;
+    await program.methods
+      .updateConfig(
+        configUpdate({
+          cpAmmProgram: mockCpAmm.programId,
+          quoteSweepRecipient: creatorQuoteAta,
+          baseSweepRecipient: creatorBaseAta,
+        })
+      )
+      .accounts({ authority: payer.publicKey, config: cpAmmConfigPda })
+      .signers([payer])
+      .rpc();
+
+    // The position accrues fees in both tokens
+    const payerQuoteAta = await getAssociatedTokenAddress(quoteMint, payer.publicKey);
+    const payerBaseAta = (
+      await getOrCreateAssociatedTokenAccount(provider.connection, payer, baseMint, payer.publicKey)
+    ).address;
+    await mintTo(provider.connection, payer, quoteMint, payerQuoteAta, payer, 300000);
+    await mintTo(provider.connection, payer, baseMint, payerBaseAta, payer, 50000);
+    await mockCpAmm.methods
+      .accrueFees(new anchor.BN(300000), new anchor.BN(50000))
+      .accounts({
+        funder: payer.publicKey,
+        pool: cpAmmPool.publicKey,
+        position: cpAmmPosition.publicKey,
+        funderQuote: payerQuoteAta,
+        funderBase: payerBaseAta,
+        quoteVault,
+        baseVault,
+        quoteMint,
+        baseMint,
+        quoteTokenProgram: TOKEN_PROGRAM_ID,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([payer])
+      .rpc();
+
+    const baseBefore = (await getAccount(provider.connection, creatorBaseAta)).amount;
+
+    // The crank claims both tokens through CPI and routes the base fees to the creator
+    await program.methods
+      .updateConfig(configUpdate({ baseFeePolicy: { routeToCreator: {} }, creatorBaseAta }))
+      .accounts({ authority: payer.publicKey, config: cpAmmConfigPda })
+      .signers([payer])
+      .rpc();
+    const cpAmmConfig = await program.account.configAccount.fetch(cpAmmConfigPda);
+    const dayId = Math.floor(Math.floor(Date.now() / 1000) / 86400);
+    const [progressPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("investor_fee_progress"), cpAmmConfigPda.toBuffer(), dayIdSeed(dayId)],
+      program.programId
+    );
+    const poolAccounts = [
+      { pubkey: cpAmmPool.publicKey, isWritable: false, isSigner: false },
+      { pubkey: poolAuthority, isWritable: false, isSigner: false },
+      { pubkey: quoteVault, isWritable: true, isSigner: false },
+      { pubkey: baseVault, isWritable: true, isSigner: false },
+    ];
+    await program.methods
+      .crankDistributePage([], new anchor.BN(1), 5000, null, new anchor.BN(0))
+      .accounts({
+        cranker: payer.publicKey,
+        config: cpAmmConfigPda,
+        progress: progressPda,
+        position: cpAmmPosition.publicKey,
+        investorFeePositionOwnerPda: cpAmmPositionOwnerPda,
+        quoteMint: quoteMint,
+        programQuoteTreasury: cpAmmQuoteTreasury,
+        programAuthority: cpAmmProgramAuthority,
+        crankerQuoteAta: null,
+        protocolConfig: protocolConfigPda,
+        protocolFeeAuthority: protocolFeeAuthorityPda,
+        protocolFeeVault: protocolFeeVault,
+        priceFeed: null,
+        basePriceFeed: null,
+        pool: cpAmmPool.publicKey,
+        baseMint: baseMint,
+        programBaseTreasury,
+        creatorBaseAta,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        capWindow: null,
+        cpAmmProgram: mockCpAmm.programId,
+        lockProgram: cpAmmConfig.lockProgram,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts(poolAccounts)
+      .signers([payer])
+      .rpc();
+
+    expect((await getAccount(provider.connection, quoteVault)).amount.toString()).to.equal("0");
+    expect((await getAccount(provider.connection, baseVault)).amount.toString()).to.equal("0");
+    const progressAccount = await program.account.progressAccount.fetch(progressPda);
+    expect(progressAccount.claimedQuoteForDay.toString()).to.equal("300000");
+    const baseAfter = (await getAccount(provider.connection, creatorBaseAta)).amount;
+    expect(Number(baseAfter - baseBefore)).to.equal(50000);
+
+    // Decommissioning needs a request and a notice period first, so investors see it coming
+    await program.methods
+      .requestDecommission()
+      .accounts({ authority: payer.publicKey, config: cpAmmConfigPda })
+      .signers([payer])
+      .rpc();
+    try {
+      await program.methods
+        .decommission(new anchor.BN(dayId))
+        .accounts({
+          authority: payer.publicKey,
+          config: cpAmmConfigPda,
+          protocolConfig: protocolConfigPda,
+          protocolFeeAuthority: protocolFeeAuthorityPda,
+          protocolFeeVault: protocolFeeVault,
+          progress: progressPda,
+          position: cpAmmPosition.publicKey,
+          investorFeePositionOwnerPda: cpAmmPositionOwnerPda,
+          quoteMint: quoteMint,
+          baseMint: baseMint,
+          programQuoteTreasury: cpAmmQuoteTreasury,
+          programBaseTreasury,
+          programAuthority: cpAmmProgramAuthority,
+          baseSweepRecipient: creatorBaseAta,
+          cpAmmProgram: mockCpAmm.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(poolAccounts)
+        .signers([payer])
+        .rpc();
+
+      expect.fail("Should have failed before the notice period elapsed");
+    } catch (error) {
+      expect(error.message).to.include("notice period");
+    }
+
+    const configAccount = await program.account.configAccount.fetch(cpAmmConfigPda);
+    expect(configAccount.closed).to.equal(false);
+    expect(configAccount.decommissionRequestedAt.toNumber()).to.be.greaterThan(0);
+    expect(configAccount.openDays).to.equal(1);
+  });
+});